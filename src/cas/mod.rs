@@ -0,0 +1,208 @@
+//! ## Cas
+//!
+//! content-addressed storage on top of any `RemoteFs`
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::fs::walk::create_dir_all;
+use crate::fs::{ChecksumAlgorithm, Metadata, ReadStream};
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Amount of hex digits of the hash used to name each shard directory
+const SHARD_WIDTH: usize = 2;
+
+/// A content-addressed store on a `RemoteFs`: objects are stored under `root`, named after the hash of their content.
+///
+/// Objects are sharded into directories named after the hash prefix (e.g. `root/ab/cd/abcdef...` with two shard levels),
+/// to keep directories small on file systems and spread keys on object stores
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CasStore {
+    root: PathBuf,
+    algorithm: ChecksumAlgorithm,
+    shards: usize,
+}
+
+impl CasStore {
+    /// Instantiates a new `CasStore` rooted at `root`, hashing objects with SHA-256 and sharding them on two levels
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            algorithm: ChecksumAlgorithm::Sha256,
+            shards: 2,
+        }
+    }
+
+    /// Set the algorithm to hash objects with
+    pub fn algorithm(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Set the amount of shard directory levels
+    pub fn shards(mut self, shards: usize) -> Self {
+        self.shards = shards;
+        self
+    }
+
+    /// Get the path of the object with `hash`.
+    /// Fails with `BadFile` if `hash` is not a lowercase hex hash long enough to be sharded
+    pub fn path(&self, hash: &str) -> RemoteResult<PathBuf> {
+        if hash.len() <= self.shards * SHARD_WIDTH
+            || !hash.bytes().all(|x| matches!(x, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::BadFile,
+                format!("invalid hash {hash:?}"),
+            ));
+        }
+        let mut path = self.root.clone();
+        for level in 0..self.shards {
+            path.push(&hash[level * SHARD_WIDTH..(level + 1) * SHARD_WIDTH]);
+        }
+        path.push(hash);
+        Ok(path)
+    }
+
+    /// Store the data read from `reader`, returning its hash.
+    /// If an object with the same hash is already stored, nothing is uploaded.
+    /// The data is hashed first, then the reader is rewound and uploaded to a temporary file,
+    /// which is moved in place once complete, so that interrupted uploads never leave a partial object behind
+    pub fn put<T, R>(&self, fs: &mut T, mut reader: R) -> RemoteResult<String>
+    where
+        T: RemoteFs + ?Sized,
+        R: Read + Seek + Send + 'static,
+    {
+        let io_err = |e| RemoteError::from_io(RemoteErrorType::IoError, e);
+        let hash = self.algorithm.hash_reader(&mut reader).map_err(io_err)?;
+        let path = self.path(&hash)?;
+        if fs.exists(path.as_path())? {
+            debug!("Object {} is already stored", hash);
+            return Ok(hash);
+        }
+        let size = reader.seek(SeekFrom::End(0)).map_err(io_err)?;
+        reader.seek(SeekFrom::Start(0)).map_err(io_err)?;
+        if let Some(parent) = path.parent() {
+            create_dir_all(fs, parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        debug!("Storing object {} ({} bytes)", hash, size);
+        fs.create_file(
+            tmp.as_path(),
+            &Metadata::default().size(size),
+            Box::new(reader),
+        )?;
+        if let Err(err) = fs.mov(tmp.as_path(), path.as_path()) {
+            error!("Failed to move object {} in place: {}", hash, err);
+            let _ = fs.remove_file(tmp.as_path());
+            return Err(err);
+        }
+        Ok(hash)
+    }
+
+    /// Returns whether the object with `hash` is stored
+    pub fn contains<T: RemoteFs + ?Sized>(&self, fs: &mut T, hash: &str) -> RemoteResult<bool> {
+        fs.exists(self.path(hash)?.as_path())
+    }
+
+    /// Open the object with `hash` for read. The stream must be finalized with `on_read`
+    pub fn get<T: RemoteFs + ?Sized>(&self, fs: &mut T, hash: &str) -> RemoteResult<ReadStream> {
+        fs.open(self.path(hash)?.as_path())
+    }
+
+    /// Write the content of the object with `hash` to `dest`, returning the amount of bytes written
+    pub fn get_to<T: RemoteFs + ?Sized>(
+        &self,
+        fs: &mut T,
+        hash: &str,
+        dest: Box<dyn Write + Send>,
+    ) -> RemoteResult<u64> {
+        fs.open_file(self.path(hash)?.as_path(), dest)
+    }
+
+    /// Remove the object with `hash`
+    pub fn remove<T: RemoteFs + ?Sized>(&self, fs: &mut T, hash: &str) -> RemoteResult<()> {
+        fs.remove_file(self.path(hash)?.as_path())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MemoryFs;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn should_shard_paths() {
+        let store = CasStore::new("/cas");
+        assert_eq!(
+            store.path(HELLO_SHA256).unwrap(),
+            PathBuf::from(format!("/cas/2c/f2/{HELLO_SHA256}"))
+        );
+        assert_eq!(
+            CasStore::new("/cas")
+                .algorithm(ChecksumAlgorithm::Crc32)
+                .shards(1)
+                .path("3610a686")
+                .unwrap(),
+            PathBuf::from("/cas/36/3610a686")
+        );
+        assert_eq!(
+            store.path("../../etc").unwrap_err().kind,
+            RemoteErrorType::BadFile
+        );
+        assert_eq!(
+            store.path("2cf2").unwrap_err().kind,
+            RemoteErrorType::BadFile
+        );
+    }
+
+    #[test]
+    fn should_put_and_get_objects() {
+        let mut fs = MemoryFs::connected().with_dir("/cas");
+        let store = CasStore::new("/cas");
+        let hash = store.put(&mut fs, Cursor::new(b"hello".to_vec())).unwrap();
+        assert_eq!(hash, HELLO_SHA256);
+        assert_eq!(
+            fs.content(format!("/cas/2c/f2/{HELLO_SHA256}")).unwrap(),
+            b"hello"
+        );
+        assert!(fs
+            .content(format!("/cas/2c/f2/{HELLO_SHA256}.tmp"))
+            .is_none());
+        assert_eq!(store.contains(&mut fs, HELLO_SHA256).unwrap(), true);
+        let mut content = Vec::new();
+        let mut stream = store.get(&mut fs, HELLO_SHA256).unwrap();
+        stream.read_to_end(&mut content).unwrap();
+        fs.on_read(stream).unwrap();
+        assert_eq!(content, b"hello");
+        store.remove(&mut fs, HELLO_SHA256).unwrap();
+        assert_eq!(store.contains(&mut fs, HELLO_SHA256).unwrap(), false);
+    }
+
+    #[test]
+    fn should_not_upload_stored_objects() {
+        let mut fs = MemoryFs::connected()
+            .with_dir("/cas")
+            .with_dir("/cas/2c")
+            .with_dir("/cas/2c/f2")
+            .with_file(format!("/cas/2c/f2/{HELLO_SHA256}"), b"hello")
+            .without("mov");
+        let store = CasStore::new("/cas");
+        assert_eq!(
+            store.put(&mut fs, Cursor::new(b"hello".to_vec())).unwrap(),
+            HELLO_SHA256
+        );
+        let err = store
+            .put(&mut fs, Cursor::new(b"world".to_vec()))
+            .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::UnsupportedFeature);
+        assert_eq!(fs.list_dir(Path::new("/cas/48/6e")).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,208 @@
+//! ## Adapter
+//!
+//! `std::io` adapters over `RemoteFs` streams
+
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use super::{ReadStream, RemoteError, RemoteErrorType, RemoteFs, RemoteResult, WriteStream};
+
+/// An `io::Read` over a file opened with `RemoteFs::open`, which finalizes the stream with `on_read`
+/// when `finish` is called or when dropped, so that it can be passed to APIs expecting `io::Read`.
+///
+/// Errors raised while finalizing on drop can't be reported, so prefer calling `finish` explicitly
+pub struct IoReader<'a, T: RemoteFs + ?Sized> {
+    fs: &'a mut T,
+    stream: Option<ReadStream>,
+}
+
+impl<'a, T: RemoteFs + ?Sized> IoReader<'a, T> {
+    /// Instantiates a new `IoReader` over `stream`, which must have been opened on `fs`
+    pub fn new(fs: &'a mut T, stream: ReadStream) -> Self {
+        Self {
+            fs,
+            stream: Some(stream),
+        }
+    }
+
+    /// Finalize the stream
+    pub fn finish(mut self) -> RemoteResult<()> {
+        match self.stream.take() {
+            Some(stream) => self.fs.on_read(stream),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: RemoteFs + ?Sized> Read for IoReader<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.stream.as_mut() {
+            Some(stream) => stream.read(buf),
+            None => Ok(0),
+        }
+    }
+}
+
+impl<T: RemoteFs + ?Sized> Seek for IoReader<'_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self.stream.as_mut() {
+            Some(stream) => stream.seek(pos),
+            None => Err(io::Error::from(io::ErrorKind::NotConnected)),
+        }
+    }
+}
+
+impl<T: RemoteFs + ?Sized> Drop for IoReader<'_, T> {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            if let Err(err) = self.fs.on_read(stream) {
+                error!("Failed to finalize read stream: {}", err);
+            }
+        }
+    }
+}
+
+/// An iterator over the lines of a file opened with `RemoteFs::open`, returned by `RemoteFs::read_lines`.
+/// Lines are decoded lossily from UTF-8 and returned without the line terminator (`\n` or `\r\n`).
+/// The stream is finalized with `on_read` once the end of the file is reached, or when dropped
+pub struct Lines<'a, T: RemoteFs + ?Sized> {
+    reader: Option<BufReader<IoReader<'a, T>>>,
+}
+
+impl<'a, T: RemoteFs + ?Sized> Lines<'a, T> {
+    /// Instantiates a new `Lines` over `stream`, which must have been opened on `fs`
+    pub fn new(fs: &'a mut T, stream: ReadStream) -> Self {
+        Self {
+            reader: Some(BufReader::new(IoReader::new(fs, stream))),
+        }
+    }
+}
+
+impl<T: RemoteFs + ?Sized> Iterator for Lines<'_, T> {
+    type Item = RemoteResult<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = self.reader.as_mut()?;
+        let mut line = Vec::new();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => self
+                .reader
+                .take()
+                .unwrap()
+                .into_inner()
+                .finish()
+                .err()
+                .map(Err),
+            Ok(_) => {
+                if line.ends_with(b"\n") {
+                    line.pop();
+                    if line.ends_with(b"\r") {
+                        line.pop();
+                    }
+                }
+                Some(Ok(String::from_utf8_lossy(&line).into_owned()))
+            }
+            Err(err) => {
+                self.reader = None;
+                Some(Err(RemoteError::from_io(RemoteErrorType::IoError, err)))
+            }
+        }
+    }
+}
+
+/// An `io::Write` over a file opened with `RemoteFs::create` or `RemoteFs::append`, which finalizes the stream
+/// with `on_written` when `finish` is called or when dropped, so that it can be passed to APIs expecting `io::Write`.
+///
+/// Errors raised while finalizing on drop can't be reported, so prefer calling `finish` explicitly
+pub struct IoWriter<'a, T: RemoteFs + ?Sized> {
+    fs: &'a mut T,
+    stream: Option<WriteStream>,
+}
+
+impl<'a, T: RemoteFs + ?Sized> IoWriter<'a, T> {
+    /// Instantiates a new `IoWriter` over `stream`, which must have been opened on `fs`
+    pub fn new(fs: &'a mut T, stream: WriteStream) -> Self {
+        Self {
+            fs,
+            stream: Some(stream),
+        }
+    }
+
+    /// Flush and finalize the stream
+    pub fn finish(mut self) -> RemoteResult<()> {
+        match self.stream.take() {
+            Some(stream) => self.fs.on_written(stream),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: RemoteFs + ?Sized> Write for IoWriter<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stream.as_mut() {
+            Some(stream) => stream.write(buf),
+            None => Err(io::Error::from(io::ErrorKind::NotConnected)),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stream.as_mut() {
+            Some(stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: RemoteFs + ?Sized> Drop for IoWriter<'_, T> {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            if let Err(err) = self.fs.on_written(stream) {
+                error!("Failed to finalize write stream: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::Metadata;
+    use crate::mock::MemoryFs;
+
+    #[test]
+    fn should_read_through_io_reader() {
+        let mut client = MemoryFs::connected().with_file("/a.txt", b"hello");
+        let stream = client.open(Path::new("/a.txt")).unwrap();
+        let mut reader = IoReader::new(&mut client, stream);
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "hello");
+        assert!(reader.finish().is_ok());
+    }
+
+    #[test]
+    fn should_iterate_over_lines() {
+        let mut client = MemoryFs::connected().with_file("/a.log", b"one\r\ntwo\n\nthree");
+        let stream = client.open(Path::new("/a.log")).unwrap();
+        let lines: Vec<String> = Lines::new(&mut client, stream)
+            .collect::<RemoteResult<_>>()
+            .unwrap();
+        assert_eq!(lines, vec!["one", "two", "", "three"]);
+    }
+
+    #[test]
+    fn should_write_through_io_writer() {
+        let mut client = MemoryFs::connected();
+        let stream = client
+            .create(Path::new("/a.txt"), &Metadata::default())
+            .unwrap();
+        let mut writer = IoWriter::new(&mut client, stream);
+        writer.write_all(b"hello").unwrap();
+        drop(writer);
+        assert_eq!(client.content("/a.txt").unwrap(), b"hello");
+    }
+}
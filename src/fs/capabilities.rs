@@ -0,0 +1,164 @@
+//! ## Capabilities
+//!
+//! features supported by the remote server
+
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime};
+
+/// Describes which features are supported by the remote server, so that callers can branch on actual
+/// server support instead of attempting an operation and handling `UnsupportedFeature`.
+///
+/// Flags default to `false`, which means the feature is not known to be supported
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether data can be appended to existing files
+    pub append: bool,
+    /// Whether commands can be executed on the remote with `exec`
+    pub exec: bool,
+    /// Whether transfers can be resumed from an offset (e.g. FTP `REST STREAM`)
+    pub resume: bool,
+    /// Whether the modification time of a file can be set (e.g. FTP `MFMT`)
+    pub set_modified: bool,
+    /// Whether the size of a single file can be queried without listing its parent (e.g. FTP `SIZE`)
+    pub size: bool,
+    /// Whether symbolic links are supported
+    pub symlink: bool,
+    /// Whether paths are exchanged as UTF-8 (e.g. FTP `UTF8`)
+    pub utf8: bool,
+    /// Precision of file times on the remote (e.g. 1 second for SFTP v3 and FTP `MDTM`), if known.
+    /// Sync logic should use it as the tolerance when comparing modification times
+    pub time_granularity: Option<Duration>,
+    /// Raw features advertised by the server (e.g. the FTP `FEAT` reply), upper case and without parameters
+    pub features: BTreeSet<String>,
+}
+
+impl Capabilities {
+    /// Set whether append is supported
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Set whether exec is supported
+    pub fn exec(mut self, exec: bool) -> Self {
+        self.exec = exec;
+        self
+    }
+
+    /// Set whether resuming transfers is supported
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Set whether setting the modification time is supported
+    pub fn set_modified(mut self, set_modified: bool) -> Self {
+        self.set_modified = set_modified;
+        self
+    }
+
+    /// Set whether querying the size of a single file is supported
+    pub fn size(mut self, size: bool) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Set whether symbolic links are supported
+    pub fn symlink(mut self, symlink: bool) -> Self {
+        self.symlink = symlink;
+        self
+    }
+
+    /// Set whether paths are exchanged as UTF-8
+    pub fn utf8(mut self, utf8: bool) -> Self {
+        self.utf8 = utf8;
+        self
+    }
+
+    /// Set precision of file times on the remote
+    pub fn time_granularity(mut self, granularity: Duration) -> Self {
+        self.time_granularity = Some(granularity);
+        self
+    }
+
+    /// Returns whether two file times are the same, within the time granularity of the remote
+    pub fn same_time(&self, a: SystemTime, b: SystemTime) -> bool {
+        let diff = a.duration_since(b).unwrap_or_else(|e| e.duration());
+        diff < self
+            .time_granularity
+            .unwrap_or_default()
+            .max(Duration::from_nanos(1))
+    }
+
+    /// Add a raw feature advertised by the server
+    pub fn feature<S: AsRef<str>>(mut self, feature: S) -> Self {
+        self.features.insert(feature.as_ref().to_ascii_uppercase());
+        self
+    }
+
+    /// Returns whether the server advertised `feature` (case insensitive)
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature.to_ascii_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::UNIX_EPOCH;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_create_capabilities() {
+        let capabilities = Capabilities::default();
+        assert_eq!(capabilities.append, false);
+        assert_eq!(capabilities.exec, false);
+        assert_eq!(capabilities.resume, false);
+        assert_eq!(capabilities.set_modified, false);
+        assert_eq!(capabilities.size, false);
+        assert_eq!(capabilities.symlink, false);
+        assert_eq!(capabilities.utf8, false);
+        assert!(capabilities.time_granularity.is_none());
+        assert!(capabilities.features.is_empty());
+        let capabilities = Capabilities::default()
+            .append(true)
+            .exec(true)
+            .resume(true)
+            .set_modified(true)
+            .size(true)
+            .symlink(true)
+            .utf8(true)
+            .time_granularity(Duration::from_secs(1))
+            .feature("mlsd");
+        assert_eq!(capabilities.append, true);
+        assert_eq!(capabilities.exec, true);
+        assert_eq!(capabilities.resume, true);
+        assert_eq!(capabilities.set_modified, true);
+        assert_eq!(capabilities.size, true);
+        assert_eq!(capabilities.symlink, true);
+        assert_eq!(capabilities.utf8, true);
+        assert_eq!(capabilities.time_granularity, Some(Duration::from_secs(1)));
+        assert_eq!(capabilities.has_feature("MLSD"), true);
+        assert_eq!(capabilities.has_feature("Mlsd"), true);
+        assert_eq!(capabilities.has_feature("MFMT"), false);
+    }
+
+    #[test]
+    fn should_compare_times_within_granularity() {
+        let t = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        let truncated = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let capabilities = Capabilities::default();
+        assert_eq!(capabilities.same_time(t, t), true);
+        assert_eq!(capabilities.same_time(t, truncated), false);
+        let capabilities = capabilities.time_granularity(Duration::from_secs(1));
+        assert_eq!(capabilities.same_time(t, truncated), true);
+        assert_eq!(capabilities.same_time(truncated, t), true);
+        assert_eq!(
+            capabilities.same_time(t, truncated + Duration::from_secs(2)),
+            false
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! ## Crc32
+//!
+//! CRC-32 (IEEE 802.3) digest
+
+use super::Digest;
+
+/// Lookup table for the reflected polynomial 0xEDB88320
+const TABLE: [u32; 256] = make_table();
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-32 (IEEE 802.3) digest
+#[derive(Debug, Clone)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self { crc: 0xFFFF_FFFF }
+    }
+}
+
+impl Crc32 {
+    /// Returns the checksum of the data fed so far
+    pub fn value(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl Digest for Crc32 {
+    fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.crc = TABLE[((self.crc ^ *byte as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    fn hex_digest(&self) -> String {
+        format!("{:08x}", self.value())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_compute_crc32() {
+        assert_eq!(Crc32::default().hex_digest(), "00000000");
+        let mut crc = Crc32::default();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.value(), 0xCBF4_3926);
+        let mut crc = Crc32::default();
+        crc.update(b"The quick brown fox jumps over the lazy dog");
+        assert_eq!(crc.hex_digest(), "414fa339");
+    }
+}
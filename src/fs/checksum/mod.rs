@@ -0,0 +1,83 @@
+//! ## Checksum
+//!
+//! digest algorithms used to compute checksums of the data flowing through streams
+
+use std::io::{self, Read};
+
+mod crc32;
+mod sha256;
+
+pub use self::crc32::Crc32;
+pub use self::sha256::Sha256;
+
+/// A digest algorithm which can be fed incrementally
+pub trait Digest: Send {
+    /// Feed `data` to the digest
+    fn update(&mut self, data: &[u8]);
+
+    /// Returns the lowercase hex digest of all the data fed so far
+    fn hex_digest(&self) -> String;
+}
+
+/// The checksum algorithms supported out of the box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumAlgorithm {
+    /// CRC-32 (IEEE 802.3), as used by zip and gzip
+    Crc32,
+    /// SHA-256
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// Instantiates a new digest for this algorithm
+    pub fn digest(&self) -> Box<dyn Digest> {
+        match self {
+            Self::Crc32 => Box::<Crc32>::default(),
+            Self::Sha256 => Box::<Sha256>::default(),
+        }
+    }
+
+    /// Compute the hex checksum of all the data read from `reader`
+    pub fn hash_reader<R: Read + ?Sized>(&self, reader: &mut R) -> io::Result<String> {
+        let mut digest = self.digest();
+        let mut buffer = [0u8; 8192];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => return Ok(digest.hex_digest()),
+                Ok(n) => digest.update(&buffer[..n]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_make_digest_for_algorithm() {
+        let mut digest = ChecksumAlgorithm::Crc32.digest();
+        digest.update(b"123456789");
+        assert_eq!(digest.hex_digest(), "cbf43926");
+        let mut digest = ChecksumAlgorithm::Sha256.digest();
+        digest.update(b"abc");
+        assert_eq!(
+            digest.hex_digest(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn should_hash_reader() {
+        let mut reader = io::Cursor::new(b"123456789".to_vec());
+        assert_eq!(
+            ChecksumAlgorithm::Crc32.hash_reader(&mut reader).unwrap(),
+            "cbf43926"
+        );
+    }
+}
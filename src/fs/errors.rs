@@ -3,7 +3,10 @@
 //! errors types
 
 use std::error::Error as StdError;
-use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{fmt, io};
 
 use thiserror::Error;
 
@@ -11,10 +14,16 @@ use thiserror::Error;
 pub type RemoteResult<T> = Result<T, RemoteError>;
 
 /// RemoteError defines the possible errors available for a file transfer
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone)]
 pub struct RemoteError {
     pub kind: RemoteErrorType,
     pub msg: Option<String>,
+    /// The operation which failed (e.g. `stat`), if set with `with_op`
+    op: Option<String>,
+    /// The path the failed operation was made on, if set with `with_path`
+    path: Option<PathBuf>,
+    /// The underlying error, kept to allow callers to downcast it (e.g. to `ssh2::Error`)
+    source: Option<Arc<dyn StdError + Send + Sync>>,
 }
 
 /// RemoteErrorType defines the possible errors available for a file transfer
@@ -34,12 +43,20 @@ pub enum RemoteErrorType {
     StatFailed,
     #[error("bad file")]
     BadFile,
+    #[error("operation cancelled")]
+    Cancelled,
+    #[error("file was changed concurrently")]
+    Conflict,
+    #[error("can't move across file systems")]
+    CrossDevice,
     #[error("directory already exists")]
     DirectoryAlreadyExists,
     #[error("directory is not empty")]
     DirectoryNotEmpty,
     #[error("failed to create file")]
     FileCreateDenied,
+    #[error("data was transferred, but the file could not be finalized")]
+    FinalizeFailed,
     #[error("failed to open file")]
     CouldNotOpenFile,
     #[error("failed to remove file")]
@@ -52,8 +69,16 @@ pub enum RemoteErrorType {
     PexError,
     #[error("protocol error")]
     ProtocolError,
+    #[error("quota exceeded")]
+    QuotaExceeded,
+    #[error("symbolic link loop detected")]
+    SymlinkLoop,
+    #[error("transferred data doesn't match the source")]
+    TransferVerificationFailed,
     #[error("not connected yet")]
     NotConnected,
+    #[error("object is archived and must be restored first")]
+    ObjectArchived,
     #[error("unsupported feature")]
     UnsupportedFeature,
 }
@@ -61,7 +86,13 @@ pub enum RemoteErrorType {
 impl RemoteError {
     /// Instantiates a new RemoteError
     pub fn new(kind: RemoteErrorType) -> RemoteError {
-        RemoteError { kind, msg: None }
+        RemoteError {
+            kind,
+            msg: None,
+            op: None,
+            path: None,
+            source: None,
+        }
     }
 
     /// Instantiates a new RemoteError with message
@@ -70,10 +101,123 @@ impl RemoteError {
         err.msg = Some(msg.to_string());
         err
     }
+
+    /// Instantiates a new RemoteError caused by `source`, which is used as message and returned by `Error::source`
+    pub fn from_source<E>(kind: RemoteErrorType, source: E) -> RemoteError
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        let mut err: RemoteError = RemoteError::new_ex(kind, &source);
+        err.source = Some(Arc::new(source));
+        err
+    }
+
+    /// Instantiates a new RemoteError from the io error `err`.
+    /// If `err` carries a `RemoteError` (see `From<RemoteError> for io::Error`), that error is returned;
+    /// otherwise a `kind` error caused by `err` is returned
+    pub(crate) fn from_io(kind: RemoteErrorType, err: io::Error) -> RemoteError {
+        match err.get_ref().and_then(|x| x.downcast_ref::<RemoteError>()) {
+            Some(inner) => inner.clone(),
+            None => RemoteError::from_source(kind, err),
+        }
+    }
+
+    /// Instantiates a new `FinalizeFailed` RemoteError, for a stream on which `bytes_written` were written
+    /// before finalizing it failed with `error`
+    pub fn finalize_failed(bytes_written: u64, error: RemoteError) -> RemoteError {
+        RemoteError::from_source(
+            RemoteErrorType::FinalizeFailed,
+            FinalizeError {
+                bytes_written,
+                error,
+            },
+        )
+    }
+
+    /// Set the operation which failed (e.g. `stat`), which is reported by `Display`
+    pub fn with_op<S: ToString>(mut self, op: S) -> Self {
+        self.op = Some(op.to_string());
+        self
+    }
+
+    /// Set the path the failed operation was made on, which is reported by `Display`
+    pub fn with_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Get the operation which failed, if set
+    pub fn op(&self) -> Option<&str> {
+        self.op.as_deref()
+    }
+
+    /// Get the path the failed operation was made on, if set
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Set `op` and `path` as context, unless the error already carries a context,
+    /// so that the innermost operation which failed is reported
+    pub(crate) fn or_context(self, op: &str, path: &Path) -> Self {
+        match self.op.is_none() && self.path.is_none() {
+            true => self.with_op(op).with_path(path),
+            false => self,
+        }
+    }
+
+    /// If this is a `FinalizeFailed` error, get the amount of bytes written before finalization failed
+    pub fn bytes_written(&self) -> Option<u64> {
+        self.source
+            .as_ref()
+            .and_then(|x| x.downcast_ref::<FinalizeError>())
+            .map(|x| x.bytes_written)
+    }
+}
+
+/// Run `f`, setting `op` on `path` as context of the error it returns, unless the error already carries a context.
+/// Used by the default implementations of `RemoteFs`, which are made of several operations
+pub(crate) fn in_context<T, F>(op: &str, path: &Path, f: F) -> RemoteResult<T>
+where
+    F: FnOnce() -> RemoteResult<T>,
+{
+    f().map_err(|err| err.or_context(op, path))
+}
+
+/// Source of a `FinalizeFailed` error, reporting how many bytes were written before finalization failed
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{bytes_written} bytes written; {error}")]
+pub struct FinalizeError {
+    /// Amount of bytes written to the stream before finalizing it
+    pub bytes_written: u64,
+    /// The error returned while finalizing the stream
+    pub error: RemoteError,
+}
+
+/// Errors are compared by kind and message only, since the source can't be compared;
+/// the operation and the path are context and are not compared either
+impl PartialEq for RemoteError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.msg == other.msg
+    }
+}
+
+impl Eq for RemoteError {}
+
+impl Hash for RemoteError {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+        self.msg.hash(state);
+    }
 }
 
 impl fmt::Display for RemoteError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.op.as_deref(), self.path.as_deref()) {
+            (Some(op), Some(path)) => write!(f, "{} {}: ", op, path.display())?,
+            (Some(op), None) => write!(f, "{op}: ")?,
+            (None, Some(path)) => write!(f, "{}: ", path.display())?,
+            (None, None) => {}
+        }
         match &self.msg {
             Some(msg) => write!(f, "{} ({})", self.kind, msg),
             None => write!(f, "{}", self.kind),
@@ -83,7 +227,37 @@ impl fmt::Display for RemoteError {
 
 impl StdError for RemoteError {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        Some(&self.kind)
+        match self.source.as_ref() {
+            Some(source) => Some(source.as_ref()),
+            None => Some(&self.kind),
+        }
+    }
+}
+
+impl From<RemoteErrorType> for io::ErrorKind {
+    fn from(kind: RemoteErrorType) -> Self {
+        match kind {
+            RemoteErrorType::AlreadyConnected => io::ErrorKind::AlreadyExists,
+            RemoteErrorType::AuthenticationFailed | RemoteErrorType::PexError => {
+                io::ErrorKind::PermissionDenied
+            }
+            RemoteErrorType::BadAddress => io::ErrorKind::InvalidInput,
+            RemoteErrorType::ConnectionError => io::ErrorKind::ConnectionRefused,
+            RemoteErrorType::DirectoryAlreadyExists => io::ErrorKind::AlreadyExists,
+            RemoteErrorType::NoSuchFileOrDirectory => io::ErrorKind::NotFound,
+            RemoteErrorType::NotConnected => io::ErrorKind::NotConnected,
+            RemoteErrorType::ProtocolError | RemoteErrorType::TransferVerificationFailed => {
+                io::ErrorKind::InvalidData
+            }
+            RemoteErrorType::UnsupportedFeature => io::ErrorKind::Unsupported,
+            _ => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl From<RemoteError> for io::Error {
+    fn from(err: RemoteError) -> Self {
+        io::Error::new(io::ErrorKind::from(err.kind), err)
     }
 }
 
@@ -146,6 +320,21 @@ mod test {
             format!("{}", RemoteError::new(RemoteErrorType::ProtocolError)),
             String::from("protocol error")
         );
+        assert_eq!(
+            format!("{}", RemoteError::new(RemoteErrorType::QuotaExceeded)),
+            String::from("quota exceeded")
+        );
+        assert_eq!(
+            format!("{}", RemoteError::new(RemoteErrorType::SymlinkLoop)),
+            String::from("symbolic link loop detected")
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                RemoteError::new(RemoteErrorType::TransferVerificationFailed)
+            ),
+            String::from("transferred data doesn't match the source")
+        );
         assert_eq!(
             format!("{}", RemoteError::new(RemoteErrorType::SslError)),
             String::from("SSL error")
@@ -154,6 +343,22 @@ mod test {
             format!("{}", RemoteError::new(RemoteErrorType::NotConnected)),
             String::from("not connected yet")
         );
+        assert_eq!(
+            format!("{}", RemoteError::new(RemoteErrorType::Cancelled)),
+            String::from("operation cancelled")
+        );
+        assert_eq!(
+            format!("{}", RemoteError::new(RemoteErrorType::Conflict)),
+            String::from("file was changed concurrently")
+        );
+        assert_eq!(
+            format!("{}", RemoteError::new(RemoteErrorType::CrossDevice)),
+            String::from("can't move across file systems")
+        );
+        assert_eq!(
+            format!("{}", RemoteError::new(RemoteErrorType::ObjectArchived)),
+            String::from("object is archived and must be restored first")
+        );
         assert_eq!(
             format!("{}", RemoteError::new(RemoteErrorType::UnsupportedFeature)),
             String::from("unsupported feature")
@@ -162,9 +367,121 @@ mod test {
         assert_eq!(err.kind, RemoteErrorType::UnsupportedFeature);
     }
 
+    #[test]
+    fn should_format_errors_with_context() {
+        let err = RemoteError::new_ex(RemoteErrorType::ProtocolError, "Failure");
+        assert_eq!(
+            err.clone()
+                .with_op("stat")
+                .with_path("/home/a.txt")
+                .to_string(),
+            "stat /home/a.txt: protocol error (Failure)"
+        );
+        assert_eq!(
+            err.clone().with_op("connect").to_string(),
+            "connect: protocol error (Failure)"
+        );
+        assert_eq!(
+            RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)
+                .with_path("/tmp")
+                .to_string(),
+            "/tmp: no such file or directory"
+        );
+        let err = err.with_op("stat");
+        assert_eq!(err.op(), Some("stat"));
+        assert!(err.path().is_none());
+    }
+
+    #[test]
+    fn should_keep_innermost_context() {
+        let err = in_context::<(), _>("remove_dir_all", Path::new("/home"), || {
+            Err(RemoteError::new(RemoteErrorType::PexError))
+        })
+        .unwrap_err();
+        assert_eq!(err.op(), Some("remove_dir_all"));
+        assert_eq!(err.path(), Some(Path::new("/home")));
+        let err = in_context::<(), _>("remove_dir_all", Path::new("/home"), || {
+            Err(RemoteError::new(RemoteErrorType::PexError).with_path("/home/a.txt"))
+        })
+        .unwrap_err();
+        assert_eq!(err.op(), None);
+        assert_eq!(err.path(), Some(Path::new("/home/a.txt")));
+    }
+
     #[test]
     fn should_report_error_cause() {
         let error = RemoteError::new(RemoteErrorType::UnsupportedFeature);
         assert!(error.source().is_some());
     }
+
+    #[test]
+    fn should_convert_into_io_error() {
+        let err = io::Error::from(RemoteError::new_ex(
+            RemoteErrorType::NoSuchFileOrDirectory,
+            "/tmp/a.txt",
+        ));
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "no such file or directory (/tmp/a.txt)");
+        let inner = err.into_inner().unwrap().downcast::<RemoteError>().unwrap();
+        assert_eq!(inner.kind, RemoteErrorType::NoSuchFileOrDirectory);
+        assert_eq!(
+            io::Error::from(RemoteError::new(RemoteErrorType::PexError)).kind(),
+            io::ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            io::Error::from(RemoteError::new(RemoteErrorType::SslError)).kind(),
+            io::ErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn should_chain_error_source() {
+        let io_err = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        let err = RemoteError::from_source(RemoteErrorType::ConnectionError, io_err);
+        assert_eq!(err.to_string(), "connection error (timed out)");
+        let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(
+            err.clone(),
+            RemoteError::new_ex(RemoteErrorType::ConnectionError, "timed out")
+        );
+        let err = RemoteError::new(RemoteErrorType::PexError);
+        assert!(err
+            .source()
+            .unwrap()
+            .downcast_ref::<RemoteErrorType>()
+            .is_some());
+    }
+
+    #[test]
+    fn should_make_error_from_io_error() {
+        let err = RemoteError::from_io(
+            RemoteErrorType::IoError,
+            io::Error::from(RemoteError::new(RemoteErrorType::QuotaExceeded)),
+        );
+        assert_eq!(err.kind, RemoteErrorType::QuotaExceeded);
+        let err = RemoteError::from_io(
+            RemoteErrorType::IoError,
+            io::Error::new(io::ErrorKind::UnexpectedEof, "eof"),
+        );
+        assert_eq!(err.kind, RemoteErrorType::IoError);
+        let source = err.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn should_make_finalize_error() {
+        let err =
+            RemoteError::finalize_failed(1024, RemoteError::new(RemoteErrorType::ProtocolError));
+        assert_eq!(err.kind, RemoteErrorType::FinalizeFailed);
+        assert_eq!(err.bytes_written(), Some(1024));
+        assert_eq!(
+            err.to_string(),
+            "data was transferred, but the file could not be finalized (1024 bytes written; protocol error)"
+        );
+        assert_eq!(
+            RemoteError::new(RemoteErrorType::ProtocolError).bytes_written(),
+            None
+        );
+    }
 }
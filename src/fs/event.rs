@@ -0,0 +1,37 @@
+//! ## Event
+//!
+//! events emitted by recursive operations and while connecting
+
+use std::path::PathBuf;
+
+/// An event emitted while a recursive operation (such as `remove_dir_all_with`) is running,
+/// which can be used to report the progress of the operation
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FsEvent {
+    /// The operation has entered a directory
+    Entered(PathBuf),
+    /// A file or directory has been removed
+    Removed(PathBuf),
+    /// A file has been copied from `src` to `dest`
+    Copied {
+        src: PathBuf,
+        dest: PathBuf,
+        bytes: u64,
+    },
+}
+
+/// A phase of the connection to the remote server, reported by `connect_with`.
+/// Clients report only the phases which apply to their protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectPhase {
+    /// Resolving the address of the server
+    Resolving,
+    /// Establishing the connection with the server
+    Connecting,
+    /// Negotiating the protocol (e.g. SSH or TLS handshake)
+    Handshaking,
+    /// Authenticating the user
+    Authenticating,
+    /// The client is connected and authenticated
+    Connected,
+}
@@ -0,0 +1,67 @@
+//! ## Archived
+//!
+//! extension trait for remote file systems with archive storage tiers (e.g. S3 Glacier)
+
+use std::path::Path;
+
+use crate::{RemoteFs, RemoteResult};
+
+/// Defines the methods to restore files stored in an archive storage tier (such as Aws S3 Glacier),
+/// which can't be read until they are restored.
+///
+/// Opening an archived file should fail with `RemoteErrorType::ObjectArchived`; then the caller can call `restore`
+/// and poll `archive_status` until the file becomes retrievable.
+pub trait ArchivedFs: RemoteFs {
+    /// Get the archive status of the file at `path`
+    fn archive_status(&mut self, path: &Path) -> RemoteResult<ArchiveStatus>;
+
+    /// Request the restore of the archived file at `path`, which will be kept available for `days`.
+    /// The restore is asynchronous: poll `archive_status` to know when the file is retrievable.
+    fn restore(&mut self, path: &Path, days: u32, tier: RestoreTier) -> RemoteResult<()>;
+}
+
+/// Describes whether an archived file can be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveStatus {
+    /// The file is not archived, or has already been restored, and can be read
+    Available,
+    /// The file is archived and must be restored before being read
+    Archived,
+    /// A restore has been requested and is in progress
+    Restoring,
+}
+
+impl ArchiveStatus {
+    /// Returns whether the file can be read
+    pub fn is_available(&self) -> bool {
+        matches!(self, Self::Available)
+    }
+}
+
+/// Describes the retrieval tier used to restore an archived file; faster tiers are usually more expensive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RestoreTier {
+    /// Fastest retrieval (minutes)
+    Expedited,
+    /// Standard retrieval (hours)
+    #[default]
+    Standard,
+    /// Cheapest retrieval (up to days)
+    Bulk,
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_tell_whether_archived_file_is_available() {
+        assert!(ArchiveStatus::Available.is_available());
+        assert!(!ArchiveStatus::Archived.is_available());
+        assert!(!ArchiveStatus::Restoring.is_available());
+        assert_eq!(RestoreTier::default(), RestoreTier::Standard);
+    }
+}
@@ -0,0 +1,11 @@
+//! ## Ext
+//!
+//! extension traits for `RemoteFs`, implemented only by the clients which support the related protocol features
+
+mod archived;
+mod raw;
+mod versioned;
+
+pub use self::archived::{ArchiveStatus, ArchivedFs, RestoreTier};
+pub use self::raw::RawFs;
+pub use self::versioned::VersionedFs;
@@ -0,0 +1,59 @@
+//! ## Raw
+//!
+//! extension trait exposing the underlying protocol client
+
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Defines the methods to reach the underlying protocol client (e.g. the sftp session or the ftp stream)
+/// and to send raw commands to the remote server.
+///
+/// ### ⚠️ Warning
+///
+/// This trait is **NOT PORTABLE**: whatever is done through it depends on the protocol in use, and it may leave
+/// the client in a state it doesn't expect (e.g. changing the working directory behind its back).
+/// Use it only to reach a protocol feature which is not exposed by `RemoteFs`.
+pub trait RawFs: RemoteFs {
+    /// The underlying protocol client
+    type Raw: ?Sized;
+
+    /// Get a mutable reference to the underlying protocol client.
+    /// Returns `NotConnected` if the client hasn't established a connection yet.
+    fn raw(&mut self) -> RemoteResult<&mut Self::Raw>;
+
+    /// Send a raw command to the remote server (e.g. a `SITE` command on FTP).
+    /// Returns the response code and message
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method returns `UnsupportedFeature`, since not all protocols have a notion of raw command
+    fn raw_command(&mut self, _cmd: &str) -> RemoteResult<(u32, String)> {
+        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MockRemoteFs;
+
+    impl RawFs for MockRemoteFs {
+        type Raw = Self;
+
+        fn raw(&mut self) -> RemoteResult<&mut Self::Raw> {
+            Ok(self)
+        }
+    }
+
+    #[test]
+    fn should_not_support_raw_commands_by_default() {
+        let mut client = MockRemoteFs {};
+        assert!(client.raw().is_ok());
+        assert_eq!(
+            client.raw_command("SITE HELP").unwrap_err().kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! ## Versioned
+//!
+//! extension trait for remote file systems supporting object versioning (e.g. S3 with versioning enabled)
+
+use std::path::Path;
+
+use crate::fs::{copy_stream, File, ReadStream, TransferOpts};
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Defines the methods to access the versions of a file on remote file systems which support object versioning,
+/// such as Aws S3 buckets with versioning enabled.
+pub trait VersionedFs: RemoteFs {
+    /// List all the versions of the file at `path`, the latest first.
+    /// The version id of each entry is set in `metadata.version`
+    fn list_versions(&mut self, path: &Path) -> RemoteResult<Vec<File>>;
+
+    /// Open the file at `path` for read at the provided `version`.
+    /// The stream must be finalized with `on_read` as any other stream returned by `open`.
+    fn open_version(&mut self, path: &Path, version: &str) -> RemoteResult<ReadStream>;
+
+    /// Permanently delete the provided `version` of the file at `path`
+    fn delete_version(&mut self, path: &Path, version: &str) -> RemoteResult<()>;
+
+    /// Restore the provided `version` of the file at `path`, making it the latest version.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method reads the version with `open_version` and writes it back to `path` with `create`.
+    /// Implement this method when the protocol supports a server-side copy
+    fn restore_version(&mut self, path: &Path, version: &str) -> RemoteResult<()> {
+        if self.is_connected() {
+            let metadata = self
+                .list_versions(path)?
+                .into_iter()
+                .find(|x| x.metadata().version.as_deref() == Some(version))
+                .map(|x| x.metadata)
+                .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))?;
+            debug!("Restoring version {} of {}", version, path.display());
+            let mut reader = self.open_version(path, version)?;
+            let mut writer = self.create(path, &metadata)?;
+            let sz = copy_stream(&mut reader, &mut writer, &TransferOpts::default())?;
+            self.on_read(reader)?;
+            self.on_written(writer)?;
+            trace!("Restored {} bytes", sz);
+            Ok(())
+        } else {
+            Err(RemoteError::new(RemoteErrorType::NotConnected))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MemoryFs;
+
+    /// Versions are stored by the mock as `{path}@{version}`
+    impl VersionedFs for MemoryFs {
+        fn list_versions(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+            let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+            let prefix = format!("{}@", path.display());
+            Ok(self
+                .list_dir(parent)?
+                .into_iter()
+                .filter_map(|x| {
+                    let name = x.path().display().to_string();
+                    name.strip_prefix(prefix.as_str()).map(|version| File {
+                        path: path.to_path_buf(),
+                        metadata: x.metadata.clone().version(version),
+                    })
+                })
+                .collect())
+        }
+
+        fn open_version(&mut self, path: &Path, version: &str) -> RemoteResult<ReadStream> {
+            self.open(PathBuf::from(format!("{}@{}", path.display(), version)).as_path())
+        }
+
+        fn delete_version(&mut self, path: &Path, version: &str) -> RemoteResult<()> {
+            self.remove_file(PathBuf::from(format!("{}@{}", path.display(), version)).as_path())
+        }
+    }
+
+    #[test]
+    fn should_restore_version() {
+        let mut client = MemoryFs::connected()
+            .with_file("/a.txt", b"latest")
+            .with_file("/a.txt@1", b"first")
+            .with_file("/a.txt@2", b"latest");
+        assert_eq!(client.list_versions(Path::new("/a.txt")).unwrap().len(), 2);
+        client.restore_version(Path::new("/a.txt"), "1").unwrap();
+        assert_eq!(client.content("/a.txt").unwrap(), b"first");
+    }
+
+    #[test]
+    fn should_not_restore_unexisting_version() {
+        let mut client = MemoryFs::connected().with_file("/a.txt", b"latest");
+        assert_eq!(
+            client
+                .restore_version(Path::new("/a.txt"), "1")
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! ## Acl
+//!
+//! access control lists, as used by object stores
+
+use super::UnixPex;
+
+/// Describes the permissions of a file, according to the model supported by the remote file system
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Permissions {
+    /// POSIX permissions
+    Unix(UnixPex),
+    /// Access control list (e.g. S3 object ACLs)
+    Acl(Vec<AclEntry>),
+    /// The remote file system doesn't expose permissions for this file
+    None,
+}
+
+impl Permissions {
+    /// Returns unix permissions, if this is `Unix`
+    pub fn unix(&self) -> Option<UnixPex> {
+        match self {
+            Self::Unix(pex) => Some(*pex),
+            _ => None,
+        }
+    }
+
+    /// Returns acl entries, if this is `Acl`
+    pub fn acl(&self) -> Option<&[AclEntry]> {
+        match self {
+            Self::Acl(entries) => Some(entries.as_slice()),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of an access control list, granting `permission` to `grantee`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AclEntry {
+    /// Who the permission is granted to
+    pub grantee: AclGrantee,
+    /// The granted permission
+    pub permission: AclPermission,
+}
+
+impl AclEntry {
+    /// Instantiates a new `AclEntry`
+    pub fn new(grantee: AclGrantee, permission: AclPermission) -> Self {
+        Self {
+            grantee,
+            permission,
+        }
+    }
+}
+
+/// The grantee of an acl entry
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AclGrantee {
+    /// A user, identified by its id on the remote
+    User(String),
+    /// A group, identified by its id or uri on the remote
+    Group(String),
+    /// Any authenticated user
+    AuthenticatedUsers,
+    /// Anyone, including anonymous users
+    Everyone,
+}
+
+/// A permission granted by an acl entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AclPermission {
+    /// Read the file content
+    Read,
+    /// Write the file content
+    Write,
+    /// Read the acl of the file
+    ReadAcl,
+    /// Write the acl of the file
+    WriteAcl,
+    /// All of the above
+    FullControl,
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_get_permissions_by_model() {
+        let pex = UnixPex::from(0o644);
+        assert_eq!(Permissions::Unix(pex).unix(), Some(pex));
+        assert!(Permissions::Unix(pex).acl().is_none());
+        let acl = Permissions::Acl(vec![AclEntry::new(
+            AclGrantee::Everyone,
+            AclPermission::Read,
+        )]);
+        assert!(acl.unix().is_none());
+        assert_eq!(
+            acl.acl().unwrap(),
+            &[AclEntry::new(AclGrantee::Everyone, AclPermission::Read)]
+        );
+        assert!(Permissions::None.unix().is_none());
+        assert!(Permissions::None.acl().is_none());
+    }
+}
@@ -2,21 +2,28 @@
 //!
 //! file metadata
 
+use std::collections::BTreeMap;
 use std::fs::Metadata as StdMetadata;
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
-use super::{FileType, UnixPex};
+use super::{AclEntry, FileType, Permissions, UnixPex};
 
 /// File metadata
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Metadata {
     /// Last access time
     pub accessed: Option<SystemTime>,
-    /// Creation time
+    /// Access control list, on remote file systems which use ACLs instead of unix permissions
+    pub acl: Option<Vec<AclEntry>>,
+    /// Custom user attributes (e.g. S3 user metadata or extended attributes on SSH)
+    pub attributes: BTreeMap<String, String>,
+    /// Creation (birth) time, where the remote provides it (e.g. SFTP v4+, the object creation time on S3 or `statx` on local file systems)
     pub created: Option<SystemTime>,
+    /// Entity tag of the file (e.g. the ETag of an object on S3)
+    pub etag: Option<String>,
     /// Group id
     pub gid: Option<u32>,
     /// Unix permissions
@@ -31,13 +38,18 @@ pub struct Metadata {
     pub file_type: FileType,
     /// User id
     pub uid: Option<u32>,
+    /// Version id of the file, on object stores which support versioning
+    pub version: Option<String>,
 }
 
 impl Default for Metadata {
     fn default() -> Self {
         Self {
             accessed: None,
+            acl: None,
+            attributes: BTreeMap::new(),
             created: None,
+            etag: None,
             gid: None,
             mode: None,
             modified: None,
@@ -45,6 +57,7 @@ impl Default for Metadata {
             symlink: None,
             file_type: FileType::File,
             uid: None,
+            version: None,
         }
     }
 }
@@ -56,12 +69,30 @@ impl Metadata {
         self
     }
 
-    /// Construct metadata with created
+    /// Construct metadata with access control list
+    pub fn acl(mut self, acl: Vec<AclEntry>) -> Self {
+        self.acl = Some(acl);
+        self
+    }
+
+    /// Construct metadata with a custom user attribute
+    pub fn attribute<K: ToString, V: ToString>(mut self, key: K, value: V) -> Self {
+        self.attributes.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Construct metadata with creation (birth) time
     pub fn created(mut self, created: SystemTime) -> Self {
         self.created = Some(created);
         self
     }
 
+    /// Construct metadata with entity tag
+    pub fn etag<S: ToString>(mut self, etag: S) -> Self {
+        self.etag = Some(etag.to_string());
+        self
+    }
+
     /// Construct metadata with group id
     pub fn gid(mut self, gid: u32) -> Self {
         self.gid = Some(gid);
@@ -104,6 +135,12 @@ impl Metadata {
         self
     }
 
+    /// Construct metadata with version id
+    pub fn version<S: ToString>(mut self, version: S) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
     /// Returns whether the file is a directory
     pub fn is_dir(&self) -> bool {
         self.file_type.is_dir()
@@ -119,6 +156,35 @@ impl Metadata {
         self.file_type.is_symlink()
     }
 
+    /// Returns the file permissions, according to the permissions model of the remote.
+    /// If both are set, unix permissions take precedence over the acl
+    pub fn permissions(&self) -> Permissions {
+        match (self.mode, self.acl.as_ref()) {
+            (Some(mode), _) => Permissions::Unix(mode),
+            (None, Some(acl)) => Permissions::Acl(acl.clone()),
+            (None, None) => Permissions::None,
+        }
+    }
+
+    /// Returns the file size in a human readable format, as `ls -h` does (`512B`, `1.5K`, `12M`, ...)
+    pub fn size_human(&self) -> String {
+        const UNITS: [char; 6] = ['K', 'M', 'G', 'T', 'P', 'E'];
+        if self.size < 1024 {
+            return format!("{}B", self.size);
+        }
+        let mut value = self.size as f64 / 1024.0;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        if value < 10.0 {
+            format!("{:.1}{}", value, UNITS[unit])
+        } else {
+            format!("{:.0}{}", value, UNITS[unit])
+        }
+    }
+
     /// Set symlink
     pub fn set_symlink<P: AsRef<Path>>(&mut self, p: P) {
         self.symlink = Some(p.as_ref().to_path_buf());
@@ -130,7 +196,10 @@ impl From<StdMetadata> for Metadata {
     fn from(metadata: StdMetadata) -> Self {
         Self {
             accessed: metadata.accessed().ok(),
+            acl: None,
+            attributes: BTreeMap::new(),
             created: metadata.created().ok(),
+            etag: None,
             gid: None,
             file_type: FileType::from(metadata.file_type()),
             modified: metadata.modified().ok(),
@@ -138,6 +207,7 @@ impl From<StdMetadata> for Metadata {
             size: metadata.len(),
             symlink: None,
             uid: None,
+            version: None,
         }
     }
 }
@@ -147,7 +217,10 @@ impl From<StdMetadata> for Metadata {
     fn from(metadata: StdMetadata) -> Self {
         Self {
             accessed: metadata.accessed().ok(),
+            acl: None,
+            attributes: BTreeMap::new(),
             created: metadata.created().ok(),
+            etag: None,
             gid: Some(metadata.gid()),
             file_type: FileType::from(metadata.file_type()),
             modified: metadata.modified().ok(),
@@ -159,6 +232,7 @@ impl From<StdMetadata> for Metadata {
             },
             symlink: None,
             uid: Some(metadata.uid()),
+            version: None,
         }
     }
 }
@@ -170,14 +244,17 @@ mod test {
 
     use pretty_assertions::assert_eq;
 
-    use super::super::UnixPexClass;
+    use super::super::{AclGrantee, AclPermission, UnixPexClass};
     use super::*;
 
     #[test]
     fn should_initialize_metadata() {
         let metadata = Metadata::default();
         assert!(metadata.accessed.is_none());
+        assert!(metadata.acl.is_none());
+        assert!(metadata.attributes.is_empty());
         assert!(metadata.created.is_none());
+        assert!(metadata.etag.is_none());
         assert!(metadata.gid.is_none());
         assert!(metadata.mode.is_none());
         assert!(metadata.modified.is_none());
@@ -185,6 +262,7 @@ mod test {
         assert!(metadata.symlink.is_none());
         assert_eq!(metadata.file_type, FileType::File);
         assert!(metadata.uid.is_none());
+        assert!(metadata.version.is_none());
     }
 
     #[test]
@@ -198,7 +276,9 @@ mod test {
             .unwrap();
         let metadata = Metadata::default()
             .accessed(accessed)
+            .attribute("project", "remotefs")
             .created(created)
+            .etag("\"d41d8cd98f00b204e9800998ecf8427e\"")
             .gid(14)
             .mode(UnixPex::new(
                 UnixPexClass::from(6),
@@ -209,9 +289,18 @@ mod test {
             .size(1024)
             .symlink(Path::new("/tmp/a.txt"))
             .file_type(FileType::Symlink)
-            .uid(10);
+            .uid(10)
+            .version("3HL4kqtJlcpXroDTDmjVBH40Nrjfkd");
         assert_eq!(metadata.accessed, Some(accessed));
+        assert_eq!(
+            metadata.attributes.get("project").map(String::as_str),
+            Some("remotefs")
+        );
         assert_eq!(metadata.created, Some(created));
+        assert_eq!(
+            metadata.etag.as_deref().unwrap(),
+            "\"d41d8cd98f00b204e9800998ecf8427e\""
+        );
         assert_eq!(metadata.gid.unwrap(), 14);
         assert!(metadata.mode.is_some());
         assert_eq!(metadata.modified, Some(modified));
@@ -224,6 +313,40 @@ mod test {
             Path::new("/tmp/a.txt")
         );
         assert_eq!(metadata.uid.unwrap(), 10);
+        assert_eq!(
+            metadata.version.as_deref().unwrap(),
+            "3HL4kqtJlcpXroDTDmjVBH40Nrjfkd"
+        );
+    }
+
+    #[test]
+    fn should_format_size_human() {
+        assert_eq!(Metadata::default().size(512).size_human(), "512B");
+        assert_eq!(Metadata::default().size(1536).size_human(), "1.5K");
+        assert_eq!(
+            Metadata::default().size(12 * 1024 * 1024).size_human(),
+            "12M"
+        );
+        assert_eq!(
+            Metadata::default()
+                .size(3 * 1024 * 1024 * 1024)
+                .size_human(),
+            "3.0G"
+        );
+    }
+
+    #[test]
+    fn should_get_permissions_from_metadata() {
+        assert_eq!(Metadata::default().permissions(), Permissions::None);
+        assert_eq!(
+            Metadata::default().mode(UnixPex::from(0o755)).permissions(),
+            Permissions::Unix(UnixPex::from(0o755))
+        );
+        let acl = vec![AclEntry::new(AclGrantee::Everyone, AclPermission::Read)];
+        assert_eq!(
+            Metadata::default().acl(acl.clone()).permissions(),
+            Permissions::Acl(acl)
+        );
     }
 
     #[test]
@@ -6,14 +6,16 @@
 use std::path::{Path, PathBuf};
 
 // -- mod
+mod acl;
 mod file_type;
 mod metadata;
 mod permissions;
 
 // -- export
+pub use acl::{AclEntry, AclGrantee, AclPermission, Permissions};
 pub use file_type::FileType;
 pub use metadata::Metadata;
-pub use permissions::{UnixPex, UnixPexClass};
+pub use permissions::{ParsePexError, UnixPex, UnixPexClass};
 
 /// A file represents an entity in the file system
 
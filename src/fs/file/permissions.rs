@@ -2,6 +2,11 @@
 //!
 //! POSIX permissions
 
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
 /// Describes the permissions on POSIX system.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct UnixPex(UnixPexClass, UnixPexClass, UnixPexClass);
@@ -28,6 +33,47 @@ impl UnixPex {
     }
 }
 
+/// Formats permissions as `rwxr-xr-x`
+impl fmt::Display for UnixPex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.0, self.1, self.2)
+    }
+}
+
+/// Parses permissions from a string such as `rw-r--r--`
+impl FromStr for UnixPex {
+    type Err = ParsePexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = s.chars().collect();
+        if chars.len() != 9 {
+            return Err(ParsePexError(s.to_string()));
+        }
+        let class = |c: &[char]| -> Result<UnixPexClass, ParsePexError> {
+            let bit = |c: char, set: char| match c {
+                '-' => Ok(false),
+                c if c == set => Ok(true),
+                _ => Err(ParsePexError(s.to_string())),
+            };
+            Ok(UnixPexClass::new(
+                bit(c[0], 'r')?,
+                bit(c[1], 'w')?,
+                bit(c[2], 'x')?,
+            ))
+        };
+        Ok(Self::new(
+            class(&chars[0..3])?,
+            class(&chars[3..6])?,
+            class(&chars[6..9])?,
+        ))
+    }
+}
+
+/// Error returned when parsing an invalid permissions string
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid permissions string: {0}")]
+pub struct ParsePexError(String);
+
 impl From<UnixPex> for u32 {
     fn from(pex: UnixPex) -> Self {
         (u32::from(pex.0) << 6) + (u32::from(pex.1) << 3) + u32::from(pex.2)
@@ -83,6 +129,19 @@ impl UnixPexClass {
     }
 }
 
+/// Formats permissions as `rwx`
+impl fmt::Display for UnixPexClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            if self.read { 'r' } else { '-' },
+            if self.write { 'w' } else { '-' },
+            if self.execute { 'x' } else { '-' }
+        )
+    }
+}
+
 impl From<u8> for UnixPexClass {
     fn from(bits: u8) -> Self {
         Self {
@@ -169,4 +228,28 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn should_format_unix_pex() {
+        assert_eq!(UnixPex::from(0o755).to_string(), "rwxr-xr-x");
+        assert_eq!(UnixPex::from(0o640).to_string(), "rw-r-----");
+        assert_eq!(UnixPexClass::from(5).to_string(), "r-x");
+    }
+
+    #[test]
+    fn should_parse_unix_pex() {
+        assert_eq!(
+            UnixPex::from_str("rw-r--r--").unwrap(),
+            UnixPex::from(0o644)
+        );
+        assert_eq!(
+            "rwx------".parse::<UnixPex>().unwrap(),
+            UnixPex::from(0o700)
+        );
+        assert!(UnixPex::from_str("rw-r--r").is_err());
+        assert_eq!(
+            UnixPex::from_str("wr-r--r--").unwrap_err().to_string(),
+            "invalid permissions string: wr-r--r--"
+        );
+    }
 }
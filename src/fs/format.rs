@@ -0,0 +1,322 @@
+//! ## Format
+//!
+//! render directory listings in standard formats (JSON, `ls -l`-like text, CSV)
+
+use std::fmt::Write as _;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{File, FileType};
+use crate::utils::time::DateTime;
+
+/// The format to render a listing with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListingFormat {
+    /// A JSON array of objects, one per file. Times are expressed as seconds since the unix epoch
+    Json,
+    /// A table like the one printed by `ls -l`. If `human_readable` is set, sizes are printed as `1.5K`, `12M`, ...
+    Long { human_readable: bool },
+    /// Comma separated values, with a header row
+    Csv,
+}
+
+impl ListingFormat {
+    /// Render `files` in this format
+    pub fn render(&self, files: &[File]) -> String {
+        match self {
+            Self::Json => render_json(files),
+            Self::Long { human_readable } => render_long(files, *human_readable),
+            Self::Csv => render_csv(files),
+        }
+    }
+}
+
+fn render_json(files: &[File]) -> String {
+    let mut out = String::from("[");
+    for (i, file) in files.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let metadata = file.metadata();
+        let _ = write!(
+            out,
+            "{{\"name\":{},\"path\":{},\"type\":\"{}\",\"size\":{},\"mode\":{},\"uid\":{},\"gid\":{},\"modified\":{},\"symlink\":{}}}",
+            json_string(&file.name()),
+            json_string(&file.path().to_string_lossy()),
+            type_name(&metadata.file_type),
+            metadata.size,
+            json_opt(metadata.mode.map(|x| format!("\"{:04o}\"", u32::from(x)))),
+            json_opt(metadata.uid),
+            json_opt(metadata.gid),
+            json_opt(metadata.modified.map(unix_secs)),
+            json_opt(
+                metadata
+                    .symlink
+                    .as_ref()
+                    .map(|x| json_string(&x.to_string_lossy()))
+            ),
+        );
+    }
+    out.push(']');
+    out
+}
+
+fn render_long(files: &[File], human_readable: bool) -> String {
+    let rows: Vec<[String; 6]> = files
+        .iter()
+        .map(|file| {
+            let metadata = file.metadata();
+            let mut name = file.name();
+            if let Some(target) = metadata.symlink.as_ref() {
+                let _ = write!(name, " -> {}", target.display());
+            }
+            [
+                format!(
+                    "{}{}",
+                    type_char(&metadata.file_type),
+                    metadata
+                        .mode
+                        .map(|x| x.to_string())
+                        .unwrap_or_else(|| "?????????".to_string())
+                ),
+                opt_string(metadata.uid),
+                opt_string(metadata.gid),
+                match human_readable {
+                    true => metadata.size_human(),
+                    false => metadata.size.to_string(),
+                },
+                metadata
+                    .modified
+                    .map(datetime_string)
+                    .unwrap_or_else(|| "-".to_string()),
+                name,
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..5)
+        .map(|col| rows.iter().map(|x| x[col].len()).max().unwrap_or(0))
+        .collect();
+    let mut out = String::new();
+    for row in rows.iter() {
+        let _ = writeln!(
+            out,
+            "{:<w0$} {:<w1$} {:<w2$} {:>w3$} {:<w4$} {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            row[4],
+            row[5],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+            w4 = widths[4],
+        );
+    }
+    out
+}
+
+fn render_csv(files: &[File]) -> String {
+    let mut out = String::from("name,path,type,size,mode,uid,gid,modified,symlink\n");
+    for file in files.iter() {
+        let metadata = file.metadata();
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{}",
+            csv_field(&file.name()),
+            csv_field(&file.path().to_string_lossy()),
+            type_name(&metadata.file_type),
+            metadata.size,
+            metadata
+                .mode
+                .map(|x| format!("{:04o}", u32::from(x)))
+                .unwrap_or_default(),
+            metadata.uid.map(|x| x.to_string()).unwrap_or_default(),
+            metadata.gid.map(|x| x.to_string()).unwrap_or_default(),
+            metadata
+                .modified
+                .map(|x| unix_secs(x).to_string())
+                .unwrap_or_default(),
+            metadata
+                .symlink
+                .as_ref()
+                .map(|x| csv_field(&x.to_string_lossy()))
+                .unwrap_or_default(),
+        );
+    }
+    out
+}
+
+fn type_name(file_type: &FileType) -> &'static str {
+    match file_type {
+        FileType::Directory => "directory",
+        FileType::File => "file",
+        FileType::Symlink => "symlink",
+    }
+}
+
+fn type_char(file_type: &FileType) -> char {
+    match file_type {
+        FileType::Directory => 'd',
+        FileType::File => '-',
+        FileType::Symlink => 'l',
+    }
+}
+
+fn opt_string<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+/// Render time as `YYYY-MM-DD HH:MM` (UTC)
+fn datetime_string(time: SystemTime) -> String {
+    let t = DateTime::from(time);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        t.year, t.month, t.day, t.hour, t.minute
+    )
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_opt<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| "null".to_string())
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::{Metadata, UnixPex};
+
+    fn files() -> Vec<File> {
+        let modified = UNIX_EPOCH + Duration::from_secs(1700000000);
+        vec![
+            File {
+                path: PathBuf::from("/home/docs"),
+                metadata: Metadata::default()
+                    .file_type(FileType::Directory)
+                    .mode(UnixPex::from(0o755))
+                    .uid(1000)
+                    .gid(1000)
+                    .size(4096)
+                    .modified(modified),
+            },
+            File {
+                path: PathBuf::from("/home/a, \"b\".txt"),
+                metadata: Metadata::default()
+                    .mode(UnixPex::from(0o644))
+                    .uid(0)
+                    .gid(0)
+                    .size(1536)
+                    .modified(modified),
+            },
+            File {
+                path: PathBuf::from("/home/link"),
+                metadata: Metadata::default()
+                    .file_type(FileType::Symlink)
+                    .symlink("/home/docs"),
+            },
+        ]
+    }
+
+    #[test]
+    fn should_render_json() {
+        assert_eq!(
+            ListingFormat::Json.render(&files()),
+            concat!(
+                "[{\"name\":\"docs\",\"path\":\"/home/docs\",\"type\":\"directory\",\"size\":4096,\"mode\":\"0755\",\"uid\":1000,\"gid\":1000,\"modified\":1700000000,\"symlink\":null},",
+                "{\"name\":\"a, \\\"b\\\".txt\",\"path\":\"/home/a, \\\"b\\\".txt\",\"type\":\"file\",\"size\":1536,\"mode\":\"0644\",\"uid\":0,\"gid\":0,\"modified\":1700000000,\"symlink\":null},",
+                "{\"name\":\"link\",\"path\":\"/home/link\",\"type\":\"symlink\",\"size\":0,\"mode\":null,\"uid\":null,\"gid\":null,\"modified\":null,\"symlink\":\"/home/docs\"}]"
+            )
+        );
+        assert_eq!(ListingFormat::Json.render(&[]), "[]");
+    }
+
+    #[test]
+    fn should_render_long() {
+        assert_eq!(
+            ListingFormat::Long {
+                human_readable: false
+            }
+            .render(&files()),
+            concat!(
+                "drwxr-xr-x 1000 1000 4096 2023-11-14 22:13 docs\n",
+                "-rw-r--r-- 0    0    1536 2023-11-14 22:13 a, \"b\".txt\n",
+                "l????????? -    -       0 -                link -> /home/docs\n",
+            )
+        );
+        assert_eq!(
+            ListingFormat::Long {
+                human_readable: true
+            }
+            .render(&files())
+            .lines()
+            .nth(1)
+            .unwrap(),
+            "-rw-r--r-- 0    0    1.5K 2023-11-14 22:13 a, \"b\".txt"
+        );
+    }
+
+    #[test]
+    fn should_render_csv() {
+        assert_eq!(
+            ListingFormat::Csv.render(&files()),
+            concat!(
+                "name,path,type,size,mode,uid,gid,modified,symlink\n",
+                "docs,/home/docs,directory,4096,0755,1000,1000,1700000000,\n",
+                "\"a, \"\"b\"\".txt\",\"/home/a, \"\"b\"\".txt\",file,1536,0644,0,0,1700000000,\n",
+                "link,/home/link,symlink,0,,,,,/home/docs\n",
+            )
+        );
+    }
+
+    #[test]
+    fn should_format_datetime() {
+        assert_eq!(datetime_string(UNIX_EPOCH), "1970-01-01 00:00");
+        assert_eq!(
+            datetime_string(UNIX_EPOCH + Duration::from_secs(951782400)),
+            "2000-02-29 00:00"
+        );
+    }
+}
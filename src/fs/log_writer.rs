@@ -0,0 +1,318 @@
+//! ## Log writer
+//!
+//! buffered append-only writer for remote log files
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{
+    copy_stream, Metadata, MoveOpts, RemoteError, RemoteErrorType, RemoteFs, RemoteResult,
+    TransferOpts,
+};
+use crate::utils::time::DateTime;
+
+/// Default amount of buffered bytes after which lines are flushed to the remote
+const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// When a remote log file is rotated.
+/// The rotated file is renamed to `<name>.<YYYY-MM-DD>`, after the day of its last write;
+/// if such a file already exists, a counter is appended (`<name>.<YYYY-MM-DD>.1`, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// Rotate before the file would exceed the given size in bytes
+    Size(u64),
+    /// Rotate when the first line of a new day (UTC) is flushed
+    Daily,
+}
+
+/// A writer of lines to a remote log file, which buffers the lines and appends them to the file
+/// once `buffer_size` bytes are buffered, or when writing a line after `flush_interval` elapsed since the last flush.
+/// Buffered lines are flushed with `finish` or when dropped.
+///
+/// Lines are appended with `RemoteFs::append_file`; if the remote doesn't support appending,
+/// the file is read and written again with the new lines, which is not safe against concurrent writers.
+///
+/// Errors raised while flushing on drop can't be reported, so prefer calling `finish` explicitly
+pub struct LogWriter<'a, T: RemoteFs + ?Sized> {
+    fs: &'a mut T,
+    path: PathBuf,
+    buffer: Vec<u8>,
+    buffer_size: usize,
+    flush_interval: Option<Duration>,
+    last_flush: Instant,
+    rotation: Option<Rotation>,
+    /// Size and day of the last write of the remote file, once known
+    current: Option<(u64, DateTime)>,
+}
+
+impl<'a, T: RemoteFs + ?Sized> LogWriter<'a, T> {
+    /// Instantiates a new `LogWriter`, appending lines to the file at `path` on `fs`
+    pub fn new(fs: &'a mut T, path: &Path) -> Self {
+        Self {
+            fs,
+            path: path.to_path_buf(),
+            buffer: Vec::new(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            flush_interval: None,
+            last_flush: Instant::now(),
+            rotation: None,
+            current: None,
+        }
+    }
+
+    /// Set the amount of buffered bytes after which lines are flushed (default: 8 KiB)
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// Flush buffered lines when a line is written after `interval` elapsed since the last flush
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Rotate the remote file as specified by `rotation`
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    /// Write `line` to the log. A line terminator is added if `line` doesn't end with one
+    pub fn write_line(&mut self, line: &str) -> RemoteResult<()> {
+        self.buffer.extend_from_slice(line.as_bytes());
+        if !line.ends_with('\n') {
+            self.buffer.push(b'\n');
+        }
+        let elapsed = self
+            .flush_interval
+            .map(|x| self.last_flush.elapsed() >= x)
+            .unwrap_or(false);
+        if self.buffer.len() >= self.buffer_size || elapsed {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Append the buffered lines to the remote file, rotating it first if necessary.
+    /// If appending fails, lines are kept in the buffer
+    pub fn flush(&mut self) -> RemoteResult<()> {
+        self.last_flush = Instant::now();
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let today = DateTime::from(SystemTime::now());
+        let (size, day) = match self.current {
+            Some(current) => current,
+            None => match self.fs.stat(self.path.as_path()) {
+                Ok(file) => (
+                    file.metadata.size,
+                    file.metadata.modified.map(DateTime::from).unwrap_or(today),
+                ),
+                Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => (0, today),
+                Err(err) => return Err(err),
+            },
+        };
+        let rotate = match self.rotation {
+            Some(Rotation::Size(max)) => size > 0 && size + self.buffer.len() as u64 > max,
+            Some(Rotation::Daily) => size > 0 && !same_day(&day, &today),
+            None => false,
+        };
+        let size = match rotate {
+            true => {
+                self.rotate(&day)?;
+                0
+            }
+            false => size,
+        };
+        self.append()?;
+        trace!(
+            "Flushed {} bytes to {}",
+            self.buffer.len(),
+            self.path.display()
+        );
+        self.current = Some((size + self.buffer.len() as u64, today));
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Flush the buffered lines
+    pub fn finish(mut self) -> RemoteResult<()> {
+        self.flush()
+    }
+
+    /// Append the buffer to the remote file, emulating append if unsupported
+    fn append(&mut self) -> RemoteResult<()> {
+        let data = Box::new(Cursor::new(self.buffer.clone()));
+        match self
+            .fs
+            .append_file(self.path.as_path(), &Metadata::default(), data)
+        {
+            Err(err) if err.kind == RemoteErrorType::UnsupportedFeature => {
+                debug!("Append is not supported; writing the whole file");
+            }
+            result => return result.map(|_| ()),
+        }
+        let mut content = Vec::new();
+        match self.fs.open(self.path.as_path()) {
+            Ok(mut stream) => {
+                copy_stream(&mut stream, &mut content, &TransferOpts::default())?;
+                self.fs.on_read(stream)?;
+            }
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => {}
+            Err(err) => return Err(err),
+        }
+        content.extend_from_slice(&self.buffer);
+        self.fs
+            .create_file(
+                self.path.as_path(),
+                &Metadata::default(),
+                Box::new(Cursor::new(content)),
+            )
+            .map(|_| ())
+    }
+
+    /// Rename the remote file after `day`
+    fn rotate(&mut self, day: &DateTime) -> RemoteResult<()> {
+        let name = self
+            .path
+            .file_name()
+            .map(|x| x.to_string_lossy().to_string())
+            .ok_or_else(|| RemoteError::new_ex(RemoteErrorType::BadFile, "log has no file name"))?;
+        let name = format!("{}.{:04}-{:02}-{:02}", name, day.year, day.month, day.day);
+        let mut dest = self.path.with_file_name(name.as_str());
+        let mut n = 0;
+        while self.fs.exists(dest.as_path())? {
+            n += 1;
+            dest = self.path.with_file_name(format!("{name}.{n}"));
+        }
+        debug!("Rotating {} to {}", self.path.display(), dest.display());
+        self.fs.move_with(
+            self.path.as_path(),
+            dest.as_path(),
+            &MoveOpts::default().copy_fallback(true),
+        )
+    }
+}
+
+impl<T: RemoteFs + ?Sized> Drop for LogWriter<'_, T> {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            error!("Failed to flush log {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+fn same_day(a: &DateTime, b: &DateTime) -> bool {
+    (a.year, a.month, a.day) == (b.year, b.month, b.day)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::UNIX_EPOCH;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MemoryFs;
+
+    #[test]
+    fn should_buffer_lines() {
+        let mut client = MemoryFs::connected().with_dir("/log");
+        let remote = client.share();
+        let mut writer = LogWriter::new(&mut client, Path::new("/log/app.log")).buffer_size(12);
+        writer.write_line("hello").unwrap();
+        assert!(remote.content("/log/app.log").is_none());
+        writer.write_line("world\n").unwrap();
+        writer.write_line("!").unwrap();
+        assert_eq!(remote.content("/log/app.log").unwrap(), b"hello\nworld\n");
+        writer.finish().unwrap();
+        assert_eq!(
+            remote.content("/log/app.log").unwrap(),
+            b"hello\nworld\n!\n"
+        );
+    }
+
+    #[test]
+    fn should_flush_after_interval() {
+        let mut client = MemoryFs::connected().with_dir("/log");
+        let remote = client.share();
+        let mut writer =
+            LogWriter::new(&mut client, Path::new("/log/app.log")).flush_interval(Duration::ZERO);
+        writer.write_line("hello").unwrap();
+        assert_eq!(remote.content("/log/app.log").unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn should_flush_on_drop() {
+        let mut client = MemoryFs::connected().with_dir("/log");
+        LogWriter::new(&mut client, Path::new("/log/app.log"))
+            .write_line("hello")
+            .unwrap();
+        assert_eq!(client.content("/log/app.log").unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn should_emulate_append() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/log")
+            .with_file("/log/app.log", b"hello\n")
+            .without("append");
+        let mut writer = LogWriter::new(&mut client, Path::new("/log/app.log"));
+        writer.write_line("world").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(client.content("/log/app.log").unwrap(), b"hello\nworld\n");
+    }
+
+    #[test]
+    fn should_rotate_by_size() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/log")
+            .with_file("/log/app.log", b"hello\n");
+        let remote = client.share();
+        let today = DateTime::from(SystemTime::now());
+        let rotated = format!(
+            "/log/app.log.{:04}-{:02}-{:02}",
+            today.year, today.month, today.day
+        );
+        let mut writer = LogWriter::new(&mut client, Path::new("/log/app.log"))
+            .buffer_size(1)
+            .rotation(Rotation::Size(12));
+        writer.write_line("world").unwrap();
+        assert_eq!(remote.content("/log/app.log").unwrap(), b"hello\nworld\n");
+        writer.write_line("again").unwrap();
+        writer.write_line("bye").unwrap();
+        assert_eq!(remote.content("/log/app.log").unwrap(), b"again\nbye\n");
+        assert_eq!(remote.content(rotated.as_str()).unwrap(), b"hello\nworld\n");
+        writer.write_line("last one").unwrap();
+        assert_eq!(remote.content("/log/app.log").unwrap(), b"last one\n");
+        assert_eq!(
+            remote.content(format!("{rotated}.1")).unwrap(),
+            b"again\nbye\n"
+        );
+    }
+
+    #[test]
+    fn should_rotate_daily() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/log")
+            .with_file("/log/app.log", b"hello\n");
+        client
+            .setstat(
+                Path::new("/log/app.log"),
+                Metadata::default().modified(UNIX_EPOCH),
+            )
+            .unwrap();
+        let mut writer =
+            LogWriter::new(&mut client, Path::new("/log/app.log")).rotation(Rotation::Daily);
+        writer.write_line("world").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            client.content("/log/app.log.1970-01-01").unwrap(),
+            b"hello\n"
+        );
+        assert_eq!(client.content("/log/app.log").unwrap(), b"world\n");
+    }
+}
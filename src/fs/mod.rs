@@ -2,14 +2,47 @@
 //!
 //! `fs` is the module which provides remote file system entities
 
+mod adapter;
+mod capabilities;
+pub mod checksum;
 mod errors;
+mod event;
+pub mod ext;
 mod file;
+pub mod format;
+mod log_writer;
+pub mod opts;
+mod report;
+mod semantics;
+mod snapshot;
 pub mod stream;
 mod sync;
+mod transfer;
+pub(crate) mod walk;
 mod welcome;
+#[cfg(feature = "zip")]
+mod zip;
 
-pub use self::errors::{RemoteError, RemoteErrorType, RemoteResult};
-pub use self::file::{File, FileType, Metadata, UnixPex, UnixPexClass};
-pub use self::stream::{ReadStream, WriteStream};
+pub use self::adapter::{IoReader, IoWriter, Lines};
+pub use self::capabilities::Capabilities;
+pub use self::checksum::ChecksumAlgorithm;
+pub use self::errors::{FinalizeError, RemoteError, RemoteErrorType, RemoteResult};
+pub use self::event::{ConnectPhase, FsEvent};
+pub use self::file::{
+    AclEntry, AclGrantee, AclPermission, File, FileType, Metadata, ParsePexError, Permissions,
+    UnixPex, UnixPexClass,
+};
+pub use self::log_writer::{LogWriter, Rotation};
+#[cfg(feature = "find")]
+pub use self::opts::IgnoreRules;
+pub use self::opts::{
+    ConnectOpts, CopyOpts, ErrorPolicy, GrepOpts, ListDirOpts, MetadataDefaults, MoveOpts,
+    PhaseCallback, PreserveMode, ProgressCallback, SortBy, StreamOpts, TransferOpts, WalkDirOpts,
+};
+pub use self::report::{BulkReport, ExecOutput, GrepMatch, WriteReport};
+pub use self::semantics::{PathError, Semantics};
+pub use self::snapshot::{Change, Snapshot, SnapshotEntry};
+pub use self::stream::{ReadStream, StreamTag, WriteStream};
 pub use self::sync::RemoteFs;
+pub use self::transfer::copy_stream;
 pub use self::welcome::Welcome;
@@ -0,0 +1,63 @@
+//! ## Connect
+//!
+//! options for `connect_with`
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::fs::ConnectPhase;
+
+/// Callback reporting the phase the connection has entered
+pub type PhaseCallback = Arc<dyn Fn(ConnectPhase) + Send + Sync>;
+
+/// Options for `connect_with`
+#[derive(Clone, Default)]
+pub struct ConnectOpts {
+    /// When set to `true`, the connection is aborted with a `Cancelled` error
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Called each time the connection enters a new phase
+    pub on_phase: Option<PhaseCallback>,
+}
+
+impl fmt::Debug for ConnectOpts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectOpts")
+            .field("cancel", &self.cancel)
+            .field("on_phase", &self.on_phase.is_some())
+            .finish()
+    }
+}
+
+impl ConnectOpts {
+    /// Abort the connection when `flag` is set to `true`
+    pub fn cancel(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Call `callback` each time the connection enters a new phase
+    pub fn on_phase<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(ConnectPhase) + Send + Sync + 'static,
+    {
+        self.on_phase = Some(Arc::new(callback));
+        self
+    }
+
+    /// Returns whether the connection has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .map(|x| x.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+
+    /// Report that the connection has entered `phase`
+    pub fn report(&self, phase: ConnectPhase) {
+        trace!("Connection phase: {:?}", phase);
+        if let Some(on_phase) = self.on_phase.as_ref() {
+            on_phase(phase);
+        }
+    }
+}
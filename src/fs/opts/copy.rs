@@ -0,0 +1,39 @@
+//! ## Copy
+//!
+//! options for `copy_with`
+
+/// Which metadata of the source is preserved on the copy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PreserveMode {
+    /// Nothing is preserved: the copy is created with the default metadata of the remote
+    #[default]
+    None,
+    /// Preserve permissions
+    Mode,
+    /// Preserve permissions, ownership and times
+    All,
+}
+
+/// Options for `copy_with`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyOpts {
+    /// Copy the files pointed by symbolic links instead of the links themselves.
+    /// Symlink cycles are detected and reported as `SymlinkLoop` errors
+    pub dereference: bool,
+    /// Metadata of the source to preserve
+    pub preserve: PreserveMode,
+}
+
+impl CopyOpts {
+    /// Copy the files pointed by symbolic links instead of the links themselves
+    pub fn dereference(mut self, dereference: bool) -> Self {
+        self.dereference = dereference;
+        self
+    }
+
+    /// Set the metadata of the source to preserve
+    pub fn preserve(mut self, preserve: PreserveMode) -> Self {
+        self.preserve = preserve;
+        self
+    }
+}
@@ -0,0 +1,13 @@
+//! ## Error policy
+//!
+//! how bulk operations behave on failure
+
+/// Defines how a bulk operation (such as `remove_dir_all_with`) behaves when an operation on an entry fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ErrorPolicy {
+    /// Stop at the first failure and return its error
+    #[default]
+    Abort,
+    /// Keep going past failures, which are collected into the returned `BulkReport`
+    Continue,
+}
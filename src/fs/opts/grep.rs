@@ -0,0 +1,36 @@
+//! ## Grep
+//!
+//! options for `grep`
+
+/// Options for `grep`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GrepOpts {
+    /// Match the pattern ignoring case (ASCII only on the fallback search)
+    pub ignore_case: bool,
+    /// Stop after this amount of matches. If `None` there is no limit
+    pub max_matches: Option<usize>,
+}
+
+impl GrepOpts {
+    /// Match the pattern ignoring case
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// Stop after `max` matches
+    pub fn max_matches(mut self, max: usize) -> Self {
+        self.max_matches = Some(max);
+        self
+    }
+
+    /// Returns whether `line` matches `pattern`
+    pub(crate) fn matches(&self, line: &str, pattern: &str) -> bool {
+        match self.ignore_case {
+            true => line
+                .to_ascii_lowercase()
+                .contains(pattern.to_ascii_lowercase().as_str()),
+            false => line.contains(pattern),
+        }
+    }
+}
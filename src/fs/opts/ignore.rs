@@ -0,0 +1,146 @@
+//! ## Ignore
+//!
+//! ignore-style rules to exclude paths from a traversal
+
+use std::path::Path;
+
+use wildmatch::WildMatch;
+
+/// A set of ignore rules, with a syntax similar to `.gitignore`. Patterns support wildcards (`*`, `?`).
+///
+/// - rules are evaluated in order, and the last rule matching a path wins
+/// - a pattern starting with `!` re-includes the paths excluded by the previous rules
+/// - a pattern ending with `/` only matches directories
+/// - a pattern containing `/` is matched against the path relative to the root of the traversal;
+///   any other pattern is matched against the file name, at any depth
+///
+/// Rules are compiled once and applied during the traversal, so excluded directories are never listed;
+/// hence a file can't be re-included if one of its parent directories is excluded
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    matcher: WildMatch,
+    anchored: bool,
+    dir_only: bool,
+    negate: bool,
+}
+
+impl Eq for IgnoreRules {}
+
+impl IgnoreRules {
+    /// Parse rules from the content of an ignore file, one rule per line.
+    /// Empty lines and lines starting with `#` are skipped
+    pub fn parse(content: &str) -> Self {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty() && !x.starts_with('#'))
+            .fold(Self::default(), |rules, line| rules.rule(line))
+    }
+
+    /// Exclude paths matching `pattern`
+    pub fn exclude<S: AsRef<str>>(self, pattern: S) -> Self {
+        self.push(pattern.as_ref(), false)
+    }
+
+    /// Re-include paths matching `pattern`, excluded by the previous rules
+    pub fn include<S: AsRef<str>>(self, pattern: S) -> Self {
+        self.push(pattern.as_ref(), true)
+    }
+
+    /// Add a rule in ignore syntax (e.g. `*.log` or `!important.log`)
+    pub fn rule<S: AsRef<str>>(self, rule: S) -> Self {
+        match rule.as_ref().strip_prefix('!') {
+            Some(pattern) => self.include(pattern),
+            None => self.exclude(rule),
+        }
+    }
+
+    /// Returns whether there are no rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns whether `path`, relative to the root of the traversal, is ignored.
+    /// `is_dir` tells whether the path is a directory
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let relative = path.to_string_lossy();
+        let relative = relative.trim_start_matches('/');
+        let name = path
+            .file_name()
+            .map(|x| x.to_string_lossy())
+            .unwrap_or_default();
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                (is_dir || !rule.dir_only)
+                    && match rule.anchored {
+                        true => rule.matcher.matches(relative),
+                        false => rule.matcher.matches(name.as_ref()),
+                    }
+            })
+            .map(|rule| !rule.negate)
+            .unwrap_or(false)
+    }
+
+    fn push(mut self, pattern: &str, negate: bool) -> Self {
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        let anchored = pattern.contains('/');
+        self.rules.push(Rule {
+            matcher: WildMatch::new(pattern.trim_start_matches('/')),
+            anchored,
+            dir_only,
+            negate,
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_match_ignore_rules() {
+        let rules = IgnoreRules::default()
+            .exclude("*.log")
+            .include("important.log")
+            .exclude("target/")
+            .exclude("/docs/*.md");
+        assert_eq!(rules.is_ignored(Path::new("a.log"), false), true);
+        assert_eq!(rules.is_ignored(Path::new("src/debug.log"), false), true);
+        assert_eq!(
+            rules.is_ignored(Path::new("src/important.log"), false),
+            false
+        );
+        assert_eq!(rules.is_ignored(Path::new("src/target"), true), true);
+        assert_eq!(rules.is_ignored(Path::new("src/target"), false), false);
+        assert_eq!(rules.is_ignored(Path::new("docs/README.md"), false), true);
+        assert_eq!(rules.is_ignored(Path::new("src/README.md"), false), false);
+        assert_eq!(rules.is_ignored(Path::new("src/main.rs"), false), false);
+    }
+
+    #[test]
+    fn should_parse_ignore_file() {
+        let rules = IgnoreRules::parse("# build\ntarget/\n\n*.log\n!keep.log\n");
+        assert_eq!(
+            rules,
+            IgnoreRules::default()
+                .exclude("target/")
+                .exclude("*.log")
+                .include("keep.log")
+        );
+        assert_eq!(rules.is_ignored(Path::new("keep.log"), false), false);
+        assert_eq!(rules.is_ignored(Path::new("a.log"), false), true);
+        assert!(IgnoreRules::parse("# nothing").is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+//! ## List dir
+//!
+//! options for `list_dir_opts`
+
+#[cfg(feature = "find")]
+use wildmatch::WildMatch;
+
+use crate::File;
+
+/// Options for `list_dir_opts`.
+/// Backends may apply these options server-side (e.g. a glob on `ls` or a prefix on S3);
+/// everything they don't support is applied client-side by `ListDirOpts::apply`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListDirOpts {
+    /// Sort entries by this key. If `None` the order is the one returned by the backend
+    pub sort: Option<SortBy>,
+    /// Reverse the sort order
+    pub reverse: bool,
+    /// Exclude hidden files (see `File::is_hidden`)
+    pub skip_hidden: bool,
+    /// Don't resolve the targets of symbolic links, which some backends do with an extra request per link
+    /// (e.g. `readlink` on SFTP). In this case `Metadata::symlink` may be `None` for symlinks,
+    /// and the target can be resolved later with `stat` only for the entries which need it
+    pub skip_symlink_targets: bool,
+    /// Keep only entries whose name matches this glob (supports '?' and '*')
+    #[cfg(feature = "find")]
+    pub filter: Option<String>,
+}
+
+/// The key to sort directory entries by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortBy {
+    /// Sort by file name
+    Name,
+    /// Sort by file size
+    Size,
+    /// Sort by last modification time
+    Modified,
+}
+
+impl ListDirOpts {
+    /// Sort entries by `key`
+    pub fn sort(mut self, key: SortBy) -> Self {
+        self.sort = Some(key);
+        self
+    }
+
+    /// Reverse the sort order
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Exclude hidden files
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /// Don't resolve the targets of symbolic links
+    pub fn skip_symlink_targets(mut self, skip: bool) -> Self {
+        self.skip_symlink_targets = skip;
+        self
+    }
+
+    /// Keep only entries whose name matches `glob`
+    #[cfg(feature = "find")]
+    pub fn filter<S: ToString>(mut self, glob: S) -> Self {
+        self.filter = Some(glob.to_string());
+        self
+    }
+
+    /// Apply options to the entries returned by `list_dir`.
+    /// The `.` and `..` entries are always removed, whatever the options are
+    pub fn apply(&self, mut entries: Vec<File>) -> Vec<File> {
+        entries.retain(|x| !(is_special_entry(x) || (self.skip_hidden && x.is_hidden())));
+        #[cfg(feature = "find")]
+        if let Some(filter) = self.filter.as_deref() {
+            let filter = WildMatch::new(filter);
+            entries.retain(|x| filter.matches(x.name().as_str()));
+        }
+        if let Some(key) = self.sort {
+            entries.sort_by(|a, b| {
+                let ord = match key {
+                    SortBy::Name => a.name().cmp(&b.name()),
+                    SortBy::Size => a.metadata().size.cmp(&b.metadata().size),
+                    SortBy::Modified => a.metadata().modified.cmp(&b.metadata().modified),
+                };
+                ord.then_with(|| a.name().cmp(&b.name()))
+            });
+            if self.reverse {
+                entries.reverse();
+            }
+        }
+        entries
+    }
+}
+
+/// Returns whether `entry` is `.` or `..`.
+/// The path is checked as a string, since `Path::file_name` doesn't return trailing `.` and `..`
+fn is_special_entry(entry: &File) -> bool {
+    let path = entry.path().to_string_lossy();
+    matches!(path.rsplit(['/', '\\']).next(), Some(".") | Some(".."))
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::Metadata;
+
+    fn entries() -> Vec<File> {
+        vec![
+            File {
+                path: PathBuf::from("/b.txt"),
+                metadata: Metadata::default()
+                    .size(10)
+                    .modified(UNIX_EPOCH + Duration::from_secs(30)),
+            },
+            File {
+                path: PathBuf::from("/c.log"),
+                metadata: Metadata::default()
+                    .size(5)
+                    .modified(UNIX_EPOCH + Duration::from_secs(10)),
+            },
+            File {
+                path: PathBuf::from("/a.txt"),
+                metadata: Metadata::default()
+                    .size(20)
+                    .modified(UNIX_EPOCH + Duration::from_secs(20)),
+            },
+        ]
+    }
+
+    fn names(entries: Vec<File>) -> Vec<String> {
+        entries.iter().map(|x| x.name()).collect()
+    }
+
+    #[test]
+    fn should_keep_backend_order_by_default() {
+        assert_eq!(
+            names(ListDirOpts::default().apply(entries())),
+            vec!["b.txt", "c.log", "a.txt"]
+        );
+    }
+
+    #[test]
+    fn should_sort_entries() {
+        assert_eq!(
+            names(ListDirOpts::default().sort(SortBy::Name).apply(entries())),
+            vec!["a.txt", "b.txt", "c.log"]
+        );
+        assert_eq!(
+            names(ListDirOpts::default().sort(SortBy::Size).apply(entries())),
+            vec!["c.log", "b.txt", "a.txt"]
+        );
+        assert_eq!(
+            names(
+                ListDirOpts::default()
+                    .sort(SortBy::Modified)
+                    .reverse(true)
+                    .apply(entries())
+            ),
+            vec!["b.txt", "a.txt", "c.log"]
+        );
+    }
+
+    #[test]
+    fn should_skip_hidden_files() {
+        let mut entries = entries();
+        entries.push(File {
+            path: PathBuf::from("/.git"),
+            metadata: Metadata::default(),
+        });
+        assert_eq!(
+            names(ListDirOpts::default().apply(entries.clone())),
+            vec!["b.txt", "c.log", "a.txt", ".git"]
+        );
+        assert_eq!(
+            names(ListDirOpts::default().skip_hidden(true).apply(entries)),
+            vec!["b.txt", "c.log", "a.txt"]
+        );
+    }
+
+    #[test]
+    fn should_keep_symlink_targets_on_apply() {
+        let entries = vec![File {
+            path: PathBuf::from("/link"),
+            metadata: Metadata::default().symlink("/a.txt"),
+        }];
+        let opts = ListDirOpts::default().skip_symlink_targets(true);
+        assert_eq!(opts.skip_symlink_targets, true);
+        assert_eq!(opts.apply(entries.clone()), entries);
+    }
+
+    #[test]
+    fn should_never_return_special_entries() {
+        let mut entries = entries();
+        entries.push(File {
+            path: PathBuf::from("/tmp/."),
+            metadata: Metadata::default(),
+        });
+        entries.push(File {
+            path: PathBuf::from("/tmp/.."),
+            metadata: Metadata::default(),
+        });
+        assert_eq!(
+            names(ListDirOpts::default().apply(entries)),
+            vec!["b.txt", "c.log", "a.txt"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "find")]
+    fn should_filter_entries() {
+        assert_eq!(
+            names(
+                ListDirOpts::default()
+                    .filter("*.txt")
+                    .sort(SortBy::Name)
+                    .apply(entries())
+            ),
+            vec!["a.txt", "b.txt"]
+        );
+    }
+}
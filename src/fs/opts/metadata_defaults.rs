@@ -0,0 +1,122 @@
+//! ## Metadata defaults
+//!
+//! per-client default metadata for newly created files
+
+use crate::fs::{Metadata, UnixPex};
+
+/// Default metadata used by a client when creating files and directories, as `umask` does for local files.
+/// Backends hold these on their builders and consume them when callers don't set a field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataDefaults {
+    /// Mode of newly created files, if not set by the caller (default: 0o644)
+    pub file_mode: UnixPex,
+    /// Mode of newly created directories (default: 0o755)
+    pub dir_mode: UnixPex,
+    /// Metadata whose fields are used for any field not set by the caller (e.g. `uid` and `gid`)
+    pub metadata: Metadata,
+}
+
+impl Default for MetadataDefaults {
+    fn default() -> Self {
+        Self {
+            file_mode: UnixPex::from(0o644),
+            dir_mode: UnixPex::from(0o755),
+            metadata: Metadata::default(),
+        }
+    }
+}
+
+impl MetadataDefaults {
+    /// Set default mode for files
+    pub fn default_file_mode(mut self, mode: UnixPex) -> Self {
+        self.file_mode = mode;
+        self
+    }
+
+    /// Set default mode for directories
+    pub fn default_dir_mode(mut self, mode: UnixPex) -> Self {
+        self.dir_mode = mode;
+        self
+    }
+
+    /// Set default metadata
+    pub fn default_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Get the metadata to create a file with, filling the fields not set in `metadata` with the defaults
+    pub fn apply_file(&self, metadata: &Metadata) -> Metadata {
+        let mut metadata = metadata.clone();
+        metadata.mode = metadata
+            .mode
+            .or(self.metadata.mode)
+            .or(Some(self.file_mode));
+        metadata.uid = metadata.uid.or(self.metadata.uid);
+        metadata.gid = metadata.gid.or(self.metadata.gid);
+        if metadata.acl.is_none() {
+            metadata.acl = self.metadata.acl.clone();
+        }
+        for (key, value) in self.metadata.attributes.iter() {
+            metadata
+                .attributes
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        metadata
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_create_metadata_defaults() {
+        let defaults = MetadataDefaults::default();
+        assert_eq!(defaults.file_mode, UnixPex::from(0o644));
+        assert_eq!(defaults.dir_mode, UnixPex::from(0o755));
+        assert_eq!(defaults.metadata, Metadata::default());
+        let defaults = MetadataDefaults::default()
+            .default_file_mode(UnixPex::from(0o600))
+            .default_dir_mode(UnixPex::from(0o700))
+            .default_metadata(Metadata::default().uid(1000));
+        assert_eq!(defaults.file_mode, UnixPex::from(0o600));
+        assert_eq!(defaults.dir_mode, UnixPex::from(0o700));
+        assert_eq!(defaults.metadata.uid, Some(1000));
+    }
+
+    #[test]
+    fn should_apply_defaults_to_unset_fields() {
+        let defaults = MetadataDefaults::default().default_metadata(
+            Metadata::default()
+                .uid(1000)
+                .gid(1000)
+                .attribute("owner", "ops"),
+        );
+        let metadata = defaults.apply_file(&Metadata::default());
+        assert_eq!(metadata.mode, Some(UnixPex::from(0o644)));
+        assert_eq!(metadata.uid, Some(1000));
+        assert_eq!(metadata.gid, Some(1000));
+        assert_eq!(
+            metadata.attributes.get("owner").map(String::as_str),
+            Some("ops")
+        );
+        let metadata = defaults.apply_file(
+            &Metadata::default()
+                .mode(UnixPex::from(0o755))
+                .uid(0)
+                .attribute("owner", "dev"),
+        );
+        assert_eq!(metadata.mode, Some(UnixPex::from(0o755)));
+        assert_eq!(metadata.uid, Some(0));
+        assert_eq!(metadata.gid, Some(1000));
+        assert_eq!(
+            metadata.attributes.get("owner").map(String::as_str),
+            Some("dev")
+        );
+    }
+}
@@ -0,0 +1,29 @@
+//! ## Opts
+//!
+//! options types for `RemoteFs` methods
+
+mod connect;
+mod copy;
+mod error_policy;
+mod grep;
+#[cfg(feature = "find")]
+mod ignore;
+mod list_dir;
+mod metadata_defaults;
+mod mov;
+mod stream;
+mod transfer;
+mod walk_dir;
+
+pub use self::connect::{ConnectOpts, PhaseCallback};
+pub use self::copy::{CopyOpts, PreserveMode};
+pub use self::error_policy::ErrorPolicy;
+pub use self::grep::GrepOpts;
+#[cfg(feature = "find")]
+pub use self::ignore::IgnoreRules;
+pub use self::list_dir::{ListDirOpts, SortBy};
+pub use self::metadata_defaults::MetadataDefaults;
+pub use self::mov::MoveOpts;
+pub use self::stream::StreamOpts;
+pub use self::transfer::{ProgressCallback, TransferOpts};
+pub use self::walk_dir::WalkDirOpts;
@@ -0,0 +1,19 @@
+//! ## Mov
+//!
+//! options for `move_with`
+
+/// Options for `move_with`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MoveOpts {
+    /// If the remote can't move `src` to `dest` (`UnsupportedFeature` or `CrossDevice`),
+    /// copy `src` to `dest` and remove it instead
+    pub copy_fallback: bool,
+}
+
+impl MoveOpts {
+    /// Fall back to copy and delete if the remote can't move the file
+    pub fn copy_fallback(mut self, fallback: bool) -> Self {
+        self.copy_fallback = fallback;
+        self
+    }
+}
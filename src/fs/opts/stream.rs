@@ -0,0 +1,56 @@
+//! ## Stream
+//!
+//! options for `open_with`, `create_with` and `append_with`
+
+use crate::fs::checksum::ChecksumAlgorithm;
+use crate::fs::{ReadStream, WriteStream};
+
+/// Options for the streams returned by `open_with`, `create_with` and `append_with`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamOpts {
+    /// Size of the buffer wrapping the stream.
+    /// If `None`, the stream is returned as the backend provides it.
+    /// `Some(0)` is a hint asking for an unbuffered stream: only backends implementing `open_with`, `create_with`
+    /// and `append_with` can drop their own buffering, while the default implementations return the stream
+    /// as `open`, `create` and `append` provide it
+    pub buffer_size: Option<usize>,
+    /// Compute the checksum of the data flowing through the stream with this algorithm.
+    /// The checksum is returned by `checksum()` on the stream and in the `WriteReport` returned by `on_written_ext`
+    pub checksum: Option<ChecksumAlgorithm>,
+}
+
+impl StreamOpts {
+    /// Set the size of the buffer wrapping the stream; `0` asks for an unbuffered stream, where supported
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = Some(size);
+        self
+    }
+
+    /// Compute the checksum of the data flowing through the stream with `algorithm`
+    pub fn checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum = Some(algorithm);
+        self
+    }
+
+    /// Apply options to a stream returned by `open`. A zero `buffer_size` leaves the stream as it is
+    pub fn apply_read(&self, mut stream: ReadStream) -> ReadStream {
+        if let Some(size) = self.buffer_size.filter(|x| *x > 0) {
+            stream = stream.buffered(size);
+        }
+        if let Some(algorithm) = self.checksum {
+            stream = stream.with_checksum(algorithm);
+        }
+        stream
+    }
+
+    /// Apply options to a stream returned by `create` or `append`. A zero `buffer_size` leaves the stream as it is
+    pub fn apply_write(&self, mut stream: WriteStream) -> WriteStream {
+        if let Some(size) = self.buffer_size.filter(|x| *x > 0) {
+            stream = stream.buffered(size);
+        }
+        if let Some(algorithm) = self.checksum {
+            stream = stream.with_checksum(algorithm);
+        }
+        stream
+    }
+}
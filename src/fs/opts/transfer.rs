@@ -0,0 +1,85 @@
+//! ## Transfer
+//!
+//! options for `copy_stream`
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Default size of the buffer used to copy data
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Callback reporting the amount of bytes copied so far
+pub type ProgressCallback = Arc<dyn Fn(u64) + Send + Sync>;
+
+/// Options for `copy_stream`
+#[derive(Clone)]
+pub struct TransferOpts {
+    /// Size of the buffer used to copy data, 64 KiB by default
+    pub buffer_size: usize,
+    /// When set to `true`, the copy is stopped with a `Cancelled` error
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Maximum transfer rate, in bytes per second
+    pub max_rate: Option<u64>,
+    /// Called after each chunk is copied, with the amount of bytes copied so far
+    pub progress: Option<ProgressCallback>,
+}
+
+impl Default for TransferOpts {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            cancel: None,
+            max_rate: None,
+            progress: None,
+        }
+    }
+}
+
+impl fmt::Debug for TransferOpts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransferOpts")
+            .field("buffer_size", &self.buffer_size)
+            .field("cancel", &self.cancel)
+            .field("max_rate", &self.max_rate)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl TransferOpts {
+    /// Set the size of the buffer used to copy data
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size.max(1);
+        self
+    }
+
+    /// Stop the copy when `flag` is set to `true`
+    pub fn cancel(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Limit the transfer rate to `bytes_per_sec`
+    pub fn max_rate(mut self, bytes_per_sec: u64) -> Self {
+        self.max_rate = Some(bytes_per_sec);
+        self
+    }
+
+    /// Call `callback` with the amount of bytes copied so far, after each chunk is copied
+    pub fn progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Returns whether the copy has been cancelled
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .map(|x| x.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,50 @@
+//! ## Walk dir
+//!
+//! options for `walk_dir`
+
+#[cfg(feature = "find")]
+use super::IgnoreRules;
+
+/// Options for `walk_dir`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkDirOpts {
+    /// Descend into directories pointed by symbolic links.
+    /// Symlink cycles are detected and reported as `SymlinkLoop` errors
+    pub follow_symlinks: bool,
+    /// Rules to exclude entries from the walk, matched against their path relative to the walked directory.
+    /// Excluded directories are not descended into
+    #[cfg(feature = "find")]
+    pub ignore: Option<IgnoreRules>,
+    /// Maximum depth to descend to; entries of the walked directory have depth 1.
+    /// If `None` there is no limit
+    pub max_depth: Option<usize>,
+    /// Exclude hidden files and don't descend into hidden directories
+    pub skip_hidden: bool,
+}
+
+impl WalkDirOpts {
+    /// Descend into directories pointed by symbolic links
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Exclude the entries matching `rules`
+    #[cfg(feature = "find")]
+    pub fn ignore(mut self, rules: IgnoreRules) -> Self {
+        self.ignore = Some(rules);
+        self
+    }
+
+    /// Set the maximum depth to descend to
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Exclude hidden files
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+}
@@ -0,0 +1,176 @@
+//! ## Report
+//!
+//! report data types
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::RemoteError;
+
+/// Structure holding all data related to a finalized write on the remote host.
+/// Fields other than `bytes` are filled only by backends which get them for free (e.g. the ETag returned by S3 on put,
+/// or the mtime returned by a `fstat` on SFTP).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WriteReport {
+    /// Amount of bytes written to the remote file
+    pub bytes: u64,
+    /// Checksum of the written content, if known
+    pub checksum: Option<String>,
+    /// Entity tag of the written file, if known
+    pub etag: Option<String>,
+    /// Last modification time of the written file, if known
+    pub modified: Option<SystemTime>,
+}
+
+impl WriteReport {
+    /// Set amount of bytes written
+    pub fn bytes(mut self, bytes: u64) -> Self {
+        self.bytes = bytes;
+        self
+    }
+
+    /// Set checksum of the written content
+    pub fn checksum<S: ToString>(mut self, checksum: S) -> Self {
+        self.checksum = Some(checksum.to_string());
+        self
+    }
+
+    /// Set entity tag of the written file
+    pub fn etag<S: ToString>(mut self, etag: S) -> Self {
+        self.etag = Some(etag.to_string());
+        self
+    }
+
+    /// Set last modification time of the written file
+    pub fn modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+}
+
+/// Structure holding the outcome of a command executed on the remote host with `exec_bytes`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExecOutput {
+    /// Exit code of the command
+    pub exit_code: u32,
+    /// Raw standard output of the command
+    pub stdout: Vec<u8>,
+    /// Raw standard error of the command, if captured by the backend
+    pub stderr: Vec<u8>,
+}
+
+impl ExecOutput {
+    /// Instantiates a new `ExecOutput`
+    pub fn new(exit_code: u32, stdout: Vec<u8>, stderr: Vec<u8>) -> Self {
+        Self {
+            exit_code,
+            stdout,
+            stderr,
+        }
+    }
+
+    /// Returns whether the command exited successfully
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    /// Get standard output as a string, replacing invalid UTF-8 sequences
+    pub fn stdout_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+
+    /// Get standard error as a string, replacing invalid UTF-8 sequences
+    pub fn stderr_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
+}
+
+/// A line matching the pattern searched with `grep`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GrepMatch {
+    /// Path of the file containing the line
+    pub path: PathBuf,
+    /// Number of the line, starting from 1
+    pub line_number: usize,
+    /// Content of the line, without the line terminator
+    pub line: String,
+}
+
+impl GrepMatch {
+    /// Instantiates a new `GrepMatch`
+    pub fn new<P: AsRef<Path>, S: ToString>(path: P, line_number: usize, line: S) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            line_number,
+            line: line.to_string(),
+        }
+    }
+}
+
+/// Structure holding the outcome of a bulk operation (such as `remove_dir_all_with`)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BulkReport {
+    /// Amount of entries successfully processed
+    pub succeeded: u64,
+    /// Entries which couldn't be processed, with the related error.
+    /// This can be non-empty only if the operation was run with `ErrorPolicy::Continue`
+    pub failures: Vec<(PathBuf, RemoteError)>,
+}
+
+impl BulkReport {
+    /// Returns whether all the entries have been processed successfully
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::UNIX_EPOCH;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::RemoteErrorType;
+
+    #[test]
+    fn should_create_write_report() {
+        let report = WriteReport::default();
+        assert_eq!(report.bytes, 0);
+        assert!(report.checksum.is_none());
+        assert!(report.etag.is_none());
+        assert!(report.modified.is_none());
+        let report = WriteReport::default()
+            .bytes(1024)
+            .checksum("cbf43926")
+            .etag("\"d41d8cd98f00b204e9800998ecf8427e\"")
+            .modified(UNIX_EPOCH);
+        assert_eq!(report.bytes, 1024);
+        assert_eq!(report.checksum.as_deref().unwrap(), "cbf43926");
+        assert_eq!(
+            report.etag.as_deref().unwrap(),
+            "\"d41d8cd98f00b204e9800998ecf8427e\""
+        );
+        assert_eq!(report.modified, Some(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn should_create_exec_output() {
+        let output = ExecOutput::new(0, vec![0x68, 0x69, 0xff], b"oops".to_vec());
+        assert!(output.success());
+        assert_eq!(output.stdout_lossy(), "hi\u{fffd}");
+        assert_eq!(output.stderr_lossy(), "oops");
+        assert!(!ExecOutput::new(1, vec![], vec![]).success());
+    }
+
+    #[test]
+    fn should_tell_whether_bulk_report_is_ok() {
+        let mut report = BulkReport::default();
+        assert!(report.is_ok());
+        report.failures.push((
+            PathBuf::from("/a.txt"),
+            RemoteError::new(RemoteErrorType::PexError),
+        ));
+        assert!(!report.is_ok());
+    }
+}
@@ -0,0 +1,216 @@
+//! ## Semantics
+//!
+//! rules of the remote file system about paths
+
+use std::path::{Component, Path};
+
+use thiserror::Error;
+
+/// Describes the rules the remote file system applies to paths, which can be used to validate a path before
+/// sending it to the remote (see `RemoteFs::validate_path`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Semantics {
+    /// Maximum length of a path in bytes
+    pub max_path_len: Option<usize>,
+    /// Maximum length of a file name in bytes
+    pub max_name_len: Option<usize>,
+    /// Characters which can't appear in a file name, in addition to path separators
+    pub forbidden_chars: Vec<char>,
+    /// Whether control characters (0x00-0x1F) are forbidden in file names
+    pub forbid_control_chars: bool,
+    /// Reserved file names (case insensitive; extensions are ignored)
+    pub reserved_names: Vec<String>,
+    /// Whether file names can end with a dot or a space
+    pub allow_trailing_dot_or_space: bool,
+}
+
+impl Semantics {
+    /// Rules of POSIX file systems (e.g. SFTP and SCP on a unix server)
+    pub fn posix() -> Self {
+        Self {
+            max_path_len: Some(4096),
+            max_name_len: Some(255),
+            forbidden_chars: vec!['\0'],
+            forbid_control_chars: false,
+            reserved_names: vec![],
+            allow_trailing_dot_or_space: true,
+        }
+    }
+
+    /// Rules of Windows file systems (e.g. SFTP or FTP on a Windows server)
+    pub fn windows() -> Self {
+        let mut reserved_names: Vec<String> = ["CON", "PRN", "AUX", "NUL"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        for i in 1..=9 {
+            reserved_names.push(format!("COM{i}"));
+            reserved_names.push(format!("LPT{i}"));
+        }
+        Self {
+            max_path_len: Some(260),
+            max_name_len: Some(255),
+            forbidden_chars: vec!['<', '>', ':', '"', '|', '?', '*'],
+            forbid_control_chars: true,
+            reserved_names,
+            allow_trailing_dot_or_space: false,
+        }
+    }
+
+    /// Rules of object stores (e.g. S3), where the path is the object key
+    pub fn object_store() -> Self {
+        Self {
+            max_path_len: Some(1024),
+            max_name_len: None,
+            forbidden_chars: vec![],
+            forbid_control_chars: false,
+            reserved_names: vec![],
+            allow_trailing_dot_or_space: true,
+        }
+    }
+
+    /// Check whether `path` is valid according to these rules
+    pub fn validate(&self, path: &Path) -> Result<(), PathError> {
+        let len = path.as_os_str().len();
+        if let Some(max) = self.max_path_len.filter(|max| len > *max) {
+            return Err(PathError::PathTooLong { len, max });
+        }
+        for component in path.components() {
+            if let Component::Normal(name) = component {
+                self.validate_name(name.to_string_lossy().as_ref())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_name(&self, name: &str) -> Result<(), PathError> {
+        if let Some(max) = self.max_name_len.filter(|max| name.len() > *max) {
+            return Err(PathError::NameTooLong {
+                name: name.to_string(),
+                max,
+            });
+        }
+        if let Some(c) = name.chars().find(|c| {
+            self.forbidden_chars.contains(c) || (self.forbid_control_chars && c.is_ascii_control())
+        }) {
+            return Err(PathError::InvalidChar {
+                name: name.to_string(),
+                c,
+            });
+        }
+        let stem = name.split('.').next().unwrap_or_default();
+        if self
+            .reserved_names
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(stem))
+        {
+            return Err(PathError::ReservedName(name.to_string()));
+        }
+        if !self.allow_trailing_dot_or_space && (name.ends_with('.') || name.ends_with(' ')) {
+            return Err(PathError::TrailingDotOrSpace(name.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Semantics {
+    fn default() -> Self {
+        Self::posix()
+    }
+}
+
+/// Describes why a path is not valid on the remote file system
+#[derive(Error, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathError {
+    #[error("path is too long ({len} bytes; max {max})")]
+    PathTooLong { len: usize, max: usize },
+    #[error("file name '{name}' is too long (max {max} bytes)")]
+    NameTooLong { name: String, max: usize },
+    #[error("file name '{name}' contains invalid character {c:?}")]
+    InvalidChar { name: String, c: char },
+    #[error("file name '{0}' is reserved")]
+    ReservedName(String),
+    #[error("file name '{0}' ends with a dot or a space")]
+    TrailingDotOrSpace(String),
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_validate_posix_paths() {
+        let semantics = Semantics::posix();
+        assert!(semantics
+            .validate(Path::new("/home/omar/my file: \"v2\".txt"))
+            .is_ok());
+        assert_eq!(
+            semantics
+                .validate(Path::new(&format!("/tmp/{}", "a".repeat(256))))
+                .unwrap_err(),
+            PathError::NameTooLong {
+                name: "a".repeat(256),
+                max: 255
+            }
+        );
+        assert_eq!(
+            semantics
+                .validate(Path::new(&"/a".repeat(2049)))
+                .unwrap_err(),
+            PathError::PathTooLong {
+                len: 4098,
+                max: 4096
+            }
+        );
+    }
+
+    #[test]
+    fn should_validate_windows_paths() {
+        let semantics = Semantics::windows();
+        assert!(semantics
+            .validate(Path::new("/inetpub/wwwroot/index.html"))
+            .is_ok());
+        assert_eq!(
+            semantics.validate(Path::new("/tmp/what?.txt")).unwrap_err(),
+            PathError::InvalidChar {
+                name: "what?.txt".to_string(),
+                c: '?'
+            }
+        );
+        assert_eq!(
+            semantics
+                .validate(Path::new("/tmp/line\nbreak"))
+                .unwrap_err(),
+            PathError::InvalidChar {
+                name: "line\nbreak".to_string(),
+                c: '\n'
+            }
+        );
+        assert_eq!(
+            semantics.validate(Path::new("/tmp/nul.txt")).unwrap_err(),
+            PathError::ReservedName("nul.txt".to_string())
+        );
+        assert_eq!(
+            semantics.validate(Path::new("/tmp/readme.")).unwrap_err(),
+            PathError::TrailingDotOrSpace("readme.".to_string())
+        );
+    }
+
+    #[test]
+    fn should_validate_object_store_paths() {
+        let semantics = Semantics::object_store();
+        assert!(semantics.validate(Path::new("/bucket/what?.txt")).is_ok());
+        assert!(semantics.validate(Path::new(&"/a".repeat(513))).is_err());
+    }
+
+    #[test]
+    fn should_format_path_errors() {
+        assert_eq!(
+            PathError::ReservedName("CON".to_string()).to_string(),
+            "file name 'CON' is reserved"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! ## Snapshot
+//!
+//! snapshot of a directory tree, to compute the changes between two points in time
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::{File, FileType};
+
+/// The state of an entry in a `Snapshot`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SnapshotEntry {
+    /// File type
+    pub file_type: FileType,
+    /// File size in bytes
+    pub size: u64,
+    /// Modify time
+    pub modified: Option<SystemTime>,
+    /// Checksum of the content, if computed
+    pub checksum: Option<String>,
+}
+
+impl SnapshotEntry {
+    /// Make a snapshot entry from `file`, without checksum
+    pub fn from_file(file: &File) -> Self {
+        Self {
+            file_type: file.metadata().file_type.clone(),
+            size: file.metadata().size,
+            modified: file.metadata().modified,
+            checksum: None,
+        }
+    }
+
+    /// Set checksum of the content
+    pub fn checksum<S: ToString>(mut self, checksum: S) -> Self {
+        self.checksum = Some(checksum.to_string());
+        self
+    }
+
+    /// Returns whether the entry changed from `other`.
+    /// Checksums are compared if both entries have one; otherwise size and modify time are compared.
+    /// Directories change only if their type changes
+    pub fn changed_from(&self, other: &Self) -> bool {
+        if self.file_type != other.file_type {
+            return true;
+        }
+        if self.file_type.is_dir() {
+            return false;
+        }
+        match (self.checksum.as_ref(), other.checksum.as_ref()) {
+            (Some(a), Some(b)) => a != b,
+            _ => self.size != other.size || self.modified != other.modified,
+        }
+    }
+}
+
+/// A change between two snapshots. Paths are relative to the snapshot root
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Change {
+    /// The entry exists only in the newer snapshot
+    Added(PathBuf),
+    /// The entry exists in both snapshots, but it changed
+    Modified(PathBuf),
+    /// The entry exists only in the older snapshot
+    Removed(PathBuf),
+}
+
+impl Change {
+    /// Get the path of the changed entry
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Added(p) | Self::Modified(p) | Self::Removed(p) => p.as_path(),
+        }
+    }
+}
+
+/// The state of a directory tree at a point in time, taken with `RemoteFs::snapshot`.
+/// Entries are stored by path relative to the root, so that snapshots of different roots
+/// (e.g. of a local and of a remote directory) can be compared
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    root: PathBuf,
+    entries: BTreeMap<PathBuf, SnapshotEntry>,
+}
+
+impl Snapshot {
+    /// Instantiates a new empty `Snapshot` of `root`
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Get the root of the snapshot
+    pub fn root(&self) -> &Path {
+        self.root.as_path()
+    }
+
+    /// Insert `entry` at `path`, relative to the root
+    pub fn insert<P: AsRef<Path>>(&mut self, path: P, entry: SnapshotEntry) {
+        self.entries.insert(path.as_ref().to_path_buf(), entry);
+    }
+
+    /// Remove the entries for which `f` returns false
+    pub fn retain<F: FnMut(&Path, &SnapshotEntry) -> bool>(&mut self, mut f: F) {
+        self.entries.retain(|k, v| f(k.as_path(), v));
+    }
+
+    /// Get the entry at `path`, relative to the root
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&SnapshotEntry> {
+        self.entries.get(path.as_ref())
+    }
+
+    /// Iterate over the entries, sorted by path
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &SnapshotEntry)> {
+        self.entries.iter().map(|(k, v)| (k.as_path(), v))
+    }
+
+    /// Returns the amount of entries in the snapshot
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the snapshot is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the changes from this snapshot to `newer`, sorted by path
+    pub fn diff(&self, newer: &Snapshot) -> Vec<Change> {
+        let mut changes: Vec<Change> = Vec::new();
+        for (path, entry) in self.entries.iter() {
+            match newer.entries.get(path) {
+                None => changes.push(Change::Removed(path.clone())),
+                Some(new) if new.changed_from(entry) => {
+                    changes.push(Change::Modified(path.clone()))
+                }
+                Some(_) => {}
+            }
+        }
+        changes.extend(
+            newer
+                .entries
+                .keys()
+                .filter(|x| !self.entries.contains_key(*x))
+                .map(|x| Change::Added(x.clone())),
+        );
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        changes
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn file(size: u64, modified: u64) -> SnapshotEntry {
+        SnapshotEntry {
+            file_type: FileType::File,
+            size,
+            modified: Some(UNIX_EPOCH + Duration::from_secs(modified)),
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn should_diff_snapshots() {
+        let mut old = Snapshot::new("/home/omar");
+        old.insert("a.txt", file(10, 100));
+        old.insert("b.txt", file(10, 100));
+        old.insert("c.txt", file(10, 100));
+        let mut new = Snapshot::new("/backup/omar");
+        new.insert("a.txt", file(10, 100));
+        new.insert("b.txt", file(12, 200));
+        new.insert("d.txt", file(10, 100));
+        assert_eq!(old.len(), 3);
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                Change::Modified(PathBuf::from("b.txt")),
+                Change::Removed(PathBuf::from("c.txt")),
+                Change::Added(PathBuf::from("d.txt")),
+            ]
+        );
+        assert!(old.diff(&old).is_empty());
+        old.retain(|path, _| path != Path::new("c.txt"));
+        assert_eq!(old.len(), 2);
+        assert!(old.get("c.txt").is_none());
+    }
+
+    #[test]
+    fn should_compare_checksums_if_available() {
+        let a = file(10, 100).checksum("cbf43926");
+        assert_eq!(a.changed_from(&file(10, 200).checksum("cbf43926")), false);
+        assert_eq!(a.changed_from(&file(10, 100).checksum("00000000")), true);
+        assert_eq!(a.changed_from(&file(10, 100)), false);
+        let dir = SnapshotEntry {
+            file_type: FileType::Directory,
+            size: 4096,
+            modified: None,
+            checksum: None,
+        };
+        assert_eq!(
+            dir.changed_from(&SnapshotEntry {
+                size: 8192,
+                ..dir.clone()
+            }),
+            false
+        );
+        assert_eq!(dir.changed_from(&a), true);
+    }
+}
@@ -1,17 +1,37 @@
 //! ## Stream
 //!
-//! this module exposes the streams returned by create, append and open methods
+//! this module exposes the streams returned by create, append and open methods.
+//!
+//! Streams are always `Send`, so transfers can be moved to worker threads.
+//! Streams can carry tags, which clients and decorators use to recognize their streams when they're finalized.
+
+use std::any::Any;
+use std::io::{
+    BufReader, BufWriter, Cursor, Error as IoError, ErrorKind as IoErrorKind, Read, Seek, Write,
+};
+
+use super::checksum::{ChecksumAlgorithm, Digest};
 
-use std::io::{Error as IoError, ErrorKind as IoErrorKind, Read, Seek, Write};
+/// A value attached to a stream with `tag`
+pub type StreamTag = Box<dyn Any + Send>;
+
+/// Remove the last tag of type `T` from `tags`
+fn take_tag<T: Any + Send>(tags: &mut Vec<StreamTag>) -> Option<T> {
+    let index = tags.iter().rposition(|x| x.is::<T>())?;
+    tags.remove(index).downcast().ok().map(|x| *x)
+}
 
 // -- read stream
 
 /// A trait which combines `io::Read` and `io::Seek` together
 pub trait ReadAndSeek: Read + Seek + Send {}
 
-/// The stream returned by RemoteFs to read a file from the remote server
+/// The stream returned by RemoteFs to read a file from the remote server.
+/// The stream is `Send`, since the inner reader is required to be `Send`
 pub struct ReadStream {
     stream: StreamReader,
+    digest: Option<Box<dyn Digest>>,
+    tags: Vec<StreamTag>,
 }
 
 /// The kind of stream contained in the stream. Can be Read only or Read + Seek
@@ -25,12 +45,72 @@ impl ReadStream {
     pub fn seekable(&self) -> bool {
         matches!(self.stream, StreamReader::ReadAndSeek(_))
     }
+
+    /// Compute the checksum of the data read from the stream with `algorithm`.
+    /// The checksum covers the bytes in the order they're read, so it's meaningful only if the stream is not seeked
+    pub fn with_checksum(self, algorithm: ChecksumAlgorithm) -> Self {
+        self.with_digest(algorithm.digest())
+    }
+
+    /// Compute the checksum of the data read from the stream with a custom `digest`
+    pub fn with_digest(mut self, digest: Box<dyn Digest>) -> Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    /// Returns the checksum of the data read so far, if the stream computes it
+    pub fn checksum(&self) -> Option<String> {
+        self.digest.as_ref().map(|x| x.hex_digest())
+    }
+
+    /// Attach `tag` to the stream
+    pub fn tag<T: Any + Send>(mut self, tag: T) -> Self {
+        self.tags.push(Box::new(tag));
+        self
+    }
+
+    /// Remove and return the last tag of type `T` attached to the stream
+    pub fn take_tag<T: Any + Send>(&mut self) -> Option<T> {
+        take_tag(&mut self.tags)
+    }
+
+    /// Remove and return all the tags attached to the stream.
+    /// Decorators wrapping a stream into a new one must move the tags to the new stream with `with_tags`
+    pub fn take_tags(&mut self) -> Vec<StreamTag> {
+        std::mem::take(&mut self.tags)
+    }
+
+    /// Attach `tags` to the stream
+    pub fn with_tags(mut self, tags: Vec<StreamTag>) -> Self {
+        self.tags.extend(tags);
+        self
+    }
+
+    /// Wrap the stream into a buffered reader with the provided `capacity`.
+    /// The returned stream is seekable if this stream is and carries its tags
+    pub fn buffered(mut self, capacity: usize) -> Self {
+        let tags = self.take_tags();
+        let stream = if self.seekable() {
+            let reader: Box<dyn ReadAndSeek> = Box::new(BufReader::with_capacity(capacity, self));
+            Self::from(reader)
+        } else {
+            let reader: Box<dyn Read + Send> = Box::new(BufReader::with_capacity(capacity, self));
+            Self::from(reader)
+        };
+        stream.with_tags(tags)
+    }
 }
 
+impl ReadAndSeek for BufReader<ReadStream> {}
+
+impl ReadAndSeek for Cursor<Vec<u8>> {}
+
 impl From<Box<dyn Read + Send>> for ReadStream {
     fn from(reader: Box<dyn Read + Send>) -> Self {
         Self {
             stream: StreamReader::Read(reader),
+            digest: None,
+            tags: Vec::new(),
         }
     }
 }
@@ -39,13 +119,19 @@ impl From<Box<dyn ReadAndSeek>> for ReadStream {
     fn from(reader: Box<dyn ReadAndSeek>) -> Self {
         Self {
             stream: StreamReader::ReadAndSeek(reader),
+            digest: None,
+            tags: Vec::new(),
         }
     }
 }
 
 impl Read for ReadStream {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.stream.read(buf)
+        let read = self.stream.read(buf)?;
+        if let Some(digest) = self.digest.as_mut() {
+            digest.update(&buf[..read]);
+        }
+        Ok(read)
     }
 }
 
@@ -81,9 +167,13 @@ impl Seek for StreamReader {
 /// A trait which combines `io::Write` and `io::Seek` together
 pub trait WriteAndSeek: Write + Seek + Send {}
 
-/// The stream returned by RemoteFs to write a file from the remote server
+/// The stream returned by RemoteFs to write a file from the remote server.
+/// The stream is `Send`, since the inner writer is required to be `Send`
 pub struct WriteStream {
     stream: StreamWriter,
+    written: u64,
+    digest: Option<Box<dyn Digest>>,
+    tags: Vec<StreamTag>,
 }
 
 /// The kind of stream contained in the stream. Can be Write only or Write + Seek
@@ -97,12 +187,80 @@ impl WriteStream {
     pub fn seekable(&self) -> bool {
         matches!(self.stream, StreamWriter::WriteAndSeek(_))
     }
+
+    /// Returns the amount of bytes written to the stream so far
+    pub fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
+    /// Compute the checksum of the data written to the stream with `algorithm`.
+    /// The checksum covers the bytes in the order they're written, so it's meaningful only if the stream is not seeked
+    pub fn with_checksum(self, algorithm: ChecksumAlgorithm) -> Self {
+        self.with_digest(algorithm.digest())
+    }
+
+    /// Compute the checksum of the data written to the stream with a custom `digest`
+    pub fn with_digest(mut self, digest: Box<dyn Digest>) -> Self {
+        self.digest = Some(digest);
+        self
+    }
+
+    /// Returns the checksum of the data written so far, if the stream computes it
+    pub fn checksum(&self) -> Option<String> {
+        self.digest.as_ref().map(|x| x.hex_digest())
+    }
+
+    /// Attach `tag` to the stream
+    pub fn tag<T: Any + Send>(mut self, tag: T) -> Self {
+        self.tags.push(Box::new(tag));
+        self
+    }
+
+    /// Remove and return the last tag of type `T` attached to the stream
+    pub fn take_tag<T: Any + Send>(&mut self) -> Option<T> {
+        take_tag(&mut self.tags)
+    }
+
+    /// Remove and return all the tags attached to the stream.
+    /// Decorators wrapping a stream into a new one must move the tags to the new stream with `with_tags`
+    pub fn take_tags(&mut self) -> Vec<StreamTag> {
+        std::mem::take(&mut self.tags)
+    }
+
+    /// Attach `tags` to the stream
+    pub fn with_tags(mut self, tags: Vec<StreamTag>) -> Self {
+        self.tags.extend(tags);
+        self
+    }
+
+    /// Wrap the stream into a buffered writer with the provided `capacity`.
+    /// The returned stream is seekable if this stream is and carries its tags.
+    /// The buffer is flushed when the stream is dropped, but flush errors are lost then:
+    /// call `flush` before finalizing the stream to handle them
+    pub fn buffered(mut self, capacity: usize) -> Self {
+        let written = self.written;
+        let tags = self.take_tags();
+        let mut stream = if self.seekable() {
+            let writer: Box<dyn WriteAndSeek> = Box::new(BufWriter::with_capacity(capacity, self));
+            Self::from(writer)
+        } else {
+            let writer: Box<dyn Write + Send> = Box::new(BufWriter::with_capacity(capacity, self));
+            Self::from(writer)
+        };
+        stream.written = written;
+        stream.with_tags(tags)
+    }
 }
 
+impl WriteAndSeek for BufWriter<WriteStream> {}
+
 impl From<Box<dyn Write + Send>> for WriteStream {
     fn from(writer: Box<dyn Write + Send>) -> Self {
         Self {
             stream: StreamWriter::Write(writer),
+            written: 0,
+            digest: None,
+            tags: Vec::new(),
         }
     }
 }
@@ -111,13 +269,21 @@ impl From<Box<dyn WriteAndSeek>> for WriteStream {
     fn from(writer: Box<dyn WriteAndSeek>) -> Self {
         Self {
             stream: StreamWriter::WriteAndSeek(writer),
+            written: 0,
+            digest: None,
+            tags: Vec::new(),
         }
     }
 }
 
 impl Write for WriteStream {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.stream.write(buf)
+        let written = self.stream.write(buf)?;
+        self.written += written as u64;
+        if let Some(digest) = self.digest.as_mut() {
+            digest.update(&buf[..written]);
+        }
+        Ok(written)
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
@@ -177,7 +343,7 @@ mod test {
         let file: Box<dyn Read + Send> =
             Box::new(File::open(temp.path()).expect("Could not open tempfile"));
         let s = ReadStream::from(file);
-        assert_eq!(s.seekable(), false);
+        assert!(!s.seekable());
     }
 
     #[test]
@@ -186,7 +352,7 @@ mod test {
         let file: Box<dyn ReadAndSeek> =
             Box::new(File::open(temp.path()).expect("Could not open tempfile"));
         let s = ReadStream::from(file);
-        assert_eq!(s.seekable(), true);
+        assert!(s.seekable());
     }
 
     #[test]
@@ -195,7 +361,7 @@ mod test {
         let file: Box<dyn Write + Send> =
             Box::new(File::create(temp.path()).expect("Could not open tempfile"));
         let s = WriteStream::from(file);
-        assert_eq!(s.seekable(), false);
+        assert!(!s.seekable());
     }
 
     #[test]
@@ -204,6 +370,82 @@ mod test {
         let file: Box<dyn WriteAndSeek> =
             Box::new(File::create(temp.path()).expect("Could not open tempfile"));
         let s = WriteStream::from(file);
-        assert_eq!(s.seekable(), true);
+        assert!(s.seekable());
+    }
+
+    #[test]
+    fn should_wrap_streams_into_buffers() {
+        let temp = NamedTempFile::new().expect("Could not make tempfile");
+        let file: Box<dyn WriteAndSeek> =
+            Box::new(File::create(temp.path()).expect("Could not open tempfile"));
+        let mut s = WriteStream::from(file).buffered(8);
+        assert!(s.seekable());
+        s.write_all(b"Hello, world!").unwrap();
+        s.flush().unwrap();
+        assert_eq!(s.bytes_written(), 13);
+        drop(s);
+        let file: Box<dyn Read + Send> =
+            Box::new(File::open(temp.path()).expect("Could not open tempfile"));
+        let mut s = ReadStream::from(file).buffered(8);
+        assert!(!s.seekable());
+        let mut content = String::new();
+        s.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "Hello, world!");
+    }
+
+    #[test]
+    fn should_compute_checksum_on_the_fly() {
+        let temp = NamedTempFile::new().expect("Could not make tempfile");
+        let file: Box<dyn Write + Send> =
+            Box::new(File::create(temp.path()).expect("Could not open tempfile"));
+        let s = WriteStream::from(file);
+        assert!(s.checksum().is_none());
+        let mut s = s.with_checksum(ChecksumAlgorithm::Crc32);
+        s.write_all(b"123456789").unwrap();
+        assert_eq!(s.checksum().as_deref(), Some("cbf43926"));
+        drop(s);
+        let file: Box<dyn Read + Send> =
+            Box::new(File::open(temp.path()).expect("Could not open tempfile"));
+        let mut s = ReadStream::from(file).with_checksum(ChecksumAlgorithm::Crc32);
+        let mut content = Vec::new();
+        s.read_to_end(&mut content).unwrap();
+        assert_eq!(s.checksum().as_deref(), Some("cbf43926"));
+    }
+
+    #[test]
+    fn should_keep_tags_on_buffered_streams() {
+        let reader: Box<dyn ReadAndSeek> = Box::new(Cursor::new(b"hello".to_vec()));
+        let mut s = ReadStream::from(reader)
+            .tag(1usize)
+            .tag("a")
+            .tag(2usize)
+            .buffered(8);
+        assert!(s.seekable());
+        assert_eq!(s.take_tag::<usize>(), Some(2));
+        assert_eq!(s.take_tag::<&str>(), Some("a"));
+        assert_eq!(s.take_tag::<usize>(), Some(1));
+        assert_eq!(s.take_tag::<usize>(), None);
+        let writer: Box<dyn Write + Send> = Box::new(Vec::new());
+        let mut s = WriteStream::from(writer).tag(3u8).buffered(8);
+        assert_eq!(s.take_tag::<usize>(), None);
+        assert_eq!(s.take_tag::<u8>(), Some(3));
+    }
+
+    #[test]
+    fn should_be_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<ReadStream>();
+        assert_send::<WriteStream>();
+    }
+
+    #[test]
+    fn should_count_bytes_written_to_write_stream() {
+        let temp = NamedTempFile::new().expect("Could not make tempfile");
+        let file: Box<dyn Write + Send> =
+            Box::new(File::create(temp.path()).expect("Could not open tempfile"));
+        let mut s = WriteStream::from(file);
+        assert_eq!(s.bytes_written(), 0);
+        s.write_all(b"Hello, world!").unwrap();
+        assert_eq!(s.bytes_written(), 13);
     }
 }
@@ -1,13 +1,25 @@
+use std::collections::VecDeque;
 use std::io;
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(feature = "find")]
 use wildmatch::WildMatch;
 
+use super::errors::in_context;
+use super::stream::ReadAndSeek;
+#[cfg(feature = "find")]
+use super::IgnoreRules;
 use super::{
-    File, Metadata, ReadStream, RemoteError, RemoteErrorType, UnixPex, Welcome, WriteStream,
+    copy_stream, walk, AclPermission, BulkReport, Capabilities, ChecksumAlgorithm, ConnectOpts,
+    ConnectPhase, CopyOpts, ErrorPolicy, ExecOutput, File, FsEvent, GrepMatch, GrepOpts, Lines,
+    ListDirOpts, Metadata, MoveOpts, PathError, Permissions, PreserveMode, ReadStream, RemoteError,
+    RemoteErrorType, Semantics, Snapshot, SnapshotEntry, StreamOpts, TransferOpts, UnixPex,
+    WalkDirOpts, Welcome, WriteReport, WriteStream,
 };
+use crate::shell::{self, ShellEnv};
 use crate::RemoteResult;
 
 /// Defines the methods which must be implemented in order to setup a Remote file system
@@ -17,12 +29,188 @@ pub trait RemoteFs {
     /// If client has already established connection, then `AlreadyConnected` error is returned.
     fn connect(&mut self) -> RemoteResult<Welcome>;
 
+    /// Connect to the remote server as `connect` does, reporting the phases of the connection to `opts.on_phase`.
+    /// If `opts.cancel` is set, the connection is aborted with a `Cancelled` error.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method reports `Connecting`, calls `connect`, then reports `Connected`.
+    /// Cancellation is checked before and after `connect`; if cancelled once connected, the client is disconnected.
+    /// Implement this method to report the actual phases of the protocol and to abort a connection in progress
+    fn connect_with(&mut self, opts: &ConnectOpts) -> RemoteResult<Welcome> {
+        let cancelled = || RemoteError::new_ex(RemoteErrorType::Cancelled, "connection aborted");
+        if opts.is_cancelled() {
+            return Err(cancelled());
+        }
+        opts.report(ConnectPhase::Connecting);
+        let welcome = self.connect()?;
+        if opts.is_cancelled() {
+            debug!("Connection cancelled; disconnecting");
+            if let Err(err) = self.disconnect() {
+                warn!("Failed to disconnect: {}", err);
+            }
+            return Err(cancelled());
+        }
+        opts.report(ConnectPhase::Connected);
+        Ok(welcome)
+    }
+
     /// Disconnect from the remote server
     fn disconnect(&mut self) -> RemoteResult<()>;
 
     /// Gets whether the client is connected to remote
     fn is_connected(&mut self) -> bool;
 
+    /// Consume the client, disconnecting from the remote server if connected,
+    /// so that the session is released deterministically.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `disconnect` if `is_connected` returns `true`
+    fn close(mut self) -> RemoteResult<()>
+    where
+        Self: Sized,
+    {
+        match self.is_connected() {
+            true => {
+                debug!("Closing connection");
+                self.disconnect()
+            }
+            false => Ok(()),
+        }
+    }
+
+    /// Re-establish the connection to the remote server, restoring the working directory
+    /// which was set before reconnecting, so that relative paths keep pointing to the same files.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method saves the working directory if connected, then calls `disconnect`, `connect`
+    /// and finally `change_dir` to the saved directory
+    fn reconnect(&mut self) -> RemoteResult<Welcome> {
+        let wrkdir = match self.is_connected() {
+            true => {
+                let wrkdir = self.pwd().ok();
+                if let Err(err) = self.disconnect() {
+                    warn!("Failed to disconnect before reconnecting: {}", err);
+                }
+                wrkdir
+            }
+            false => None,
+        };
+        let welcome = self.connect()?;
+        if let Some(wrkdir) = wrkdir {
+            debug!("Restoring working directory {}", wrkdir.display());
+            self.change_dir(wrkdir.as_path())?;
+        }
+        Ok(welcome)
+    }
+
+    /// Get the features supported by the remote server
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method returns `Capabilities::default()`, which reports no feature as supported.
+    /// Implement this method to report what the server actually supports (e.g. from the FTP `FEAT` reply)
+    fn capabilities(&mut self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// Perform a cheap round trip to the remote server to check whether the connection is alive.
+    /// Returns the measured latency
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method times a `stat` of the working directory.
+    /// Implement this method when the protocol has a cheaper command (e.g. SSH keepalive, FTP `NOOP`, S3 `HEAD` bucket)
+    fn ping(&mut self) -> RemoteResult<Duration> {
+        if self.is_connected() {
+            let wrkdir = self.pwd()?;
+            let started = Instant::now();
+            self.stat(wrkdir.as_path())?;
+            let latency = started.elapsed();
+            trace!("Ping took {:?}", latency);
+            Ok(latency)
+        } else {
+            Err(RemoteError::new(RemoteErrorType::NotConnected))
+        }
+    }
+
+    /// Get the current time on the remote server, so that clock skew between client and server can be compensated
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method executes `date -u +%s` on the remote, so it returns `UnsupportedFeature` if `exec` is not supported.
+    /// Implement this method when the protocol provides the server time (e.g. HTTP `Date` header)
+    fn server_time(&mut self) -> RemoteResult<SystemTime> {
+        if self.is_connected() {
+            let (exit_code, output) = self.exec("date -u +%s")?;
+            if exit_code != 0 {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::ProtocolError,
+                    format!("date exited with code {exit_code}"),
+                ));
+            }
+            let secs: u64 = output.trim().parse().map_err(|_| {
+                RemoteError::new_ex(
+                    RemoteErrorType::ProtocolError,
+                    format!("invalid date output: {}", output.trim()),
+                )
+            })?;
+            Ok(UNIX_EPOCH + Duration::from_secs(secs))
+        } else {
+            Err(RemoteError::new(RemoteErrorType::NotConnected))
+        }
+    }
+
+    /// Get the home directory of the user on the remote server, which `~` stands for in paths (see `wrapper::TildeFs`)
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method executes `echo "$HOME"` on the remote, so it returns `UnsupportedFeature` if `exec` is not supported.
+    /// Implement this method when the protocol provides the home directory (e.g. SFTP `realpath(".")` right after connecting)
+    fn home_dir(&mut self) -> RemoteResult<PathBuf> {
+        if self.is_connected() {
+            let (exit_code, output) = self.exec("echo \"$HOME\"")?;
+            let home = PathBuf::from(output.trim_end_matches(['\r', '\n']));
+            if exit_code != 0 || !home.is_absolute() {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::ProtocolError,
+                    format!("could not get home directory: {}", output.trim()),
+                ));
+            }
+            Ok(home)
+        } else {
+            Err(RemoteError::new(RemoteErrorType::NotConnected))
+        }
+    }
+
+    /// Get a summary of the remote the client connects to (e.g. `sftp://user@host:22`), to be displayed in UI titles and logs.
+    /// The summary must never include secrets: implementors can build it with `url::redact_url`
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method returns `None`
+    fn summary(&self) -> Option<String> {
+        None
+    }
+
+    /// Get the rules the remote file system applies to paths
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method returns `Semantics::posix()`.
+    /// Implement this method if the remote file system has different rules
+    fn semantics(&self) -> Semantics {
+        Semantics::posix()
+    }
+
+    /// Check whether `path` is valid on the remote file system, according to `semantics`,
+    /// so that names can be sanitized before attempting an operation
+    fn validate_path(&self, path: &Path) -> Result<(), PathError> {
+        self.semantics().validate(path)
+    }
+
     /// Get working directory
     fn pwd(&mut self) -> RemoteResult<PathBuf>;
 
@@ -30,16 +218,267 @@ pub trait RemoteFs {
     /// Returns the realpath of new directory
     fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf>;
 
-    /// List directory entries at specified `path`
+    /// List directory entries at specified `path`.
+    /// The special entries `.` and `..` must not be returned
     fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>>;
 
+    /// List directory entries at specified `path`, sorting and filtering them as specified in `opts`
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `list_dir` and applies options client-side.
+    /// Implement this method when the protocol can filter entries server-side
+    fn list_dir_opts(&mut self, path: &Path, opts: &ListDirOpts) -> RemoteResult<Vec<File>> {
+        in_context("list_dir_opts", path, || {
+            self.list_dir(path).map(|entries| opts.apply(entries))
+        })
+    }
+
+    /// List directory entries at specified `path`, calling `on_entry` for each entry as soon as it's available,
+    /// so that large directories can be rendered incrementally.
+    /// Listing stops as soon as `on_entry` returns `ControlFlow::Break`
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `list_dir` and then `on_entry` for each entry.
+    /// Implement this method when the protocol returns entries in pages (e.g. S3 pagination or SFTP `readdir`)
+    fn list_dir_cb(
+        &mut self,
+        path: &Path,
+        on_entry: &mut dyn FnMut(File) -> ControlFlow<()>,
+    ) -> RemoteResult<()> {
+        in_context("list_dir_cb", path, || {
+            for entry in self.list_dir(path)? {
+                if on_entry(entry).is_break() {
+                    trace!("Listing of {} stopped by the caller", path.display());
+                    break;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Get a token which changes whenever the content of the directory at `path` changes,
+    /// so that callers can poll it cheaply and list the directory again only when it changes.
+    /// Returns `None` if the remote can't provide such a token.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `stat` on the directory and returns its ETag if set, otherwise its modification time.
+    /// Note that on most file systems the modification time of a directory changes only when entries are added, removed or renamed.
+    /// Implement this method when the protocol provides a better token (e.g. WebDAV `getctag`, or a hash of the ETags of a S3 listing)
+    fn dir_version(&mut self, path: &Path) -> RemoteResult<Option<String>> {
+        in_context("dir_version", path, || {
+            let dir = self.stat(path)?;
+            if !dir.is_dir() {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::BadFile,
+                    "not a directory",
+                ));
+            }
+            let modified = dir
+                .metadata
+                .modified
+                .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+                .map(|x| format!("{}.{:09}", x.as_secs(), x.subsec_nanos()));
+            Ok(dir.metadata.etag.or(modified))
+        })
+    }
+
     /// Stat file at specified `path` and return Entry
     fn stat(&mut self, path: &Path) -> RemoteResult<File>;
 
     /// Set metadata for file at specified `path`
     fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()>;
 
+    /// Set permissions for file at specified `path`
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method sets unix permissions with `stat` and `setstat`, returns `UnsupportedFeature` for acl
+    /// and does nothing for `Permissions::None`.
+    /// Implement this method when the remote supports acl
+    fn set_permissions(&mut self, path: &Path, permissions: Permissions) -> RemoteResult<()> {
+        in_context("set_permissions", path, || {
+            if self.is_connected() {
+                match permissions {
+                    Permissions::Unix(mode) => {
+                        let metadata = self.stat(path)?.metadata.mode(mode);
+                        self.setstat(path, metadata)
+                    }
+                    Permissions::Acl(_) => {
+                        Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+                    }
+                    Permissions::None => Ok(()),
+                }
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
+    }
+
+    /// Set owner (`uid`) and/or group (`gid`) for file at specified `path` and, if it is a directory, for all its content.
+    /// If `uid` or `gid` is `None`, it is left unchanged.
+    ///
+    /// Symbolic links in the tree are not followed and left unchanged.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `setstat` for each entry in the tree.
+    /// Implement this method when there is a faster way to achieve this (e.g. `chown -R`)
+    fn set_owner_recursive(
+        &mut self,
+        path: &Path,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> RemoteResult<()> {
+        in_context("set_owner_recursive", path, || {
+            if self.is_connected() {
+                let root = self.stat(path)?;
+                debug!(
+                    "Setting owner {:?}:{:?} recursively on {}",
+                    uid,
+                    gid,
+                    root.path().display()
+                );
+                let mut entries = vec![];
+                if root.is_dir() {
+                    entries = self.walk_dir(root.path(), &WalkDirOpts::default())?;
+                }
+                entries.insert(0, root);
+                for entry in entries.into_iter().filter(|x| !x.is_symlink()) {
+                    let mut metadata = entry.metadata;
+                    metadata.uid = uid.or(metadata.uid);
+                    metadata.gid = gid.or(metadata.gid);
+                    self.setstat(entry.path.as_path(), metadata)?;
+                }
+                Ok(())
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
+    }
+
+    /// Set unix permissions for file at specified `path` and, if it is a directory, for all its content.
+    ///
+    /// Symbolic links in the tree are not followed and left unchanged.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `setstat` for each entry in the tree.
+    /// Implement this method when there is a faster way to achieve this (e.g. `chmod -R`)
+    fn set_mode_recursive(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        in_context("set_mode_recursive", path, || {
+            if self.is_connected() {
+                let root = self.stat(path)?;
+                debug!(
+                    "Setting mode {:o} recursively on {}",
+                    u32::from(mode),
+                    root.path().display()
+                );
+                let mut entries = vec![];
+                if root.is_dir() {
+                    entries = self.walk_dir(root.path(), &WalkDirOpts::default())?;
+                }
+                entries.insert(0, root);
+                for entry in entries.into_iter().filter(|x| !x.is_symlink()) {
+                    self.setstat(entry.path.as_path(), entry.metadata.mode(mode))?;
+                }
+                Ok(())
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
+    }
+
+    /// Check whether the file at `path` can be read, without side effects, so that UIs can disable actions up front.
+    /// Returns `false` if the file doesn't exist
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method runs `test -r` with `exec`, if the server supports it according to `capabilities`.
+    /// Otherwise, or if the command fails, the permissions returned by `stat` are checked with a heuristic,
+    /// since the identity of the user on the remote is unknown: the file is considered readable
+    /// if any unix class or ACL entry grants read access, or if the remote doesn't report permissions.
+    /// Implement this method when the protocol can check the effective permissions (e.g. SFTP open with access flags)
+    fn can_read(&mut self, path: &Path) -> RemoteResult<bool> {
+        in_context("can_read", path, || {
+            if let Some(result) = test_access(self, "-r", path) {
+                return Ok(result);
+            }
+            match self.stat(path) {
+                Ok(file) => Ok(permits(&file.metadata.permissions(), false)),
+                Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    /// Check whether the file at `path` can be written, or created if it doesn't exist, without side effects,
+    /// so that UIs can disable actions up front
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method runs `test -w` with `exec` on the file, or on its parent if it doesn't exist,
+    /// if the server supports it according to `capabilities`.
+    /// Otherwise, or if the command fails, the permissions returned by `stat` are checked with the same heuristic as `can_read`.
+    /// Implement this method when the protocol can check the effective permissions
+    fn can_write(&mut self, path: &Path) -> RemoteResult<bool> {
+        in_context("can_write", path, || {
+            let target = match self.exists(path)? {
+                true => path.to_path_buf(),
+                false => match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                    _ => self.pwd()?,
+                },
+            };
+            if let Some(result) = test_access(self, "-w", target.as_path()) {
+                return Ok(result);
+            }
+            match self.stat(target.as_path()) {
+                Ok(file) => Ok(permits(&file.metadata.permissions(), true)),
+                Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    /// Set access and modification times for file at specified `path`.
+    /// If the file doesn't exist, an empty file is created.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method creates the file with `create_file` if it doesn't exist, then sets times with `setstat`.
+    /// Implement this method when the protocol has a specific command for this
+    fn touch(
+        &mut self,
+        path: &Path,
+        accessed: SystemTime,
+        modified: SystemTime,
+    ) -> RemoteResult<()> {
+        in_context("touch", path, || {
+            if self.is_connected() {
+                if !self.exists(path)? {
+                    debug!("{} doesn't exist; creating an empty file", path.display());
+                    self.create_file(path, &Metadata::default(), Box::new(io::empty()))?;
+                }
+                let metadata = self
+                    .stat(path)?
+                    .metadata
+                    .accessed(accessed)
+                    .modified(modified);
+                self.setstat(path, metadata)
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
+    }
+
     /// Returns whether file at specified `path` exists.
+    ///
+    /// `Ok(false)` must be returned only when the remote reported that the file doesn't exist.
+    /// If the existence of the file can't be determined (e.g. because of a network, authentication or permission error),
+    /// the error must be returned instead, since callers use the result to decide whether to create, overwrite or remove files.
     fn exists(&mut self, path: &Path) -> RemoteResult<bool>;
 
     /// Remove file at specified `path`.
@@ -58,72 +497,544 @@ pub trait RemoteFs {
     ///
     /// ### Default implementation
     ///
-    /// By default this method will combine `remove_file` and `remove_file` to remove all the content.
+    /// By default this method will combine `remove_file` and `remove_dir` to remove all the content.
     /// Implement this method when there is a faster way to achieve this
     fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
-        if self.is_connected() {
-            let path = crate::utils::path::absolutize(&self.pwd()?, path);
-            debug!("Removing {}...", path.display());
-            let entry = self.stat(path.as_path())?;
-            if entry.is_dir() {
-                // list dir
-                debug!(
-                    "{} is a directory; removing all directory entries",
-                    entry.name()
-                );
-                let directory_content = self.list_dir(entry.path())?;
-                for entry in directory_content.iter() {
-                    self.remove_dir_all(entry.path())?;
+        self.remove_dir_all_with(path, ErrorPolicy::Abort, &mut |_| {})
+            .map(|_| ())
+            .map_err(|err| match err.op() {
+                // failures of the removal itself are reported as failures of `remove_dir_all`
+                Some("remove_dir_all_with") => err.with_op("remove_dir_all"),
+                _ => err.or_context("remove_dir_all", path),
+            })
+    }
+
+    /// Removes a directory at this path, after removing all its contents, as `remove_dir_all` does,
+    /// calling `on_event` each time a directory is entered or an entry is removed.
+    ///
+    /// With `ErrorPolicy::Continue`, failures don't stop the removal: they're collected into the returned `BulkReport`.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method will combine `remove_file` and `remove_dir` to remove all the content.
+    fn remove_dir_all_with(
+        &mut self,
+        path: &Path,
+        policy: ErrorPolicy,
+        on_event: &mut dyn FnMut(FsEvent),
+    ) -> RemoteResult<BulkReport> {
+        in_context("remove_dir_all_with", path, || {
+            if self.is_connected() {
+                let path = crate::utils::path::absolutize(&self.pwd()?, path);
+                debug!("Removing {}...", path.display());
+                let entry = self.stat(path.as_path())?;
+                let mut report = BulkReport::default();
+                if entry.is_dir() {
+                    debug!(
+                        "{} is a directory; removing all directory entries",
+                        entry.name()
+                    );
+                    walk::remove_tree(self, entry.path(), policy, &mut report, on_event)?;
+                } else {
+                    self.remove_file(entry.path())?;
+                    on_event(FsEvent::Removed(entry.path));
+                    report.succeeded += 1;
                 }
-                trace!(
-                    "Removed all files in {}; removing directory",
-                    entry.path().display()
-                );
-                self.remove_dir(entry.path())
+                Ok(report)
             } else {
-                self.remove_file(entry.path())
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
             }
-        } else {
-            Err(RemoteError::new(RemoteErrorType::NotConnected))
-        }
+        })
+    }
+
+    /// Walk directory at `path` recursively, returning all the entries found in the tree.
+    /// Each directory is followed by its content.
+    ///
+    /// Symbolic links are followed only if `follow_symlinks` is set in `opts`;
+    /// in that case, if a symlink points to a directory being walked, `SymlinkLoop` is returned.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `list_dir` for each directory in the tree.
+    /// Implement this method when there is a faster way to achieve this
+    fn walk_dir(&mut self, path: &Path, opts: &WalkDirOpts) -> RemoteResult<Vec<File>> {
+        in_context("walk_dir", path, || {
+            if self.is_connected() {
+                let path = crate::utils::path::absolutize(&self.pwd()?, path);
+                debug!("Walking {}...", path.display());
+                walk::walk(self, path.as_path(), opts)
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
+    }
+
+    /// Compute the checksum of the file at `path` with `algorithm`, returned as a lowercase hex string
+    ///
+    /// ### Default implementation
+    ///
+    /// By default, for SHA-256, this method runs `sha256sum` with `exec` on the file read from the standard input,
+    /// so that the output doesn't depend on the file name, if the server supports it according to `capabilities`;
+    /// otherwise, or if the command fails, the file is read with `open`.
+    /// Implement this method when the protocol can compute checksums on the server side
+    fn checksum(&mut self, path: &Path, algorithm: ChecksumAlgorithm) -> RemoteResult<String> {
+        in_context("checksum", path, || {
+            if algorithm == ChecksumAlgorithm::Sha256 && self.capabilities().exec {
+                let cmd = format!("sha256sum < {}", shell::escape_path(path));
+                match self.exec_bytes(cmd.as_str()) {
+                    Ok(output) if output.success() => {
+                        if let Some(digest) = output.stdout_lossy().split_whitespace().next() {
+                            return Ok(digest.to_ascii_lowercase());
+                        }
+                    }
+                    Ok(output) => debug!(
+                        "sha256sum exited with code {}; reading the file",
+                        output.exit_code
+                    ),
+                    Err(err) => debug!("Could not run sha256sum ({}); reading the file", err),
+                }
+            }
+            let mut stream = self.open(path)?;
+            let digest = algorithm
+                .hash_reader(&mut stream)
+                .map_err(|e| RemoteError::from_io(RemoteErrorType::IoError, e))?;
+            self.on_read(stream)?;
+            Ok(digest)
+        })
+    }
+
+    /// Take a snapshot of the tree at `path`, walked as specified in `opts`, which can be compared with another one with `Snapshot::diff`.
+    /// If `checksum` is set, the checksum of each file is computed too.
+    /// A relative `path` is resolved from the working directory, which is the root of the returned snapshot
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method walks the tree with `walk_dir`; checksums are computed with `checksum`
+    fn snapshot(
+        &mut self,
+        path: &Path,
+        opts: &WalkDirOpts,
+        checksum: Option<ChecksumAlgorithm>,
+    ) -> RemoteResult<Snapshot> {
+        in_context("snapshot", path, || {
+            let root = crate::utils::path::normalize(
+                crate::utils::path::absolutize(&self.pwd()?, path).as_path(),
+            );
+            let mut snapshot = Snapshot::new(root.as_path());
+            for file in self.walk_dir(root.as_path(), opts)? {
+                let mut entry = SnapshotEntry::from_file(&file);
+                if let (true, Some(alg)) = (file.is_file(), checksum) {
+                    entry = entry.checksum(self.checksum(file.path(), alg)?);
+                }
+                let relative = file
+                    .path()
+                    .strip_prefix(root.as_path())
+                    .unwrap_or(file.path());
+                snapshot.insert(relative, entry);
+            }
+            Ok(snapshot)
+        })
     }
 
     /// Create a directory at `path` with specified mode.
     fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()>;
 
+    /// Create a directory at `path` with specified mode as `create_dir` does, returning the created directory with its effective metadata,
+    /// since the server may apply a umask to the requested mode.
+    /// If `strict_mode` is set and the effective mode differs from `mode`, the mode is set again with `setstat`
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `create_dir`, then `stat`, and `setstat` if required
+    fn create_dir_ext(
+        &mut self,
+        path: &Path,
+        mode: UnixPex,
+        strict_mode: bool,
+    ) -> RemoteResult<File> {
+        in_context("create_dir_ext", path, || {
+            self.create_dir(path, mode)?;
+            effective_metadata(self, path, Some(mode), strict_mode)
+        })
+    }
+
     /// Create a symlink at `path` pointing at `target`
     fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()>;
 
     /// Copy `src` to `dest`
     fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()>;
 
+    /// Copy `src` to `dest` as specified in `opts`, recursively if `src` is a directory.
+    /// Symbolic links are copied as links, unless `dereference` is set; the metadata of `src` is preserved according to `preserve`.
+    /// If the remote doesn't support changing metadata, a warning is logged and the metadata is not preserved
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method copies `src` through `open` and `create`, then sets the preserved metadata with `setstat`.
+    /// Implement this method when the protocol can copy on the server side (e.g. `cp -RPp` on SSH)
+    fn copy_with(&mut self, src: &Path, dest: &Path, opts: &CopyOpts) -> RemoteResult<()> {
+        in_context("copy_with", src, || {
+            if self.is_connected() {
+                let bytes = walk::copy_tree(self, src, dest, opts, &mut |_| {})?;
+                trace!(
+                    "Copied {} bytes from {} to {}",
+                    bytes,
+                    src.display(),
+                    dest.display()
+                );
+                Ok(())
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
+    }
+
     /// move file/directory from `src` to `dest`
     fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()>;
 
+    /// Move file/directory from `src` to `dest` as specified in `opts`.
+    /// If `copy_fallback` is set and the remote can't move `src` to `dest`, because renaming is not supported
+    /// (`UnsupportedFeature`, e.g. on object stores) or because they're on different file systems (`CrossDevice`),
+    /// `src` is copied to `dest` and then removed; any other error is returned as is.
+    ///
+    /// If the copy succeeds but `src` can't be removed, the error is returned and both `src` and `dest` are left in place.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method tries `mov`; on fallback it copies `src` through `open` and `create`, recursively for directories,
+    /// preserving its metadata, then removes it with `remove_dir_all` or `remove_file`
+    fn move_with(&mut self, src: &Path, dest: &Path, opts: &MoveOpts) -> RemoteResult<()> {
+        in_context("move_with", src, || {
+            if !self.is_connected() {
+                return Err(RemoteError::new(RemoteErrorType::NotConnected));
+            }
+            match self.mov(src, dest) {
+                Ok(()) => return Ok(()),
+                Err(err)
+                    if !opts.copy_fallback
+                        || !matches!(
+                            err.kind,
+                            RemoteErrorType::UnsupportedFeature | RemoteErrorType::CrossDevice
+                        ) =>
+                {
+                    return Err(err)
+                }
+                Err(err) => debug!(
+                    "Could not move {} to {} ({}); falling back to copy",
+                    src.display(),
+                    dest.display(),
+                    err
+                ),
+            }
+            let is_dir = self.stat(src)?.is_dir();
+            let opts = CopyOpts::default().preserve(PreserveMode::All);
+            let bytes = walk::copy_tree(self, src, dest, &opts, &mut |_| {})?;
+            trace!("Copied {} bytes; removing {}", bytes, src.display());
+            if is_dir {
+                self.remove_dir_all(src)
+            } else {
+                self.remove_file(src)
+            }
+        })
+    }
+
     /// Execute a command on remote host if supported by host.
     /// Returns command exit code and output (stdout)
     fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)>;
 
+    /// Execute a command on remote host, if supported by host, with the environment described by `env`.
+    /// Returns command exit code and output (stdout)
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `exec` with the command wrapped by `env`
+    fn exec_with_env(&mut self, cmd: &str, env: &ShellEnv) -> RemoteResult<(u32, String)> {
+        self.exec(env.wrap(cmd).as_str())
+    }
+
+    /// Execute a command on remote host if supported by host.
+    /// Returns command exit code and raw output, which is not required to be valid UTF-8.
+    /// `exec` should be considered as a lossy convenience of this method
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `exec`, so stdout is already lossy and stderr is empty.
+    /// Implement this method when the protocol gives access to the raw output
+    fn exec_bytes(&mut self, cmd: &str) -> RemoteResult<ExecOutput> {
+        let (exit_code, stdout) = self.exec(cmd)?;
+        Ok(ExecOutput::new(exit_code, stdout.into_bytes(), vec![]))
+    }
+
     /// Open file at `path` for appending data.
     /// If the file doesn't exist, the file is created.
     ///
-    /// ### ⚠️ Warning
+    /// ### ⚠️ Warning
+    ///
+    /// metadata should be the same of the local file.
+    /// In some protocols, such as `scp` the `size` field is used to define the transfer size (required by the protocol)
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream>;
+
+    /// Create file at path for write.
+    /// If the file already exists, its content will be overwritten
+    ///
+    /// ### ⚠️ Warning
+    ///
+    /// metadata should be the same of the local file.
+    /// In some protocols, such as `scp` the `size` field is used to define the transfer size (required by the protocol)
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream>;
+
+    /// Open file at specified path for read.
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream>;
+
+    /// Open file at `path` for appending data as `append` does, with the provided stream options.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `append` and applies options with `StreamOpts::apply_write`.
+    /// Implement this method when the backend already buffers the stream, in order to avoid double buffering
+    /// and to honor a zero `buffer_size`, asking for an unbuffered stream
+    fn append_with(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        opts: &StreamOpts,
+    ) -> RemoteResult<WriteStream> {
+        in_context("append_with", path, || {
+            self.append(path, metadata).map(|x| opts.apply_write(x))
+        })
+    }
+
+    /// Create file at `path` for write as `create` does, with the provided stream options.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `create` and applies options with `StreamOpts::apply_write`.
+    /// Implement this method when the backend already buffers the stream, in order to avoid double buffering
+    /// and to honor a zero `buffer_size`, asking for an unbuffered stream
+    fn create_with(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        opts: &StreamOpts,
+    ) -> RemoteResult<WriteStream> {
+        in_context("create_with", path, || {
+            self.create(path, metadata).map(|x| opts.apply_write(x))
+        })
+    }
+
+    /// Open file at `path` for read as `open` does, with the provided stream options.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `open` and applies options with `StreamOpts::apply_read`.
+    /// Implement this method when the backend already buffers the stream, in order to avoid double buffering
+    /// and to honor a zero `buffer_size`, asking for an unbuffered stream
+    fn open_with(&mut self, path: &Path, opts: &StreamOpts) -> RemoteResult<ReadStream> {
+        in_context("open_with", path, || {
+            self.open(path).map(|x| opts.apply_read(x))
+        })
+    }
+
+    /// Open the text file at `path` and iterate over its lines, without downloading the whole file.
+    /// Lines are decoded lossily from UTF-8 and returned without the line terminator
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method reads the file through a `Lines` iterator over `open`, which is finalized at the end of the file
+    fn read_lines(
+        &mut self,
+        path: &Path,
+    ) -> RemoteResult<Box<dyn Iterator<Item = RemoteResult<String>> + '_>> {
+        let stream = self
+            .open(path)
+            .map_err(|err| err.or_context("read_lines", path))?;
+        Ok(Box::new(Lines::new(self, stream)))
+    }
+
+    /// Get the last `n` lines of the text file at `path`
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method runs `tail -n` with `exec`, if the server supports it according to `capabilities`;
+    /// otherwise, or if the command fails, the whole file is read with `read_lines`
+    fn tail(&mut self, path: &Path, n: usize) -> RemoteResult<Vec<String>> {
+        in_context("tail", path, || {
+            if self.capabilities().exec {
+                let cmd = format!("tail -n {} {}", n, shell::escape_path(path));
+                match self.exec_bytes(cmd.as_str()) {
+                    Ok(output) if output.success() => {
+                        return Ok(output.stdout_lossy().lines().map(String::from).collect());
+                    }
+                    Ok(output) => debug!(
+                        "tail exited with code {}; reading the whole file",
+                        output.exit_code
+                    ),
+                    Err(err) => debug!("Could not run tail ({}); reading the whole file", err),
+                }
+            }
+            let mut lines = VecDeque::with_capacity(n);
+            for line in self.read_lines(path)? {
+                if lines.len() == n {
+                    lines.pop_front();
+                }
+                if n > 0 {
+                    lines.push_back(line?);
+                }
+            }
+            Ok(lines.into())
+        })
+    }
+
+    /// Search `pattern` in the lines of the file at `path`, or of all the files in the tree at `path` if it's a directory.
+    /// The pattern is matched as a fixed string. Binary files may be skipped
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method runs `grep -rnHIF` with `exec`, if the server supports it according to `capabilities`;
+    /// otherwise, or if the command fails, each file is read with `read_lines`
+    fn grep(
+        &mut self,
+        path: &Path,
+        pattern: &str,
+        opts: &GrepOpts,
+    ) -> RemoteResult<Vec<GrepMatch>> {
+        in_context("grep", path, || {
+            if self.capabilities().exec {
+                let cmd = format!(
+                    "grep -rnHIF{} --null -e {} {}",
+                    if opts.ignore_case { "i" } else { "" },
+                    shell::escape(pattern),
+                    shell::escape_path(path)
+                );
+                match self.exec_bytes(cmd.as_str()) {
+                    Ok(output) if output.exit_code <= 1 => {
+                        let matches = output
+                            .stdout
+                            .split(|x| *x == b'\n')
+                            .filter_map(|line| {
+                                let sep = line.iter().position(|x| *x == 0)?;
+                                let path = String::from_utf8_lossy(&line[..sep]).into_owned();
+                                let rest = String::from_utf8_lossy(&line[sep + 1..]);
+                                let (line_number, line) = rest.split_once(':')?;
+                                Some(GrepMatch::new(path, line_number.parse().ok()?, line))
+                            })
+                            .take(opts.max_matches.unwrap_or(usize::MAX))
+                            .collect();
+                        return Ok(matches);
+                    }
+                    Ok(output) => debug!(
+                        "grep exited with code {}; searching files one by one",
+                        output.exit_code
+                    ),
+                    Err(err) => debug!("Could not run grep ({}); searching files one by one", err),
+                }
+            }
+            let file = self.stat(path)?;
+            let files = match file.is_dir() {
+                true => self
+                    .walk_dir(path, &WalkDirOpts::default())?
+                    .into_iter()
+                    .filter(|x| x.is_file())
+                    .map(|x| x.path)
+                    .collect(),
+                false => vec![path.to_path_buf()],
+            };
+            let max = opts.max_matches.unwrap_or(usize::MAX);
+            let mut matches = Vec::new();
+            for file in files {
+                if matches.len() >= max {
+                    break;
+                }
+                for (i, line) in self.read_lines(file.as_path())?.enumerate() {
+                    if matches.len() >= max {
+                        break;
+                    }
+                    let line = line?;
+                    if opts.matches(line.as_str(), pattern) {
+                        matches.push(GrepMatch::new(file.as_path(), i + 1, line));
+                    }
+                }
+            }
+            Ok(matches)
+        })
+    }
+    /// Pack the tree at `dir` into a new zip archive at `archive`, replacing it if it exists
+    ///
+    /// ### Default implementation
     ///
-    /// metadata should be the same of the local file.
-    /// In some protocols, such as `scp` the `size` field is used to define the transfer size (required by the protocol)
-    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream>;
+    /// By default this method runs `zip -r` in `dir` with `exec`, if the server supports it according to `capabilities`;
+    /// otherwise, or if the command fails, the archive is written through the client streams with the `zip` feature,
+    /// deflating files, while `UnsupportedFeature` is returned without it
+    fn zip_dir(&mut self, dir: &Path, archive: &Path) -> RemoteResult<()> {
+        in_context("zip_dir", dir, || {
+            if self.capabilities().exec {
+                let archive = crate::utils::path::absolutize(&self.pwd()?, archive);
+                let cmd = format!(
+                    "rm -f {archive} && cd {} && zip -qry {archive} .",
+                    shell::escape_path(dir),
+                    archive = shell::escape_path(archive.as_path())
+                );
+                match self.exec_bytes(cmd.as_str()) {
+                    Ok(output) if output.success() => return Ok(()),
+                    Ok(output) => debug!(
+                        "zip exited with code {}; writing the archive through the client",
+                        output.exit_code
+                    ),
+                    Err(err) => debug!(
+                        "Could not run zip ({}); writing the archive through the client",
+                        err
+                    ),
+                }
+            }
+            #[cfg(feature = "zip")]
+            return super::zip::zip_tree(self, dir, archive);
+            #[cfg(not(feature = "zip"))]
+            Err(RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "zip is not available and the zip feature is disabled",
+            ))
+        })
+    }
 
-    /// Create file at path for write.
-    /// If the file already exists, its content will be overwritten
+    /// Extract the zip archive at `archive` into `dest`, which is created if it doesn't exist.
+    /// Existing files are overwritten
     ///
-    /// ### ⚠️ Warning
+    /// ### Default implementation
     ///
-    /// metadata should be the same of the local file.
-    /// In some protocols, such as `scp` the `size` field is used to define the transfer size (required by the protocol)
-    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream>;
-
-    /// Open file at specified path for read.
-    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream>;
+    /// By default this method runs `unzip -o` with `exec`, if the server supports it according to `capabilities`;
+    /// otherwise, or if the command fails, the archive is extracted through the client streams with the `zip` feature,
+    /// while `UnsupportedFeature` is returned without it.
+    /// If the archive stream isn't seekable, entries are read in order and those written with a data descriptor
+    /// (i.e. whose size follows their data) fail with `UnsupportedFeature`
+    fn unzip(&mut self, archive: &Path, dest: &Path) -> RemoteResult<()> {
+        in_context("unzip", archive, || {
+            if self.capabilities().exec {
+                let cmd = format!(
+                    "unzip -qo {} -d {}",
+                    shell::escape_path(archive),
+                    shell::escape_path(dest)
+                );
+                match self.exec_bytes(cmd.as_str()) {
+                    Ok(output) if output.success() => return Ok(()),
+                    Ok(output) => debug!(
+                        "unzip exited with code {}; extracting the archive through the client",
+                        output.exit_code
+                    ),
+                    Err(err) => debug!(
+                        "Could not run unzip ({}); extracting the archive through the client",
+                        err
+                    ),
+                }
+            }
+            #[cfg(feature = "zip")]
+            return super::zip::unzip_archive(self, archive, dest);
+            #[cfg(not(feature = "zip"))]
+            Err(RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "unzip is not available and the zip feature is disabled",
+            ))
+        })
+    }
 
     /// Finalize `create_file` and `append_file` methods.
     /// This method must be implemented only if necessary; in case you don't need it, just return `Ok(())`
@@ -138,6 +1049,25 @@ pub trait RemoteFs {
         Ok(())
     }
 
+    /// Finalize `create` and `append` methods as `on_written` does, but returns a `WriteReport`
+    /// describing the written file.
+    /// Implement this method if the protocol returns data about the written file on finalize
+    /// (e.g. the ETag on S3), so that the caller doesn't have to `stat` the file again.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this function calls `on_written` and returns a report containing only the amount of bytes written
+    /// and the checksum, if computed by the stream
+    fn on_written_ext(&mut self, writable: WriteStream) -> RemoteResult<WriteReport> {
+        let bytes = writable.bytes_written();
+        let checksum = writable.checksum();
+        self.on_written(writable)?;
+        Ok(WriteReport {
+            checksum,
+            ..WriteReport::default().bytes(bytes)
+        })
+    }
+
     /// Finalize `open_file` method.
     /// This method must be implemented only if necessary; in case you don't need it, just return `Ok(())`
     /// The purpose of this method is to finalize the connection with the peer when reading data.
@@ -159,24 +1089,169 @@ pub trait RemoteFs {
     ///
     /// ### Default implementation
     ///
-    /// By default this function uses the streams function to copy content from reader to writer
+    /// By default this function uses the streams function to copy content from reader to writer with `copy_stream`.
+    /// If `on_written` fails after the content has been copied, a `FinalizeFailed` error is returned,
+    /// from which the amount of bytes written can be retrieved with `RemoteError::bytes_written`
     fn append_file(
         &mut self,
         path: &Path,
         metadata: &Metadata,
         mut reader: Box<dyn Read + Send>,
     ) -> RemoteResult<u64> {
-        if self.is_connected() {
-            trace!("Opened remote file");
-            let mut stream = self.append(path, metadata)?;
-            let sz = io::copy(&mut reader, &mut stream)
-                .map_err(|e| RemoteError::new_ex(RemoteErrorType::ProtocolError, e.to_string()))?;
-            self.on_written(stream)?;
-            trace!("Written {} bytes to destination", sz);
-            Ok(sz)
-        } else {
-            Err(RemoteError::new(RemoteErrorType::NotConnected))
-        }
+        in_context("append_file", path, || {
+            if self.is_connected() {
+                trace!("Opened remote file");
+                let mut stream = self.append(path, metadata)?;
+                let sz = copy_stream(&mut reader, &mut stream, &TransferOpts::default())?;
+                self.on_written(stream)
+                    .map_err(|e| RemoteError::finalize_failed(sz, e))?;
+                trace!("Written {} bytes to destination", sz);
+                Ok(sz)
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
+    }
+
+    /// Resume the upload of `reader` to `path`, continuing from the size of the remote file.
+    /// If `verify` is set, the content already present on the remote is checked against the beginning of `reader`
+    /// with the given checksum algorithm; if it doesn't match, or if the remote file is bigger than `reader`,
+    /// the upload is started over.
+    /// Returns the amount of bytes written to the remote file by this call
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `stat` to get the remote size, then `append_file` from the offset.
+    /// Verification downloads the remote prefix with `open`.
+    /// Implement this method when the protocol can compute checksums server-side
+    fn resume_upload(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        mut reader: Box<dyn ReadAndSeek>,
+        verify: Option<ChecksumAlgorithm>,
+    ) -> RemoteResult<u64> {
+        in_context("resume_upload", path, || {
+            if !self.is_connected() {
+                return Err(RemoteError::new(RemoteErrorType::NotConnected));
+            }
+            let io_err = |e: io::Error| RemoteError::from_io(RemoteErrorType::IoError, e);
+            let offset = match self.stat(path) {
+                Ok(file) => file.metadata().size,
+                Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => 0,
+                Err(err) => return Err(err),
+            };
+            let len = reader.seek(SeekFrom::End(0)).map_err(io_err)?;
+            let mut resumable = offset <= len;
+            if let (true, Some(alg)) = (resumable && offset > 0, verify) {
+                reader.seek(SeekFrom::Start(0)).map_err(io_err)?;
+                let local = alg
+                    .hash_reader(&mut (&mut reader).take(offset))
+                    .map_err(io_err)?;
+                let mut stream = self.open(path)?;
+                let remote = alg
+                    .hash_reader(&mut (&mut stream).take(offset))
+                    .map_err(io_err)?;
+                self.on_read(stream)?;
+                resumable = local == remote;
+            }
+            if resumable {
+                debug!("Resuming upload of {} from {}", path.display(), offset);
+                reader.seek(SeekFrom::Start(offset)).map_err(io_err)?;
+                self.append_file(path, metadata, Box::new(reader))
+            } else {
+                warn!(
+                    "Remote content of {} doesn't match the local one; starting over",
+                    path.display()
+                );
+                reader.seek(SeekFrom::Start(0)).map_err(io_err)?;
+                self.create_file(path, metadata, Box::new(reader))
+            }
+        })
+    }
+
+    /// Read the file at `path`, replace its content with the output of `f` and write it back.
+    /// The new content is written to a temporary file next to `path`, which is then moved over it,
+    /// so that readers never see a partially written file.
+    /// Fails with `Conflict` if the file was changed by someone else in the meantime; in that case the file is left untouched.
+    ///
+    /// ### Default implementation
+    ///
+    /// By default the file is read with `open`, written with `create_file` and moved with `mov`.
+    /// Right before moving, the file is checked for changes with `stat`, comparing ETag, version, modification time and size;
+    /// if the remote reports neither an ETag nor a modification time, the content is read again and compared too.
+    /// Since checking and moving are two operations, a change in between can't be detected:
+    /// implement this method where the protocol supports conditional writes (e.g. `If-Match` on S3 and WebDAV)
+    fn edit<F>(&mut self, path: &Path, f: F) -> RemoteResult<()>
+    where
+        F: FnOnce(Vec<u8>) -> Vec<u8>,
+        Self: Sized,
+    {
+        in_context("edit", path, || {
+            if !self.is_connected() {
+                return Err(RemoteError::new(RemoteErrorType::NotConnected));
+            }
+            let io_err = |e: io::Error| RemoteError::from_io(RemoteErrorType::IoError, e);
+            let before = self.stat(path)?;
+            if !before.is_file() {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::BadFile,
+                    "not a regular file",
+                ));
+            }
+            let weak = before.metadata.etag.is_none() && before.metadata.modified.is_none();
+            let mut content = Vec::with_capacity(before.metadata.size as usize);
+            let mut stream = self.open(path)?;
+            copy_stream(&mut stream, &mut content, &TransferOpts::default())?;
+            self.on_read(stream)?;
+            let digest = match weak {
+                true => Some(
+                    ChecksumAlgorithm::Sha256
+                        .hash_reader(&mut content.as_slice())
+                        .map_err(io_err)?,
+                ),
+                false => None,
+            };
+            let content = f(content);
+            let name = before.name();
+            let tmp = path.with_file_name(format!(".{name}.edit.tmp"));
+            let mut metadata = Metadata::default();
+            metadata.mode = before.metadata.mode;
+            trace!(
+                "Writing new content of {} to {}",
+                path.display(),
+                tmp.display()
+            );
+            self.create_file(tmp.as_path(), &metadata, Box::new(io::Cursor::new(content)))?;
+            let after = self.stat(path)?;
+            let mut changed = before.metadata.etag != after.metadata.etag
+                || before.metadata.version != after.metadata.version
+                || before.metadata.modified != after.metadata.modified
+                || before.metadata.size != after.metadata.size;
+            if let (false, Some(digest)) = (changed, digest) {
+                let mut stream = self.open(path)?;
+                let current = ChecksumAlgorithm::Sha256
+                    .hash_reader(&mut stream)
+                    .map_err(io_err)?;
+                self.on_read(stream)?;
+                changed = current != digest;
+            }
+            let result = match changed {
+                true => Err(RemoteError::new_ex(
+                    RemoteErrorType::Conflict,
+                    format!("{} was changed while editing it", path.display()),
+                )),
+                false => self.mov(tmp.as_path(), path),
+            };
+            if let Err(err) = result {
+                debug!("Could not edit {}: {}", path.display(), err);
+                if let Err(err) = self.remove_file(tmp.as_path()) {
+                    warn!("Failed to remove {}: {}", tmp.display(), err);
+                }
+                return Err(err);
+            }
+            Ok(())
+        })
     }
 
     /// Blocking implementation of `create`
@@ -187,24 +1262,48 @@ pub trait RemoteFs {
     ///
     /// ### Default implementation
     ///
-    /// By default this function uses the streams function to copy content from reader to writer
+    /// By default this function uses the streams function to copy content from reader to writer with `copy_stream`.
+    /// If `on_written` fails after the content has been copied, a `FinalizeFailed` error is returned,
+    /// from which the amount of bytes written can be retrieved with `RemoteError::bytes_written`
     fn create_file(
         &mut self,
         path: &Path,
         metadata: &Metadata,
         mut reader: Box<dyn Read + Send>,
     ) -> RemoteResult<u64> {
-        if self.is_connected() {
-            let mut stream = self.create(path, metadata)?;
-            trace!("Opened remote file");
-            let sz = io::copy(&mut reader, &mut stream)
-                .map_err(|e| RemoteError::new_ex(RemoteErrorType::ProtocolError, e.to_string()))?;
-            self.on_written(stream)?;
-            trace!("Written {} bytes to destination", sz);
-            Ok(sz)
-        } else {
-            Err(RemoteError::new(RemoteErrorType::NotConnected))
-        }
+        in_context("create_file", path, || {
+            if self.is_connected() {
+                let mut stream = self.create(path, metadata)?;
+                trace!("Opened remote file");
+                let sz = copy_stream(&mut reader, &mut stream, &TransferOpts::default())?;
+                self.on_written(stream)
+                    .map_err(|e| RemoteError::finalize_failed(sz, e))?;
+                trace!("Written {} bytes to destination", sz);
+                Ok(sz)
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
+    }
+
+    /// Create the file at `path` with the content of `reader` as `create_file` does, returning the created file with its effective metadata,
+    /// since the server may apply a umask to the mode requested in `metadata`.
+    /// If `strict_mode` is set and the effective mode differs from the requested one, the mode is set again with `setstat`
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method calls `create_file`, then `stat`, and `setstat` if required
+    fn create_file_ext(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+        strict_mode: bool,
+    ) -> RemoteResult<File> {
+        in_context("create_file_ext", path, || {
+            self.create_file(path, metadata, reader)?;
+            effective_metadata(self, path, metadata.mode, strict_mode)
+        })
     }
 
     /// Blocking implementation of `open`
@@ -216,19 +1315,20 @@ pub trait RemoteFs {
     ///
     /// ### Default implementation
     ///
-    /// By default this function uses the streams function to copy content from reader to writer
+    /// By default this function uses the streams function to copy content from reader to writer with `copy_stream`
     fn open_file(&mut self, src: &Path, mut dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
-        if self.is_connected() {
-            let mut stream = self.open(src)?;
-            trace!("File opened");
-            let sz = io::copy(&mut stream, &mut dest)
-                .map_err(|e| RemoteError::new_ex(RemoteErrorType::ProtocolError, e.to_string()))?;
-            self.on_read(stream)?;
-            trace!("Copied {} bytes to destination", sz);
-            Ok(sz)
-        } else {
-            Err(RemoteError::new(RemoteErrorType::NotConnected))
-        }
+        in_context("open_file", src, || {
+            if self.is_connected() {
+                let mut stream = self.open(src)?;
+                trace!("File opened");
+                let sz = copy_stream(&mut stream, &mut dest, &TransferOpts::default())?;
+                self.on_read(stream)?;
+                trace!("Copied {} bytes to destination", sz);
+                Ok(sz)
+            } else {
+                Err(RemoteError::new(RemoteErrorType::NotConnected))
+            }
+        })
     }
 
     /// Find files from current directory (in all subdirectories) whose name matches the provided search
@@ -247,6 +1347,29 @@ pub trait RemoteFs {
         }
     }
 
+    /// Find files from current directory (in all subdirectories) whose name matches the provided search,
+    /// skipping the paths excluded by `rules`. Excluded directories are not listed.
+    /// Search supports wildcards ('?', '*')
+    ///
+    /// ### Default implementation
+    ///
+    /// By default this method walks the current directory with `walk_dir`, then filters entries by name
+    #[cfg(feature = "find")]
+    fn find_with(&mut self, search: &str, rules: &IgnoreRules) -> RemoteResult<Vec<File>> {
+        if self.is_connected() {
+            let pwd = self.pwd()?;
+            let filter = WildMatch::new(search);
+            let opts = WalkDirOpts::default().ignore(rules.clone());
+            Ok(self
+                .walk_dir(pwd.as_path(), &opts)?
+                .into_iter()
+                .filter(|x| filter.matches(x.name().as_str()))
+                .collect())
+        } else {
+            Err(RemoteError::new(RemoteErrorType::NotConnected))
+        }
+    }
+
     /// Search recursively in `dir` for file matching the wildcard.
     ///
     /// ### ⚠️ Warning
@@ -255,42 +1378,1270 @@ pub trait RemoteFs {
     /// NOTE: don't call this method from outside; consider it as private
     #[cfg(feature = "find")]
     fn iter_search(&mut self, dir: &Path, filter: &WildMatch) -> RemoteResult<Vec<File>> {
-        let mut drained: Vec<File> = Vec::new();
-        // Scan directory
-        match self.list_dir(dir) {
-            Ok(entries) => {
-                /* For each entry:
-                - if is dir: call iter_search with `dir`
-                    - push `iter_search` result to `drained`
-                - if is file: check if it matches `filter`
-                    - if it matches `filter`: push to to filter
-                */
-                for entry in entries.into_iter() {
-                    if entry.is_dir() {
-                        // If directory name, matches wildcard, push it to drained
-                        if filter.matches(entry.name().as_str()) {
-                            drained.push(entry.clone());
+        in_context("iter_search", dir, || {
+            let mut drained: Vec<File> = Vec::new();
+            // Scan directory
+            match self.list_dir(dir) {
+                Ok(entries) => {
+                    /* For each entry:
+                    - if is dir: call iter_search with `dir`
+                        - push `iter_search` result to `drained`
+                    - if is file: check if it matches `filter`
+                        - if it matches `filter`: push to to filter
+                    */
+                    for entry in entries.into_iter() {
+                        if entry.is_dir() {
+                            // If directory name, matches wildcard, push it to drained
+                            if filter.matches(entry.name().as_str()) {
+                                drained.push(entry.clone());
+                            }
+                            drained.append(&mut self.iter_search(entry.path(), filter)?);
+                        } else if filter.matches(entry.name().as_str()) {
+                            drained.push(entry);
                         }
-                        drained.append(&mut self.iter_search(entry.path(), filter)?);
-                    } else if filter.matches(entry.name().as_str()) {
-                        drained.push(entry);
                     }
+                    Ok(drained)
                 }
-                Ok(drained)
+                Err(err) => Err(err),
             }
-            Err(err) => Err(err),
+        })
+    }
+}
+
+/// Check access to `path` running `test` with `flag`, if the remote supports `exec`.
+/// Returns `None` if the check couldn't be made
+fn test_access<T: RemoteFs + ?Sized>(fs: &mut T, flag: &str, path: &Path) -> Option<bool> {
+    if !fs.capabilities().exec {
+        return None;
+    }
+    let cmd = format!("test {} {}", flag, shell::escape_path(path));
+    match fs.exec(cmd.as_str()) {
+        Ok((0, _)) => Some(true),
+        Ok((1, _)) => Some(false),
+        Ok((code, _)) => {
+            debug!("test exited with code {}; checking permissions", code);
+            None
+        }
+        Err(err) => {
+            debug!("Could not run test ({}); checking permissions", err);
+            None
         }
     }
 }
 
+/// Returns whether `permissions` grant read (or write) access to anyone.
+/// If the remote doesn't report permissions, access is assumed to be granted
+fn permits(permissions: &Permissions, write: bool) -> bool {
+    match permissions {
+        Permissions::Unix(mode) => [mode.user(), mode.group(), mode.others()].iter().any(|x| {
+            if write {
+                x.write()
+            } else {
+                x.read()
+            }
+        }),
+        Permissions::Acl(acl) => acl.iter().any(|x| {
+            matches!(
+                (x.permission, write),
+                (AclPermission::FullControl, _)
+                    | (AclPermission::Read, false)
+                    | (AclPermission::Write, true)
+            )
+        }),
+        Permissions::None => true,
+    }
+}
+
+/// Stat the file at `path`, which has been created with `mode`, setting the mode again if it differs and `strict_mode` is set
+fn effective_metadata<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    path: &Path,
+    mode: Option<UnixPex>,
+    strict_mode: bool,
+) -> RemoteResult<File> {
+    let file = fs.stat(path)?;
+    let mode = match mode {
+        Some(mode) if file.metadata.mode != Some(mode) => mode,
+        _ => return Ok(file),
+    };
+    if !strict_mode {
+        debug!(
+            "Effective mode of {} is {:?} instead of {:o}",
+            path.display(),
+            file.metadata.mode.map(u32::from),
+            u32::from(mode)
+        );
+        return Ok(file);
+    }
+    debug!(
+        "Setting mode {:o} on {} again",
+        u32::from(mode),
+        path.display()
+    );
+    fs.setstat(path, file.metadata.mode(mode))?;
+    fs.stat(path)
+}
+
 #[cfg(test)]
 mod test {
 
+    use pretty_assertions::assert_eq;
+
+    use std::io::Cursor;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::{Arc, Mutex};
+
     use super::*;
-    use crate::mock::MockRemoteFs;
+    use crate::fs::{AclEntry, AclGrantee, Change};
+    use crate::mock::{MemoryFs, MockRemoteFs};
 
     #[test]
     fn should_be_able_to_create_trait_object() {
         let _: Box<dyn RemoteFs> = Box::new(MockRemoteFs {});
     }
+
+    #[test]
+    fn should_list_dir_with_opts() {
+        let mut client = MemoryFs::connected()
+            .with_file("/b.txt", b"hello")
+            .with_file("/a.txt", b"hello, world");
+        let entries = client
+            .list_dir_opts(
+                Path::new("/"),
+                &ListDirOpts::default()
+                    .sort(crate::fs::SortBy::Size)
+                    .reverse(true),
+            )
+            .unwrap();
+        assert_eq!(
+            entries.iter().map(|x| x.name()).collect::<Vec<String>>(),
+            vec!["a.txt", "b.txt"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "find")]
+    fn should_find_with_ignore_rules() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"")
+            .with_dir("/home/.git")
+            .with_file("/home/.git/b.txt", b"")
+            .with_dir("/home/docs")
+            .with_file("/home/docs/c.txt", b"")
+            .with_file("/home/docs/d.txt", b"");
+        client.change_dir(Path::new("/home")).unwrap();
+        let rules = IgnoreRules::default()
+            .exclude(".git/")
+            .exclude("/docs/d.txt");
+        assert_eq!(
+            client
+                .find_with("*.txt", &rules)
+                .unwrap()
+                .into_iter()
+                .map(|x| x.path)
+                .collect::<Vec<PathBuf>>(),
+            vec![
+                PathBuf::from("/home/a.txt"),
+                PathBuf::from("/home/docs/c.txt"),
+            ]
+        );
+        assert_eq!(client.find("*.txt").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn should_walk_dir() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_dir("/home/omar")
+            .with_file("/home/omar/.bashrc", b"")
+            .with_file("/home/omar/a.txt", b"")
+            .with_dir("/tmp")
+            .with_symlink("/home/omar/tmp", "../../tmp")
+            .with_file("/tmp/b.txt", b"");
+        let paths = |entries: Vec<File>| {
+            entries
+                .into_iter()
+                .map(|x| x.path)
+                .collect::<Vec<PathBuf>>()
+        };
+        assert_eq!(
+            paths(
+                client
+                    .walk_dir(Path::new("/home"), &WalkDirOpts::default())
+                    .unwrap()
+            ),
+            vec![
+                PathBuf::from("/home/omar"),
+                PathBuf::from("/home/omar/.bashrc"),
+                PathBuf::from("/home/omar/a.txt"),
+                PathBuf::from("/home/omar/tmp"),
+            ]
+        );
+        assert_eq!(
+            paths(
+                client
+                    .walk_dir(
+                        Path::new("/home"),
+                        &WalkDirOpts::default()
+                            .follow_symlinks(true)
+                            .skip_hidden(true)
+                    )
+                    .unwrap()
+            ),
+            vec![
+                PathBuf::from("/home/omar"),
+                PathBuf::from("/home/omar/a.txt"),
+                PathBuf::from("/home/omar/tmp"),
+                PathBuf::from("/home/omar/tmp/b.txt"),
+            ]
+        );
+        assert_eq!(
+            paths(
+                client
+                    .walk_dir(Path::new("/home"), &WalkDirOpts::default().max_depth(1))
+                    .unwrap()
+            ),
+            vec![PathBuf::from("/home/omar")]
+        );
+    }
+
+    #[test]
+    fn should_detect_symlink_loops_on_walk_dir() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_dir("/home/omar")
+            .with_symlink("/home/omar/home", "/home")
+            .with_symlink("/home/a", "/home/b")
+            .with_symlink("/home/b", "/home/a");
+        assert_eq!(
+            client
+                .walk_dir(
+                    Path::new("/home/omar"),
+                    &WalkDirOpts::default().follow_symlinks(true)
+                )
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::SymlinkLoop
+        );
+        assert_eq!(
+            client
+                .walk_dir(
+                    Path::new("/"),
+                    &WalkDirOpts::default().follow_symlinks(true)
+                )
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::SymlinkLoop
+        );
+    }
+
+    #[test]
+    fn should_remove_dir_all_without_following_symlinks() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_dir("/home/omar")
+            .with_file("/home/omar/a.txt", b"")
+            .with_symlink("/home/omar/home", "/home")
+            .with_dir("/tmp")
+            .with_file("/tmp/b.txt", b"")
+            .with_symlink("/home/tmp", "/tmp");
+        assert!(client.remove_dir_all(Path::new("/home")).is_ok());
+        assert_eq!(client.exists(Path::new("/home")).unwrap(), false);
+        assert_eq!(client.exists(Path::new("/tmp/b.txt")).unwrap(), true);
+    }
+
+    #[test]
+    fn should_set_permissions() {
+        let mut client = MemoryFs::connected().with_file("/a.txt", b"");
+        client
+            .set_permissions(Path::new("/a.txt"), Permissions::Unix(UnixPex::from(0o640)))
+            .unwrap();
+        assert_eq!(
+            client.stat(Path::new("/a.txt")).unwrap().metadata.mode,
+            Some(UnixPex::from(0o640))
+        );
+        assert_eq!(
+            client
+                .set_permissions(Path::new("/a.txt"), Permissions::Acl(vec![]))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+
+    #[test]
+    fn should_set_owner_recursive() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"")
+            .with_dir("/home/omar")
+            .with_file("/home/omar/b.txt", b"")
+            .with_symlink("/home/tmp", "/tmp");
+        client
+            .set_owner_recursive(Path::new("/home"), Some(1000), None)
+            .unwrap();
+        for path in ["/home", "/home/a.txt", "/home/omar", "/home/omar/b.txt"] {
+            let metadata = client.stat(Path::new(path)).unwrap().metadata;
+            assert_eq!(metadata.uid, Some(1000));
+            assert!(metadata.gid.is_none());
+        }
+        assert!(client
+            .stat(Path::new("/home/tmp"))
+            .unwrap()
+            .metadata
+            .uid
+            .is_none());
+    }
+
+    #[test]
+    fn should_set_mode_recursive() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"")
+            .with_dir("/home/omar")
+            .with_file("/home/omar/b.txt", b"");
+        client
+            .set_mode_recursive(Path::new("/home"), UnixPex::from(0o700))
+            .unwrap();
+        for path in ["/home", "/home/a.txt", "/home/omar", "/home/omar/b.txt"] {
+            assert_eq!(
+                client.stat(Path::new(path)).unwrap().metadata.mode,
+                Some(UnixPex::from(0o700))
+            );
+        }
+    }
+
+    #[test]
+    fn should_touch_file() {
+        let mut client = MemoryFs::connected().with_file("/a.txt", b"hello");
+        let accessed = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(3600);
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(7200);
+        client
+            .touch(Path::new("/a.txt"), accessed, modified)
+            .unwrap();
+        let metadata = client.stat(Path::new("/a.txt")).unwrap().metadata;
+        assert_eq!(metadata.accessed, Some(accessed));
+        assert_eq!(metadata.modified, Some(modified));
+        assert_eq!(client.content("/a.txt").unwrap(), b"hello");
+        // create file
+        client
+            .touch(Path::new("/b.txt"), accessed, modified)
+            .unwrap();
+        assert_eq!(
+            client.stat(Path::new("/b.txt")).unwrap().metadata.modified,
+            Some(modified)
+        );
+        assert_eq!(client.content("/b.txt").unwrap(), b"");
+    }
+
+    #[test]
+    fn should_emit_events_on_remove_dir_all() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"")
+            .with_dir("/home/omar")
+            .with_file("/home/omar/b.txt", b"");
+        let mut events = vec![];
+        client
+            .remove_dir_all_with(Path::new("/home"), ErrorPolicy::Abort, &mut |ev| {
+                events.push(ev)
+            })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                FsEvent::Entered(PathBuf::from("/home")),
+                FsEvent::Removed(PathBuf::from("/home/a.txt")),
+                FsEvent::Entered(PathBuf::from("/home/omar")),
+                FsEvent::Removed(PathBuf::from("/home/omar/b.txt")),
+                FsEvent::Removed(PathBuf::from("/home/omar")),
+                FsEvent::Removed(PathBuf::from("/home")),
+            ]
+        );
+        assert_eq!(client.exists(Path::new("/home")).unwrap(), false);
+    }
+
+    #[test]
+    fn should_continue_on_error_on_remove_dir_all() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"")
+            .with_dir("/home/omar")
+            .with_file("/home/omar/b.txt", b"")
+            .with_file("/home/z.txt", b"");
+        // a file which can't be removed
+        client.fail_on("/home/omar/b.txt");
+        assert_eq!(
+            client
+                .remove_dir_all_with(Path::new("/home"), ErrorPolicy::Abort, &mut |_| {})
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(client.exists(Path::new("/home/z.txt")).unwrap(), true);
+        let report = client
+            .remove_dir_all_with(Path::new("/home"), ErrorPolicy::Continue, &mut |_| {})
+            .unwrap();
+        assert_eq!(report.is_ok(), false);
+        assert_eq!(
+            report
+                .failures
+                .iter()
+                .map(|(path, err)| (path.as_path(), err.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                (Path::new("/home/omar/b.txt"), RemoteErrorType::PexError),
+                (Path::new("/home/omar"), RemoteErrorType::DirectoryNotEmpty),
+                (Path::new("/home"), RemoteErrorType::DirectoryNotEmpty),
+            ]
+        );
+        assert_eq!(report.succeeded, 1);
+        assert_eq!(client.exists(Path::new("/home/z.txt")).unwrap(), false);
+        assert_eq!(client.exists(Path::new("/home/omar/b.txt")).unwrap(), true);
+    }
+
+    #[test]
+    fn should_open_buffered_streams() {
+        let mut client = MemoryFs::connected();
+        let opts = StreamOpts::default().buffer_size(4);
+        let mut stream = client
+            .create_with(Path::new("/a.txt"), &Metadata::default(), &opts)
+            .unwrap();
+        stream.write_all(b"Hello, world!").unwrap();
+        stream.flush().unwrap();
+        client.on_written(stream).unwrap();
+        assert_eq!(client.content("/a.txt").unwrap(), b"Hello, world!");
+        let mut stream = client.open_with(Path::new("/a.txt"), &opts).unwrap();
+        assert!(stream.seekable());
+        let mut content = String::new();
+        stream.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "Hello, world!");
+    }
+
+    #[test]
+    fn should_report_checksum_on_finalize() {
+        let mut client = MemoryFs::connected();
+        let opts = StreamOpts::default().checksum(crate::fs::ChecksumAlgorithm::Crc32);
+        let mut stream = client
+            .create_with(Path::new("/a.txt"), &Metadata::default(), &opts)
+            .unwrap();
+        stream.write_all(b"123456789").unwrap();
+        let report = client.on_written_ext(stream).unwrap();
+        assert_eq!(report.bytes, 9);
+        assert_eq!(report.checksum.as_deref(), Some("cbf43926"));
+    }
+
+    #[test]
+    fn should_restore_wrkdir_on_reconnect() {
+        let mut client = MemoryFs::connected().with_dir("/home");
+        client.change_dir(Path::new("/home")).unwrap();
+        assert!(client.reconnect().is_ok());
+        assert_eq!(client.is_connected(), true);
+        assert_eq!(client.pwd().unwrap(), PathBuf::from("/home"));
+        // reconnect when disconnected
+        client.disconnect().unwrap();
+        assert!(client.reconnect().is_ok());
+        assert_eq!(client.pwd().unwrap(), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn should_close() {
+        assert!(MemoryFs::connected().close().is_ok());
+        // closing a disconnected client is a no-op
+        assert!(MemoryFs::default().close().is_ok());
+    }
+
+    #[test]
+    fn should_edit_file() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"hello");
+        client
+            .edit(Path::new("/home/a.txt"), |mut x| {
+                x.extend_from_slice(b" world");
+                x
+            })
+            .unwrap();
+        assert_eq!(client.content("/home/a.txt").unwrap(), b"hello world");
+        assert_eq!(client.list_dir(Path::new("/home")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn should_not_edit_file_changed_concurrently() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"hello");
+        // size changes
+        let mut other = client.share();
+        let err = client
+            .edit(Path::new("/home/a.txt"), move |x| {
+                other
+                    .create_file(
+                        Path::new("/home/a.txt"),
+                        &Metadata::default(),
+                        Box::new(io::Cursor::new(b"hello there".to_vec())),
+                    )
+                    .unwrap();
+                x
+            })
+            .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::Conflict);
+        assert_eq!(client.content("/home/a.txt").unwrap(), b"hello there");
+        assert_eq!(client.list_dir(Path::new("/home")).unwrap().len(), 1);
+        // same size, but different content
+        let mut other = client.share();
+        let err = client
+            .edit(Path::new("/home/a.txt"), move |x| {
+                other
+                    .create_file(
+                        Path::new("/home/a.txt"),
+                        &Metadata::default(),
+                        Box::new(io::Cursor::new(b"HELLO THERE".to_vec())),
+                    )
+                    .unwrap();
+                x
+            })
+            .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::Conflict);
+        assert_eq!(client.content("/home/a.txt").unwrap(), b"HELLO THERE");
+    }
+
+    #[test]
+    fn should_list_dir_with_callback() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"a")
+            .with_file("/home/b.txt", b"b")
+            .with_file("/home/c.txt", b"c");
+        let mut entries = 0;
+        client
+            .list_dir_cb(Path::new("/home"), &mut |_| {
+                entries += 1;
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(entries, 3);
+        let mut entries = 0;
+        client
+            .list_dir_cb(Path::new("/home"), &mut |_| {
+                entries += 1;
+                ControlFlow::Break(())
+            })
+            .unwrap();
+        assert_eq!(entries, 1);
+    }
+
+    #[test]
+    fn should_set_error_context_in_defaults() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"hello");
+        let err = client.dir_version(Path::new("/home/a.txt")).unwrap_err();
+        assert_eq!(err.op(), Some("dir_version"));
+        assert_eq!(err.path(), Some(Path::new("/home/a.txt")));
+        assert_eq!(
+            client
+                .remove_dir_all(Path::new("/tmp"))
+                .unwrap_err()
+                .to_string(),
+            "remove_dir_all /tmp: no such file or directory"
+        );
+        let err = client
+            .copy_with(
+                Path::new("/home/b.txt"),
+                Path::new("/home/c.txt"),
+                &CopyOpts::default(),
+            )
+            .unwrap_err();
+        assert_eq!(err.op(), Some("copy_with"));
+        assert_eq!(err.path(), Some(Path::new("/home/b.txt")));
+    }
+
+    #[test]
+    fn should_get_dir_version() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"hello");
+        assert_eq!(client.dir_version(Path::new("/home")).unwrap(), None);
+        client
+            .setstat(
+                Path::new("/home"),
+                Metadata::default().modified(UNIX_EPOCH + Duration::from_millis(1500)),
+            )
+            .unwrap();
+        assert_eq!(
+            client.dir_version(Path::new("/home")).unwrap().as_deref(),
+            Some("1.500000000")
+        );
+        client
+            .setstat(Path::new("/home"), Metadata::default().etag("abc"))
+            .unwrap();
+        assert_eq!(
+            client.dir_version(Path::new("/home")).unwrap().as_deref(),
+            Some("abc")
+        );
+        assert_eq!(
+            client
+                .dir_version(Path::new("/home/a.txt"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::BadFile
+        );
+    }
+
+    #[test]
+    fn should_get_home_dir() {
+        let mut client = MemoryFs::connected().with_exec("echo \"$HOME\"", 0, "/home/omar\n");
+        assert_eq!(client.home_dir().unwrap(), PathBuf::from("/home/omar"));
+        let mut client = MemoryFs::connected().with_exec("echo \"$HOME\"", 0, "\n");
+        assert_eq!(
+            client.home_dir().unwrap_err().kind,
+            RemoteErrorType::ProtocolError
+        );
+        assert_eq!(
+            MemoryFs::connected().home_dir().unwrap_err().kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+
+    #[test]
+    fn should_create_with_effective_mode() {
+        let mut client = MemoryFs::connected().with_umask(0o022);
+        let dir = client
+            .create_dir_ext(Path::new("/a"), UnixPex::from(0o777), false)
+            .unwrap();
+        assert_eq!(dir.metadata.mode, Some(UnixPex::from(0o755)));
+        let dir = client
+            .create_dir_ext(Path::new("/b"), UnixPex::from(0o777), true)
+            .unwrap();
+        assert_eq!(dir.metadata.mode, Some(UnixPex::from(0o777)));
+        let file = client
+            .create_file_ext(
+                Path::new("/a/c.txt"),
+                &Metadata::default().mode(UnixPex::from(0o666)),
+                Box::new(io::Cursor::new(b"hello".to_vec())),
+                false,
+            )
+            .unwrap();
+        assert_eq!(file.metadata.mode, Some(UnixPex::from(0o644)));
+        let file = client
+            .create_file_ext(
+                Path::new("/a/d.txt"),
+                &Metadata::default().mode(UnixPex::from(0o666)),
+                Box::new(io::Cursor::new(b"hello".to_vec())),
+                true,
+            )
+            .unwrap();
+        assert_eq!(file.metadata.mode, Some(UnixPex::from(0o666)));
+        assert_eq!(file.metadata.size, 5);
+    }
+
+    #[test]
+    fn should_not_summarize_by_default() {
+        assert!(MemoryFs::connected().summary().is_none());
+    }
+
+    #[test]
+    fn should_check_access_with_exec() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"a")
+            .with_exec("test -r /home/a.txt", 0, "")
+            .with_exec("test -w /home/a.txt", 1, "")
+            .with_exec("test -w /home", 0, "");
+        assert_eq!(client.can_read(Path::new("/home/a.txt")).unwrap(), true);
+        assert_eq!(client.can_write(Path::new("/home/a.txt")).unwrap(), false);
+        assert_eq!(client.can_write(Path::new("/home/b.txt")).unwrap(), true);
+    }
+
+    #[test]
+    fn should_check_access_with_permissions() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"a")
+            .with_file("/home/b.txt", b"b");
+        client
+            .setstat(
+                Path::new("/home/a.txt"),
+                Metadata::default().mode(UnixPex::from(0o444)),
+            )
+            .unwrap();
+        let acl = vec![AclEntry::new(AclGrantee::Everyone, AclPermission::Write)];
+        client
+            .setstat(Path::new("/home/b.txt"), Metadata::default().acl(acl))
+            .unwrap();
+        assert_eq!(client.can_read(Path::new("/home/a.txt")).unwrap(), true);
+        assert_eq!(client.can_write(Path::new("/home/a.txt")).unwrap(), false);
+        assert_eq!(client.can_read(Path::new("/home/b.txt")).unwrap(), false);
+        assert_eq!(client.can_write(Path::new("/home/b.txt")).unwrap(), true);
+        assert_eq!(client.can_read(Path::new("/home/c.txt")).unwrap(), false);
+        // permissions are not reported for /home
+        assert_eq!(client.can_write(Path::new("/home/c.txt")).unwrap(), true);
+    }
+
+    #[test]
+    fn should_exec_bytes() {
+        let mut client = MemoryFs::connected().with_exec("echo hello", 0, "hello\n");
+        let output = client.exec_bytes("echo hello").unwrap();
+        assert_eq!(output, ExecOutput::new(0, b"hello\n".to_vec(), vec![]));
+        assert_eq!(
+            client.exec_bytes("uname").unwrap_err().kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+
+    #[test]
+    fn should_exec_with_env() {
+        let mut client =
+            MemoryFs::connected().with_exec("export LC_ALL=C TZ=UTC; date", 0, "Thu Jan  1");
+        assert_eq!(
+            client
+                .exec_with_env("date", &ShellEnv::default().var("TZ", "UTC"))
+                .unwrap(),
+            (0, "Thu Jan  1".to_string())
+        );
+    }
+
+    #[test]
+    fn should_get_server_time() {
+        let mut client = MemoryFs::connected().with_exec("date -u +%s", 0, "1700000000\n");
+        assert_eq!(
+            client.server_time().unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1700000000)
+        );
+        let mut client = MemoryFs::connected().with_exec("date -u +%s", 0, "yesterday");
+        assert_eq!(
+            client.server_time().unwrap_err().kind,
+            RemoteErrorType::ProtocolError
+        );
+        let mut client = MemoryFs::connected();
+        assert_eq!(
+            client.server_time().unwrap_err().kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+
+    #[test]
+    fn should_resume_upload() {
+        let mut client = MemoryFs::connected().with_file("/a.txt", b"hello");
+        let reader = Box::new(Cursor::new(b"hello, world!".to_vec()));
+        assert_eq!(
+            client
+                .resume_upload(Path::new("/a.txt"), &Metadata::default(), reader, None)
+                .unwrap(),
+            8
+        );
+        assert_eq!(client.content("/a.txt").unwrap(), b"hello, world!");
+        // missing file
+        let reader = Box::new(Cursor::new(b"hello".to_vec()));
+        assert_eq!(
+            client
+                .resume_upload(Path::new("/b.txt"), &Metadata::default(), reader, None)
+                .unwrap(),
+            5
+        );
+        assert_eq!(client.content("/b.txt").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn should_start_over_resume_upload_if_prefix_differs() {
+        let mut client = MemoryFs::connected()
+            .with_file("/a.txt", b"HELLO")
+            .with_file("/b.txt", b"hello");
+        let reader = Box::new(Cursor::new(b"hello, world!".to_vec()));
+        assert_eq!(
+            client
+                .resume_upload(
+                    Path::new("/a.txt"),
+                    &Metadata::default(),
+                    reader,
+                    Some(ChecksumAlgorithm::Sha256)
+                )
+                .unwrap(),
+            13
+        );
+        assert_eq!(client.content("/a.txt").unwrap(), b"hello, world!");
+        let reader = Box::new(Cursor::new(b"hello, world!".to_vec()));
+        assert_eq!(
+            client
+                .resume_upload(
+                    Path::new("/b.txt"),
+                    &Metadata::default(),
+                    reader,
+                    Some(ChecksumAlgorithm::Crc32)
+                )
+                .unwrap(),
+            8
+        );
+        assert_eq!(client.content("/b.txt").unwrap(), b"hello, world!");
+        // remote bigger than local
+        let reader = Box::new(Cursor::new(b"hi".to_vec()));
+        assert_eq!(
+            client
+                .resume_upload(Path::new("/b.txt"), &Metadata::default(), reader, None)
+                .unwrap(),
+            2
+        );
+        assert_eq!(client.content("/b.txt").unwrap(), b"hi");
+    }
+
+    #[test]
+    fn should_copy_with_opts() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_dir("/home/omar")
+            .with_file("/home/omar/a.txt", b"hello")
+            .with_symlink("/home/omar/b.txt", "a.txt")
+            .with_symlink("/home/omar/loop", "/home")
+            .with_dir("/tmp");
+        client
+            .setstat(
+                Path::new("/home/omar/a.txt"),
+                Metadata::default().mode(UnixPex::from(0o600)).uid(1000),
+            )
+            .unwrap();
+        // links are copied as links
+        client
+            .copy_with(
+                Path::new("/home/omar/b.txt"),
+                Path::new("/tmp/b.txt"),
+                &CopyOpts::default(),
+            )
+            .unwrap();
+        assert!(client.stat(Path::new("/tmp/b.txt")).unwrap().is_symlink());
+        // dereference
+        let opts = CopyOpts::default().dereference(true);
+        client
+            .copy_with(
+                Path::new("/home/omar/b.txt"),
+                Path::new("/tmp/c.txt"),
+                &opts,
+            )
+            .unwrap();
+        let file = client.stat(Path::new("/tmp/c.txt")).unwrap();
+        assert!(file.is_file());
+        assert_eq!(file.metadata().mode, None);
+        assert_eq!(client.content("/tmp/c.txt").unwrap(), b"hello");
+        assert_eq!(
+            client
+                .copy_with(Path::new("/home/omar"), Path::new("/tmp/omar"), &opts)
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::SymlinkLoop
+        );
+        // preserve
+        let opts = CopyOpts::default().preserve(PreserveMode::Mode);
+        client
+            .copy_with(
+                Path::new("/home/omar/a.txt"),
+                Path::new("/tmp/d.txt"),
+                &opts,
+            )
+            .unwrap();
+        let metadata = client.stat(Path::new("/tmp/d.txt")).unwrap().metadata;
+        assert_eq!(metadata.mode, Some(UnixPex::from(0o600)));
+        let opts = CopyOpts::default().preserve(PreserveMode::All);
+        client
+            .copy_with(
+                Path::new("/home/omar/a.txt"),
+                Path::new("/tmp/e.txt"),
+                &opts,
+            )
+            .unwrap();
+        let metadata = client.stat(Path::new("/tmp/e.txt")).unwrap().metadata;
+        assert_eq!(metadata.mode, Some(UnixPex::from(0o600)));
+        assert_eq!(metadata.uid, Some(1000));
+    }
+
+    #[test]
+    fn should_move_with_fallback() {
+        let mut client = MemoryFs::connected()
+            .without("mov")
+            .with_dir("/home")
+            .with_dir("/home/omar")
+            .with_file("/home/omar/a.txt", b"hello")
+            .with_symlink("/home/omar/b.txt", "a.txt")
+            .with_dir("/tmp")
+            .with_file("/tmp/c.txt", b"world");
+        let opts = MoveOpts::default().copy_fallback(true);
+        assert_eq!(
+            client
+                .move_with(
+                    Path::new("/tmp/c.txt"),
+                    Path::new("/home/c.txt"),
+                    &MoveOpts::default()
+                )
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+        assert!(client
+            .move_with(Path::new("/home/omar"), Path::new("/tmp/omar"), &opts)
+            .is_ok());
+        assert_eq!(client.exists(Path::new("/home/omar")).unwrap(), false);
+        assert_eq!(client.content("/tmp/omar/a.txt").unwrap(), b"hello");
+        assert_eq!(
+            client
+                .stat(Path::new("/tmp/omar/b.txt"))
+                .unwrap()
+                .metadata()
+                .symlink
+                .as_deref(),
+            Some(Path::new("a.txt"))
+        );
+        assert!(client
+            .move_with(Path::new("/tmp/c.txt"), Path::new("/home/c.txt"), &opts)
+            .is_ok());
+        assert_eq!(client.exists(Path::new("/tmp/c.txt")).unwrap(), false);
+        assert_eq!(client.content("/home/c.txt").unwrap(), b"world");
+        assert_eq!(
+            client
+                .move_with(Path::new("/tmp/d.txt"), Path::new("/home/d.txt"), &opts)
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+    }
+
+    #[test]
+    fn should_not_fall_back_to_copy_on_other_errors() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"hello")
+            .with_dir("/tmp");
+        client.fail_on("/home/a.txt");
+        let opts = MoveOpts::default().copy_fallback(true);
+        assert_eq!(
+            client
+                .move_with(Path::new("/home/a.txt"), Path::new("/tmp/a.txt"), &opts)
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(client.exists(Path::new("/tmp/a.txt")).unwrap(), false);
+    }
+
+    #[test]
+    fn should_report_finalize_failure_on_create_file() {
+        let mut client = MemoryFs::connected().without("on_written");
+        let err = client
+            .create_file(
+                Path::new("/a.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"hello".to_vec())),
+            )
+            .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::FinalizeFailed);
+        assert_eq!(err.bytes_written(), Some(5));
+        let err = client
+            .append_file(
+                Path::new("/a.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"!".to_vec())),
+            )
+            .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::FinalizeFailed);
+        assert_eq!(err.bytes_written(), Some(1));
+    }
+
+    #[test]
+    fn should_compute_checksum() {
+        let mut client = MemoryFs::connected().with_file("/a.txt", b"world");
+        assert_eq!(
+            client
+                .checksum(Path::new("/a.txt"), ChecksumAlgorithm::Crc32)
+                .unwrap(),
+            "3a771143"
+        );
+        assert_eq!(
+            client
+                .checksum(Path::new("/a.txt"), ChecksumAlgorithm::Sha256)
+                .unwrap(),
+            "486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7"
+        );
+        assert!(client
+            .checksum(Path::new("/b.txt"), ChecksumAlgorithm::Crc32)
+            .is_err());
+        let mut client = MemoryFs::connected()
+            .with_file("/a.txt", b"world")
+            .with_exec("sha256sum < /a.txt", 0, "ABCDEF  -\n");
+        assert_eq!(
+            client
+                .checksum(Path::new("/a.txt"), ChecksumAlgorithm::Sha256)
+                .unwrap(),
+            "abcdef"
+        );
+        // sha256sum escapes names with `\` or a newline, prefixing the line with `\`
+        let mut client = MemoryFs::connected()
+            .with_file("/a\\b.txt", b"world")
+            .with_exec("sha256sum < '/a\\b.txt'", 0, "abcdef  -\n");
+        assert_eq!(
+            client
+                .checksum(Path::new("/a\\b.txt"), ChecksumAlgorithm::Sha256)
+                .unwrap(),
+            "abcdef"
+        );
+    }
+
+    #[test]
+    fn should_take_snapshots() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/a.txt", b"hello")
+            .with_dir("/home/docs")
+            .with_file("/home/docs/b.txt", b"world");
+        let old = client
+            .snapshot(
+                Path::new("/home"),
+                &WalkDirOpts::default(),
+                Some(ChecksumAlgorithm::Crc32),
+            )
+            .unwrap();
+        assert_eq!(old.len(), 3);
+        assert_eq!(old.root(), Path::new("/home"));
+        assert_eq!(
+            old.get("docs/b.txt").unwrap().checksum.as_deref(),
+            Some("3a771143")
+        );
+        client.remove_file(Path::new("/home/a.txt")).unwrap();
+        client
+            .create_file(
+                Path::new("/home/docs/b.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"World".to_vec())),
+            )
+            .unwrap();
+        let new = client
+            .snapshot(
+                Path::new("/home"),
+                &WalkDirOpts::default(),
+                Some(ChecksumAlgorithm::Crc32),
+            )
+            .unwrap();
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                Change::Removed(PathBuf::from("a.txt")),
+                Change::Modified(PathBuf::from("docs/b.txt")),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_take_snapshots_of_relative_paths() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/home")
+            .with_dir("/home/docs")
+            .with_file("/home/docs/b.txt", b"world");
+        client.change_dir(Path::new("/home")).unwrap();
+        let snapshot = client
+            .snapshot(Path::new("./docs"), &WalkDirOpts::default(), None)
+            .unwrap();
+        assert_eq!(snapshot.root(), Path::new("/home/docs"));
+        assert_eq!(snapshot.len(), 1);
+        assert!(snapshot.get("b.txt").is_some());
+    }
+
+    #[test]
+    fn should_read_lines() {
+        let mut client = MemoryFs::connected().with_file("/a.log", b"one\ntwo\nthree\n");
+        assert_eq!(
+            client
+                .read_lines(Path::new("/a.log"))
+                .unwrap()
+                .collect::<RemoteResult<Vec<String>>>()
+                .unwrap(),
+            vec!["one", "two", "three"]
+        );
+        assert!(client.read_lines(Path::new("/b.log")).is_err());
+    }
+
+    #[test]
+    fn should_tail_file() {
+        let mut client = MemoryFs::connected().with_file("/a.log", b"one\ntwo\nthree\n");
+        assert_eq!(
+            client.tail(Path::new("/a.log"), 2).unwrap(),
+            vec!["two", "three"]
+        );
+        assert_eq!(client.tail(Path::new("/a.log"), 5).unwrap().len(), 3);
+        assert!(client.tail(Path::new("/a.log"), 0).unwrap().is_empty());
+        // fast path
+        let mut client = MemoryFs::connected()
+            .with_file("/a.log", b"one\ntwo\nthree\n")
+            .with_exec("tail -n 1 /a.log", 0, "THREE\n");
+        assert_eq!(client.tail(Path::new("/a.log"), 1).unwrap(), vec!["THREE"]);
+        assert_eq!(
+            client.tail(Path::new("/a.log"), 2).unwrap(),
+            vec!["two", "three"]
+        );
+        // file names starting with `-` are not taken for options
+        let mut client = MemoryFs::connected()
+            .with_file("/--help", b"one\ntwo\n")
+            .with_exec("tail -n 1 ./--help", 0, "TWO\n");
+        assert_eq!(client.tail(Path::new("--help"), 1).unwrap(), vec!["TWO"]);
+    }
+
+    #[test]
+    fn should_grep_files() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/logs")
+            .with_file("/logs/a.log", b"INFO start\nERROR disk full\nINFO stop\n")
+            .with_dir("/logs/old")
+            .with_file("/logs/old/b.log", b"error: timeout\n");
+        assert_eq!(
+            client
+                .grep(Path::new("/logs"), "ERROR", &GrepOpts::default())
+                .unwrap(),
+            vec![GrepMatch::new("/logs/a.log", 2, "ERROR disk full")]
+        );
+        let opts = GrepOpts::default().ignore_case(true);
+        assert_eq!(
+            client.grep(Path::new("/logs"), "error", &opts).unwrap(),
+            vec![
+                GrepMatch::new("/logs/a.log", 2, "ERROR disk full"),
+                GrepMatch::new("/logs/old/b.log", 1, "error: timeout"),
+            ]
+        );
+        assert_eq!(
+            client
+                .grep(Path::new("/logs/a.log"), "INFO", &opts.max_matches(1))
+                .unwrap(),
+            vec![GrepMatch::new("/logs/a.log", 1, "INFO start")]
+        );
+    }
+
+    #[test]
+    fn should_grep_with_exec() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/logs")
+            .with_exec(
+                "grep -rnHIF --null -e ERROR /logs",
+                0,
+                "/logs/a.log\u{0}2:ERROR: disk full\n/logs/old/b:c.log\u{0}10:ERROR\n",
+            )
+            .with_exec("grep -rnHIF --null -e DEBUG /logs", 1, "");
+        assert_eq!(
+            client
+                .grep(Path::new("/logs"), "ERROR", &GrepOpts::default())
+                .unwrap(),
+            vec![
+                GrepMatch::new("/logs/a.log", 2, "ERROR: disk full"),
+                GrepMatch::new("/logs/old/b:c.log", 10, "ERROR"),
+            ]
+        );
+        assert!(client
+            .grep(Path::new("/logs"), "DEBUG", &GrepOpts::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn should_zip_with_exec() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/site")
+            .with_exec("rm -f /site.zip && cd /site && zip -qry /site.zip .", 0, "")
+            .with_exec("unzip -qo /site.zip -d /deploy", 0, "");
+        assert!(client
+            .zip_dir(Path::new("/site"), Path::new("site.zip"))
+            .is_ok());
+        assert!(client.content("/site.zip").is_none());
+        assert!(client
+            .unzip(Path::new("/site.zip"), Path::new("/deploy"))
+            .is_ok());
+        assert_eq!(client.exists(Path::new("/deploy")).unwrap(), false);
+    }
+
+    #[test]
+    #[cfg(feature = "zip")]
+    fn should_zip_through_client() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/site")
+            .with_file("/site/index.html", b"<html></html>");
+        client
+            .zip_dir(Path::new("/site"), Path::new("/site.zip"))
+            .unwrap();
+        client
+            .unzip(Path::new("/site.zip"), Path::new("/deploy"))
+            .unwrap();
+        assert_eq!(
+            client.content("/deploy/index.html").unwrap(),
+            b"<html></html>"
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "zip"))]
+    fn should_not_zip_through_client_without_zip_feature() {
+        let mut client = MemoryFs::connected()
+            .with_dir("/site")
+            .with_file("/site/index.html", b"<html></html>");
+        assert_eq!(
+            client
+                .zip_dir(Path::new("/site"), Path::new("/site.zip"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+        assert!(client.content("/site.zip").is_none());
+    }
+
+    #[test]
+    fn should_connect_with_phases() {
+        let phases = Arc::new(Mutex::new(Vec::new()));
+        let reported = phases.clone();
+        let opts =
+            ConnectOpts::default().on_phase(move |phase| reported.lock().unwrap().push(phase));
+        let mut client = MemoryFs::default();
+        assert!(client.connect_with(&opts).is_ok());
+        assert!(client.is_connected());
+        assert_eq!(
+            *phases.lock().unwrap(),
+            vec![ConnectPhase::Connecting, ConnectPhase::Connected]
+        );
+        let mut client = MemoryFs::default();
+        let opts = ConnectOpts::default().cancel(Arc::new(AtomicBool::new(true)));
+        assert_eq!(
+            client.connect_with(&opts).unwrap_err().kind,
+            RemoteErrorType::Cancelled
+        );
+        assert!(!client.is_connected());
+    }
+
+    #[test]
+    fn should_ping() {
+        let mut client = MemoryFs::connected().with_dir("/home");
+        assert!(client.ping().is_ok());
+        let mut client = MemoryFs::default();
+        assert_eq!(
+            client.ping().unwrap_err().kind,
+            RemoteErrorType::NotConnected
+        );
+    }
+
+    #[test]
+    fn should_get_default_capabilities() {
+        let mut client = MemoryFs::connected();
+        assert_eq!(client.capabilities(), Capabilities::default());
+    }
+
+    #[test]
+    fn should_validate_path() {
+        let client = MemoryFs::connected();
+        assert!(client.validate_path(Path::new("/tmp/a.txt")).is_ok());
+        assert!(client.validate_path(Path::new("/tmp/a\0.txt")).is_err());
+    }
+
+    #[test]
+    fn should_report_bytes_written_on_finalize() {
+        let mut client = MemoryFs::connected();
+        let mut stream = client
+            .create(Path::new("/a.txt"), &Metadata::default())
+            .unwrap();
+        stream.write_all(b"Hello, world!").unwrap();
+        let report = client.on_written_ext(stream).unwrap();
+        assert_eq!(report.bytes, 13);
+        assert!(report.etag.is_none());
+        assert_eq!(client.content("/a.txt").unwrap(), b"Hello, world!");
+    }
 }
@@ -0,0 +1,134 @@
+//! ## Transfer
+//!
+//! copy data between streams, shared by all the copy paths of the crate
+
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::TransferOpts;
+use crate::{RemoteError, RemoteErrorType, RemoteResult};
+
+/// Copy all the data from `reader` to `writer`, as specified in `opts`, returning the amount of bytes copied.
+/// The writer is flushed at the end.
+///
+/// Fails with `Cancelled` if the copy is cancelled through `opts.cancel`.
+/// If reading or writing fails with an error converted from a `RemoteError` (e.g. `QuotaExceeded`),
+/// that error is returned; any other io error is returned as a `ProtocolError` caused by it
+pub fn copy_stream<R, W>(reader: &mut R, writer: &mut W, opts: &TransferOpts) -> RemoteResult<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut buffer = vec![0u8; opts.buffer_size.max(1)];
+    let started = Instant::now();
+    let mut copied = 0u64;
+    loop {
+        if opts.is_cancelled() {
+            debug!("Copy cancelled after {} bytes", copied);
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::Cancelled,
+                format!("cancelled after {copied} bytes"),
+            ));
+        }
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(protocol_error(err)),
+        };
+        writer.write_all(&buffer[..read]).map_err(protocol_error)?;
+        copied += read as u64;
+        if let Some(progress) = opts.progress.as_ref() {
+            progress(copied);
+        }
+        if let Some(rate) = opts.max_rate.filter(|x| *x > 0) {
+            let expected = Duration::from_secs_f64(copied as f64 / rate as f64);
+            if let Some(wait) = expected.checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+    }
+    writer.flush().map_err(protocol_error)?;
+    Ok(copied)
+}
+
+fn protocol_error(err: io::Error) -> RemoteError {
+    RemoteError::from_io(RemoteErrorType::ProtocolError, err)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_copy_stream() {
+        let progress = Arc::new(AtomicU64::new(0));
+        let calls = progress.clone();
+        let opts = TransferOpts::default().buffer_size(4).progress(move |_| {
+            calls.fetch_add(1, Ordering::Relaxed);
+        });
+        let mut writer = Vec::new();
+        assert_eq!(
+            copy_stream(&mut Cursor::new(b"hello world"), &mut writer, &opts).unwrap(),
+            11
+        );
+        assert_eq!(writer, b"hello world");
+        assert_eq!(progress.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn should_cancel_copy() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = cancel.clone();
+        let opts = TransferOpts::default()
+            .buffer_size(4)
+            .cancel(cancel)
+            .progress(move |copied| flag.store(copied >= 8, Ordering::Relaxed));
+        let mut writer = Vec::new();
+        let err = copy_stream(&mut Cursor::new(b"hello world"), &mut writer, &opts).unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::Cancelled);
+        assert_eq!(writer, b"hello wo");
+    }
+
+    #[test]
+    fn should_keep_remote_error_on_copy() {
+        struct Full;
+
+        impl Write for Full {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(RemoteError::new(RemoteErrorType::QuotaExceeded).into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let opts = TransferOpts::default();
+        let err = copy_stream(&mut Cursor::new(b"hello"), &mut Full, &opts).unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::QuotaExceeded);
+        let err =
+            copy_stream(&mut Cursor::new(b"hello"), &mut [0u8; 2].as_mut(), &opts).unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::ProtocolError);
+        assert!(std::error::Error::source(&err)
+            .unwrap()
+            .downcast_ref::<io::Error>()
+            .is_some());
+    }
+
+    #[test]
+    fn should_throttle_copy() {
+        let opts = TransferOpts::default().buffer_size(50).max_rate(1000);
+        let started = Instant::now();
+        copy_stream(&mut Cursor::new(vec![0u8; 100]), &mut io::sink(), &opts).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(90));
+    }
+}
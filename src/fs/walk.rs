@@ -0,0 +1,357 @@
+//! ## Walk
+//!
+//! recursive directory traversal shared by the default implementations of `RemoteFs`
+
+use std::path::{Path, PathBuf};
+
+use super::{
+    copy_stream, BulkReport, CopyOpts, ErrorPolicy, File, FsEvent, ListDirOpts, Metadata,
+    PreserveMode, TransferOpts, UnixPex, WalkDirOpts,
+};
+use crate::utils::path::{absolutize, normalize};
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Maximum amount of symlinks to traverse while resolving a symlink, as `MAXSYMLINKS` on Linux
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// Walk `dir` recursively, returning all the entries found, each directory followed by its content.
+pub fn walk<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    dir: &Path,
+    opts: &WalkDirOpts,
+) -> RemoteResult<Vec<File>> {
+    let mut ancestors = vec![normalize(dir)];
+    let mut entries = Vec::new();
+    walk_into(fs, dir, dir, opts, 1, &mut ancestors, &mut entries)?;
+    Ok(entries)
+}
+
+/// Create `dir` and all its missing parents
+pub fn create_dir_all<T: RemoteFs + ?Sized>(fs: &mut T, dir: &Path) -> RemoteResult<()> {
+    let mut missing: Vec<&Path> = dir
+        .ancestors()
+        .filter(|x| !x.as_os_str().is_empty())
+        .collect();
+    missing.reverse();
+    for dir in missing {
+        if !fs.exists(dir)? {
+            fs.create_dir(dir, UnixPex::from(0o755))?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove directory `dir` and all its content, calling `on_event` each time a directory is entered
+/// or an entry is removed. Symlinks are removed and never followed.
+/// Failures are handled according to `policy` and collected into `report`
+pub fn remove_tree<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    dir: &Path,
+    policy: ErrorPolicy,
+    report: &mut BulkReport,
+    on_event: &mut dyn FnMut(FsEvent),
+) -> RemoteResult<()> {
+    on_event(FsEvent::Entered(dir.to_path_buf()));
+    let entries = match fs.list_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => return handle_failure(dir, err, policy, report),
+    };
+    for entry in entries {
+        if entry.is_dir() {
+            remove_tree(fs, entry.path(), policy, report, on_event)?;
+        } else if let Err(err) = fs.remove_file(entry.path()) {
+            handle_failure(entry.path(), err, policy, report)?;
+        } else {
+            on_event(FsEvent::Removed(entry.path));
+            report.succeeded += 1;
+        }
+    }
+    trace!("Removed all files in {}; removing directory", dir.display());
+    match fs.remove_dir(dir) {
+        Ok(()) => {
+            on_event(FsEvent::Removed(dir.to_path_buf()));
+            report.succeeded += 1;
+            Ok(())
+        }
+        Err(err) => handle_failure(dir, err, policy, report),
+    }
+}
+
+/// Copy `src` to `dest` through the client streams, recursively if `src` is a directory.
+/// Symlinks are recreated pointing to the same target, unless `opts.dereference` is set.
+/// `on_event` is called each time a file is copied.
+/// Returns the amount of bytes copied
+pub fn copy_tree<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    src: &Path,
+    dest: &Path,
+    opts: &CopyOpts,
+    on_event: &mut dyn FnMut(FsEvent),
+) -> RemoteResult<u64> {
+    let mut ancestors = Vec::new();
+    copy_into(fs, src, dest, opts, &mut ancestors, on_event)
+}
+
+/// Copy `src` to `dest`. `ancestors` contains the real paths of the directories being copied,
+/// which are used to detect symlink cycles when dereferencing
+fn copy_into<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    src: &Path,
+    dest: &Path,
+    opts: &CopyOpts,
+    ancestors: &mut Vec<PathBuf>,
+    on_event: &mut dyn FnMut(FsEvent),
+) -> RemoteResult<u64> {
+    let mut file = fs.stat(src)?;
+    if opts.dereference && file.is_symlink() {
+        file = dereference(fs, &file)?;
+    }
+    if file.is_dir() {
+        let real = normalize(file.path());
+        if ancestors.contains(&real) {
+            error!("{} is being copied into itself", src.display());
+            return Err(symlink_loop(&file));
+        }
+        let mode = match opts.preserve {
+            PreserveMode::None => None,
+            PreserveMode::Mode | PreserveMode::All => file.metadata().mode,
+        };
+        fs.create_dir(dest, mode.unwrap_or_else(|| 0o755.into()))?;
+        ancestors.push(real);
+        let mut bytes = 0;
+        for entry in fs.list_dir(file.path())? {
+            match copy_into(
+                fs,
+                entry.path(),
+                dest.join(entry.name()).as_path(),
+                opts,
+                ancestors,
+                on_event,
+            ) {
+                Ok(n) => bytes += n,
+                Err(err) => {
+                    ancestors.pop();
+                    return Err(err);
+                }
+            }
+        }
+        ancestors.pop();
+        preserve(fs, dest, file.metadata(), opts.preserve)?;
+        Ok(bytes)
+    } else if let Some(target) = file.metadata().symlink.as_deref() {
+        fs.symlink(dest, target)?;
+        Ok(0)
+    } else {
+        let metadata = match opts.preserve {
+            PreserveMode::None => Metadata::default().size(file.metadata().size),
+            PreserveMode::Mode | PreserveMode::All => file.metadata().clone(),
+        };
+        let bytes = copy_file(fs, file.path(), dest, &metadata)?;
+        preserve(fs, dest, file.metadata(), opts.preserve)?;
+        on_event(FsEvent::Copied {
+            src: src.to_path_buf(),
+            dest: dest.to_path_buf(),
+            bytes,
+        });
+        Ok(bytes)
+    }
+}
+
+/// Set the metadata of `src` to preserve on `dest`.
+/// If the client doesn't support `setstat` a warning is logged and the metadata is not preserved
+fn preserve<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    dest: &Path,
+    src: &Metadata,
+    mode: PreserveMode,
+) -> RemoteResult<()> {
+    let metadata = match mode {
+        PreserveMode::None => return Ok(()),
+        PreserveMode::Mode => fs.stat(dest)?.metadata,
+        PreserveMode::All => Metadata {
+            accessed: src.accessed,
+            gid: src.gid,
+            modified: src.modified,
+            uid: src.uid,
+            ..fs.stat(dest)?.metadata
+        },
+    };
+    let metadata = Metadata {
+        mode: src.mode.or(metadata.mode),
+        ..metadata
+    };
+    match fs.setstat(dest, metadata) {
+        Err(err) if err.kind == RemoteErrorType::UnsupportedFeature => {
+            warn!("Could not preserve metadata of {}: {}", dest.display(), err);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Follow the symlink `link` until a file which is not a symlink is found
+fn dereference<T: RemoteFs + ?Sized>(fs: &mut T, link: &File) -> RemoteResult<File> {
+    let mut current = link.clone();
+    for _ in 0..MAX_SYMLINK_HOPS {
+        let target = match current.metadata().symlink.as_deref() {
+            Some(target) => {
+                let parent = current.path().parent().unwrap_or_else(|| Path::new("/"));
+                normalize(absolutize(parent, target).as_path())
+            }
+            None => return Ok(current),
+        };
+        current = fs.stat(target.as_path())?;
+        current.path = target;
+    }
+    error!(
+        "Could not resolve symlink {} within {} hops",
+        link.path().display(),
+        MAX_SYMLINK_HOPS
+    );
+    Err(symlink_loop(link))
+}
+
+/// Copy file `src` to `dest` through the client streams
+fn copy_file<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    src: &Path,
+    dest: &Path,
+    metadata: &Metadata,
+) -> RemoteResult<u64> {
+    let mut reader = fs.open(src)?;
+    let mut writer = fs.create(dest, metadata)?;
+    let bytes = copy_stream(&mut reader, &mut writer, &TransferOpts::default())?;
+    fs.on_read(reader)?;
+    fs.on_written(writer)?;
+    Ok(bytes)
+}
+
+/// Handle a failure on `path` according to `policy`: either return the error or push it into `report`
+fn handle_failure(
+    path: &Path,
+    err: RemoteError,
+    policy: ErrorPolicy,
+    report: &mut BulkReport,
+) -> RemoteResult<()> {
+    match policy {
+        ErrorPolicy::Abort => Err(err),
+        ErrorPolicy::Continue => {
+            warn!("Operation on {} failed: {}", path.display(), err);
+            report.failures.push((path.to_path_buf(), err));
+            Ok(())
+        }
+    }
+}
+
+/// Walk `dir`, pushing its entries to `entries`.
+/// `ancestors` contains the resolved paths of the directories walked to reach `dir` (`dir` included),
+/// which are used to detect symlink cycles.
+/// The directory listed is the resolved path of `dir`, while entries are reported under `dir`.
+/// Ignore rules are matched against the path of the entries relative to `root`
+#[cfg_attr(not(feature = "find"), allow(clippy::only_used_in_recursion))]
+fn walk_into<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    root: &Path,
+    dir: &Path,
+    opts: &WalkDirOpts,
+    depth: usize,
+    ancestors: &mut Vec<PathBuf>,
+    entries: &mut Vec<File>,
+) -> RemoteResult<()> {
+    if opts.max_depth.map(|max| depth > max).unwrap_or(false) {
+        return Ok(());
+    }
+    let list_opts = ListDirOpts::default().skip_hidden(opts.skip_hidden);
+    let real_dir = ancestors
+        .last()
+        .cloned()
+        .unwrap_or_else(|| dir.to_path_buf());
+    for mut entry in fs.list_dir_opts(real_dir.as_path(), &list_opts)? {
+        let real = match (entry.is_dir(), entry.is_symlink()) {
+            (true, _) => Some(real_path(ancestors, &entry)),
+            (false, true) if opts.follow_symlinks => resolve_symlink(fs, ancestors, &entry)?,
+            _ => None,
+        };
+        let path = dir.join(entry.name());
+        #[cfg(feature = "find")]
+        if let Some(rules) = opts.ignore.as_ref() {
+            let relative = path.strip_prefix(root).unwrap_or(path.as_path());
+            if rules.is_ignored(relative, entry.is_dir() || real.is_some()) {
+                trace!("Ignoring {}", path.display());
+                continue;
+            }
+        }
+        entry.path = path.clone();
+        entries.push(entry);
+        if let Some(real) = real {
+            ancestors.push(real);
+            let result = walk_into(
+                fs,
+                root,
+                path.as_path(),
+                opts,
+                depth + 1,
+                ancestors,
+                entries,
+            );
+            ancestors.pop();
+            result?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the real path of a directory entry, given the real path of its parent
+fn real_path(ancestors: &[PathBuf], entry: &File) -> PathBuf {
+    let parent = ancestors.last().cloned().unwrap_or_default();
+    parent.join(entry.name())
+}
+
+/// Resolve the target of a symlink entry, returning its real path if it points to a directory.
+/// Fails with `SymlinkLoop` if the target is the directory being walked or one of its ancestors,
+/// or if it can't be resolved within `MAX_SYMLINK_HOPS`
+fn resolve_symlink<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    ancestors: &[PathBuf],
+    entry: &File,
+) -> RemoteResult<Option<PathBuf>> {
+    let mut parent = ancestors.last().cloned().unwrap_or_default();
+    let mut link = entry.clone();
+    for _ in 0..MAX_SYMLINK_HOPS {
+        let target = match link.metadata().symlink.as_deref() {
+            Some(target) => normalize(absolutize(parent.as_path(), target).as_path()),
+            None => return Ok(None),
+        };
+        if ancestors.iter().any(|x| x.starts_with(target.as_path())) {
+            error!(
+                "Symlink {} points to {}, which is being walked",
+                entry.path().display(),
+                target.display()
+            );
+            return Err(symlink_loop(entry));
+        }
+        match fs.stat(target.as_path()) {
+            Ok(file) if file.is_dir() => return Ok(Some(target)),
+            Ok(file) if file.is_symlink() => {
+                parent = target.parent().map(Path::to_path_buf).unwrap_or_default();
+                link = file;
+            }
+            Ok(_) => return Ok(None),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => {
+                debug!("Symlink {} is broken", entry.path().display());
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    error!(
+        "Could not resolve symlink {} within {} hops",
+        entry.path().display(),
+        MAX_SYMLINK_HOPS
+    );
+    Err(symlink_loop(entry))
+}
+
+fn symlink_loop(entry: &File) -> RemoteError {
+    RemoteError::new_ex(RemoteErrorType::SymlinkLoop, entry.path().display())
+}
@@ -2,20 +2,65 @@
 //!
 //! welcome data type
 
+use std::collections::BTreeMap;
+
+use super::Capabilities;
+
 /// Structure holding all data related to a successful connection and authentication
 /// on remote host.
 #[derive(Debug, Default)]
 pub struct Welcome {
+    /// Negotiated algorithms, by kind (e.g. `kex`, `cipher`, `mac` on SSH)
+    pub algorithms: BTreeMap<String, String>,
+    /// Authentication method which succeeded (e.g. `publickey`)
+    pub auth_method: Option<String>,
     /// Welcome message / banner
     pub banner: Option<String>,
+    /// Features supported by the server, if negotiated on connect (e.g. with FTP `FEAT`)
+    pub capabilities: Option<Capabilities>,
+    /// Whether the transport is compressed (e.g. SSH compression)
+    pub compression: bool,
+    /// Fingerprint of the server key (e.g. `SHA256:...` of the SSH host key)
+    pub fingerprint: Option<String>,
 }
 
 impl Welcome {
+    /// Set the algorithm negotiated for `kind`
+    pub fn algorithm<K: ToString, V: ToString>(mut self, kind: K, algorithm: V) -> Self {
+        self.algorithms
+            .insert(kind.to_string(), algorithm.to_string());
+        self
+    }
+
+    /// Set authentication method which succeeded
+    pub fn auth_method<S: ToString>(mut self, method: S) -> Self {
+        self.auth_method = Some(method.to_string());
+        self
+    }
+
     /// Set welcome message or banner
     pub fn banner(mut self, banner: Option<String>) -> Self {
         self.banner = banner;
         self
     }
+
+    /// Set capabilities negotiated on connect
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// Set whether the transport is compressed
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set fingerprint of the server key
+    pub fn fingerprint<S: ToString>(mut self, fingerprint: S) -> Self {
+        self.fingerprint = Some(fingerprint.to_string());
+        self
+    }
 }
 
 #[cfg(test)]
@@ -27,8 +72,31 @@ mod test {
     #[test]
     fn should_create_welcome_type() {
         let welcome = Welcome::default();
+        assert!(welcome.algorithms.is_empty());
+        assert!(welcome.auth_method.is_none());
         assert!(welcome.banner.is_none());
+        assert!(welcome.capabilities.is_none());
+        assert_eq!(welcome.compression, false);
+        assert!(welcome.fingerprint.is_none());
         let welcome = Welcome::default().banner(Some("Hello, world!".to_string()));
         assert_eq!(welcome.banner.as_deref().unwrap(), "Hello, world!");
+        let welcome = Welcome::default().capabilities(Capabilities::default().utf8(true));
+        assert_eq!(welcome.capabilities.unwrap().utf8, true);
+        assert_eq!(Welcome::default().compression(true).compression, true);
+        let welcome = Welcome::default()
+            .algorithm("kex", "curve25519-sha256")
+            .algorithm("cipher", "aes256-gcm@openssh.com")
+            .auth_method("publickey")
+            .fingerprint("SHA256:nThbg6kXUpJWGl7E1IGOCspRomTxdCARLviKw6E5SY8");
+        assert_eq!(
+            welcome.algorithms.get("kex").map(String::as_str),
+            Some("curve25519-sha256")
+        );
+        assert_eq!(welcome.algorithms.len(), 2);
+        assert_eq!(welcome.auth_method.as_deref(), Some("publickey"));
+        assert_eq!(
+            welcome.fingerprint.as_deref(),
+            Some("SHA256:nThbg6kXUpJWGl7E1IGOCspRomTxdCARLviKw6E5SY8")
+        );
     }
 }
@@ -0,0 +1,280 @@
+//! ## Zip
+//!
+//! zip archive support through the `zip` crate, used by the default implementations of `zip_dir` and `unzip`.
+//! Files are deflated; archives are written and read through the client streams, without buffering them in memory
+
+use std::io::Read;
+use std::path::{Component, Path};
+use std::time::SystemTime;
+
+use ::zip::read::{read_zipfile_from_stream, ZipFile};
+use ::zip::result::ZipError;
+use ::zip::write::SimpleFileOptions;
+use ::zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::walk::create_dir_all;
+use super::{copy_stream, Metadata, TransferOpts, UnixPex, WalkDirOpts};
+use crate::utils::time::DateTime;
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Write the tree at `dir` on `fs` into a new zip archive at `archive`
+pub fn zip_tree<T: RemoteFs + ?Sized>(fs: &mut T, dir: &Path, archive: &Path) -> RemoteResult<()> {
+    let entries = fs.walk_dir(dir, &WalkDirOpts::default())?;
+    let mut writer = ZipWriter::new_stream(fs.create(archive, &Metadata::default())?);
+    for entry in entries {
+        let name = entry_name(entry.path().strip_prefix(dir).unwrap_or(entry.path()));
+        let metadata = entry.metadata();
+        let mut options = SimpleFileOptions::default().last_modified_time(dos_datetime(
+            metadata.modified.unwrap_or_else(SystemTime::now),
+        ));
+        if let Some(mode) = metadata.mode {
+            options = options.unix_permissions(u32::from(mode));
+        }
+        if entry.is_dir() {
+            writer
+                .add_directory(format!("{name}/"), options)
+                .map_err(zip_error)?;
+        } else if let Some(target) = metadata.symlink.as_ref() {
+            writer
+                .add_symlink(name.as_str(), entry_name(target), options)
+                .map_err(zip_error)?;
+        } else {
+            let options = options
+                .compression_method(CompressionMethod::Deflated)
+                .large_file(metadata.size >= u32::MAX as u64);
+            writer
+                .start_file(name.as_str(), options)
+                .map_err(zip_error)?;
+            let mut reader = fs.open(entry.path())?;
+            let result = copy_stream(&mut reader, &mut writer, &TransferOpts::default());
+            fs.on_read(reader)?;
+            result?;
+        }
+        trace!("Added {} to {}", name, archive.display());
+    }
+    let stream = writer.finish().map_err(zip_error)?.into_inner();
+    fs.on_written(stream)
+}
+
+/// Extract the zip archive at `archive` on `fs` into `dest`, which is created if it doesn't exist.
+///
+/// If the archive stream is seekable, entries are located through the central directory;
+/// otherwise they're read in order from the stream, and entries whose size is only known
+/// after their data (i.e. written with a data descriptor) fail with `UnsupportedFeature`.
+/// Entries whose name would escape `dest` are rejected with `BadFile`
+pub fn unzip_archive<T: RemoteFs + ?Sized>(
+    fs: &mut T,
+    archive: &Path,
+    dest: &Path,
+) -> RemoteResult<()> {
+    let mut stream = fs.open(archive)?;
+    create_dir_all(fs, dest)?;
+    if stream.seekable() {
+        let mut zip = ZipArchive::new(stream).map_err(zip_error)?;
+        let result = (0..zip.len()).try_for_each(|i| {
+            let mut entry = zip.by_index(i).map_err(zip_error)?;
+            extract(fs, dest, &mut entry)
+        });
+        fs.on_read(zip.into_inner())?;
+        result
+    } else {
+        let result = extract_stream(fs, &mut stream, dest);
+        fs.on_read(stream)?;
+        result
+    }
+}
+
+/// Extract the entries read in order from `reader` into `dest`
+fn extract_stream<T: RemoteFs + ?Sized, R: Read>(
+    fs: &mut T,
+    reader: &mut R,
+    dest: &Path,
+) -> RemoteResult<()> {
+    while let Some(mut entry) = read_zipfile_from_stream(reader).map_err(zip_error)? {
+        extract(fs, dest, &mut entry)?;
+    }
+    Ok(())
+}
+
+/// Extract `entry` into `dest`
+fn extract<T: RemoteFs + ?Sized, R: Read>(
+    fs: &mut T,
+    dest: &Path,
+    entry: &mut ZipFile<'_, R>,
+) -> RemoteResult<()> {
+    let name = entry.name().map_err(zip_error)?.into_owned();
+    let relative = Path::new(name.trim_end_matches('/'));
+    if relative
+        .components()
+        .any(|x| !matches!(x, Component::Normal(_) | Component::CurDir))
+    {
+        error!("Zip entry {} escapes the destination", name);
+        return Err(bad_archive(format!("invalid entry name {name}")));
+    }
+    let path = dest.join(relative);
+    if entry.is_dir() {
+        return create_dir_all(fs, path.as_path());
+    }
+    if let Some(parent) = path.parent() {
+        create_dir_all(fs, parent)?;
+    }
+    trace!("Extracting {}", path.display());
+    if entry.is_symlink() {
+        let mut target = String::new();
+        entry
+            .read_to_string(&mut target)
+            .map_err(|e| RemoteError::from_io(RemoteErrorType::IoError, e))?;
+        return fs.symlink(path.as_path(), Path::new(&target));
+    }
+    let mut metadata = Metadata::default().size(entry.size());
+    if let Some(mode) = entry.unix_mode().map(|x| x & 0o7777).filter(|x| *x != 0) {
+        metadata = metadata.mode(UnixPex::from(mode));
+    }
+    let mut stream = fs.create(path.as_path(), &metadata)?;
+    let sz = copy_stream(entry, &mut stream, &TransferOpts::default())?;
+    fs.on_written(stream)
+        .map_err(|e| RemoteError::finalize_failed(sz, e))
+}
+
+/// Get the name of the entry at `path`, with `/` as separator
+fn entry_name(path: &Path) -> String {
+    path.iter()
+        .map(|x| x.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn bad_archive(msg: String) -> RemoteError {
+    RemoteError::new_ex(RemoteErrorType::BadFile, msg)
+}
+
+/// Convert a `ZipError` into a `RemoteError`, keeping io errors raised by the streams
+fn zip_error(err: ZipError) -> RemoteError {
+    match err {
+        ZipError::Io(err) => RemoteError::from_io(RemoteErrorType::IoError, err),
+        ZipError::UnsupportedArchive(_) | ZipError::CompressionMethodNotSupported(_) => {
+            RemoteError::from_source(RemoteErrorType::UnsupportedFeature, err)
+        }
+        err => RemoteError::from_source(RemoteErrorType::BadFile, err),
+    }
+}
+
+/// Convert `time` to a zip date time; times before 1980 are clamped
+fn dos_datetime(time: SystemTime) -> ::zip::DateTime {
+    let t = DateTime::from(time);
+    if t.year < 1980 {
+        return ::zip::DateTime::default();
+    }
+    ::zip::DateTime::from_date_and_time(
+        t.year.min(2107) as u16,
+        t.month as u8,
+        t.day as u8,
+        t.hour as u8,
+        t.minute as u8,
+        t.second as u8,
+    )
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::{Cursor, Write};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MemoryFs;
+
+    #[test]
+    fn should_zip_and_unzip_tree() {
+        let mut fs = MemoryFs::connected()
+            .with_dir("/site")
+            .with_file("/site/index.html", b"<html></html>")
+            .with_dir("/site/css")
+            .with_file("/site/css/main.css", b"body {}")
+            .with_symlink("/site/home.html", "index.html");
+        zip_tree(&mut fs, Path::new("/site"), Path::new("/site.zip")).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(fs.content("/site.zip").unwrap())).unwrap();
+        assert_eq!(archive.len(), 4);
+        assert!(archive.by_name("css/").unwrap().is_dir());
+        assert_eq!(
+            archive.by_name("css/main.css").unwrap().compression(),
+            CompressionMethod::Deflated
+        );
+        unzip_archive(&mut fs, Path::new("/site.zip"), Path::new("/deploy")).unwrap();
+        assert_eq!(fs.content("/deploy/index.html").unwrap(), b"<html></html>");
+        assert_eq!(fs.content("/deploy/css/main.css").unwrap(), b"body {}");
+        assert_eq!(
+            fs.stat(Path::new("/deploy/home.html"))
+                .unwrap()
+                .metadata()
+                .symlink
+                .as_deref(),
+            Some(Path::new("index.html"))
+        );
+    }
+
+    #[test]
+    fn should_unzip_from_stream() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "docs/a.txt",
+                SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+            )
+            .unwrap();
+        writer.write_all(b"hello").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        let mut fs = MemoryFs::connected().with_dir("/out");
+        extract_stream(&mut fs, &mut archive.as_slice(), Path::new("/out")).unwrap();
+        assert_eq!(fs.content("/out/docs/a.txt").unwrap(), b"hello");
+        // sizes of streamed entries are only known after their data
+        let mut writer = ZipWriter::new_stream(Vec::new());
+        writer
+            .start_file("a.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"hello").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        assert_eq!(
+            extract_stream(&mut fs, &mut archive.as_slice(), Path::new("/out"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+
+    #[test]
+    fn should_reject_bad_archives() {
+        let mut fs = MemoryFs::connected().with_file("/a.zip", b"not a zip");
+        assert_eq!(
+            unzip_archive(&mut fs, Path::new("/a.zip"), Path::new("/out"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::BadFile
+        );
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("../evil", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"evil").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        let mut fs = fs.with_file("/evil.zip", &archive);
+        assert_eq!(
+            unzip_archive(&mut fs, Path::new("/evil.zip"), Path::new("/out"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::BadFile
+        );
+        assert!(fs.content("/evil").is_none());
+    }
+
+    #[test]
+    fn should_convert_dos_datetime() {
+        assert_eq!(dos_datetime(UNIX_EPOCH), ::zip::DateTime::default());
+        let time = dos_datetime(UNIX_EPOCH + Duration::from_secs(1700000000));
+        assert_eq!((time.year(), time.month(), time.day()), (2023, 11, 14));
+        assert_eq!((time.hour(), time.minute(), time.second()), (22, 13, 20));
+    }
+}
@@ -27,6 +27,10 @@
 //! these features are supported:
 //!
 //! - `no-log`: disable logging. By default, this library will log via the `log` crate.
+//! - `serde`: save and load connection profiles (`profiles::Registry`) as TOML.
+//! - `test-util`: enable the `test_util` module, with utilities to test applications built on top of `RemoteFs`.
+//! - `tracing`: make the calls on `wrapper::TracedFs` within `tracing` spans.
+//! - `zip`: let `RemoteFs::zip_dir` and `RemoteFs::unzip` pack and extract archives through the client streams when `zip` and `unzip` can't be run on the server.
 
 #![doc(html_playground_url = "https://play.rust-lang.org")]
 #![doc(
@@ -43,10 +47,19 @@ extern crate log;
 // -- export
 pub use fs::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
 // -- modules
+pub mod cas;
 pub mod fs;
+pub mod profiles;
+pub mod sync;
+pub mod wrapper;
+
+// -- test util
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 // -- utils
 pub(crate) mod utils;
+pub use utils::{shell, url};
 // -- mock
 #[cfg(test)]
 pub(crate) mod mock;
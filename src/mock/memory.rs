@@ -0,0 +1,475 @@
+//! ## Memory
+//!
+//! An in-memory remote file system, used to test the default implementations of `RemoteFs`
+
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::fs::stream::ReadAndSeek;
+use crate::fs::{Capabilities, FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use crate::utils::path::absolutize;
+use crate::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// A node of the in-memory tree
+#[derive(Debug, Clone)]
+struct Node {
+    metadata: Metadata,
+    content: Vec<u8>,
+}
+
+type Tree = Arc<Mutex<HashMap<PathBuf, Node>>>;
+
+/// In-memory file system
+pub struct MemoryFs {
+    connected: bool,
+    exec_replies: HashMap<String, (u32, String)>,
+    failing: HashSet<PathBuf>,
+    unsupported: HashSet<&'static str>,
+    tree: Tree,
+    umask: u32,
+    wrkdir: PathBuf,
+}
+
+impl Default for MemoryFs {
+    fn default() -> Self {
+        let mut tree = HashMap::new();
+        tree.insert(
+            PathBuf::from("/"),
+            Node {
+                metadata: Metadata::default().file_type(FileType::Directory),
+                content: vec![],
+            },
+        );
+        Self {
+            connected: false,
+            exec_replies: HashMap::new(),
+            failing: HashSet::new(),
+            unsupported: HashSet::new(),
+            tree: Arc::new(Mutex::new(tree)),
+            umask: 0,
+            wrkdir: PathBuf::from("/"),
+        }
+    }
+}
+
+impl MemoryFs {
+    /// Instantiates a new connected `MemoryFs`
+    pub fn connected() -> Self {
+        Self {
+            connected: true,
+            ..Default::default()
+        }
+    }
+
+    /// Insert a directory into the tree
+    pub fn with_dir<P: AsRef<Path>>(self, path: P) -> Self {
+        self.insert(
+            path.as_ref(),
+            Metadata::default().file_type(FileType::Directory),
+            vec![],
+        );
+        self
+    }
+
+    /// Insert a file into the tree
+    pub fn with_file<P: AsRef<Path>>(self, path: P, content: &[u8]) -> Self {
+        self.insert(
+            path.as_ref(),
+            Metadata::default().size(content.len() as u64),
+            content.to_vec(),
+        );
+        self
+    }
+
+    /// Insert a symlink into the tree
+    pub fn with_symlink<P: AsRef<Path>, T: AsRef<Path>>(self, path: P, target: T) -> Self {
+        self.insert(
+            path.as_ref(),
+            Metadata::default()
+                .file_type(FileType::Symlink)
+                .symlink(target),
+            vec![],
+        );
+        self
+    }
+
+    /// Make `exec` reply to `cmd` with `exit_code` and `output`; other commands are unsupported
+    pub fn with_exec<S: ToString>(mut self, cmd: &str, exit_code: u32, output: S) -> Self {
+        self.exec_replies
+            .insert(cmd.to_string(), (exit_code, output.to_string()));
+        self
+    }
+
+    /// Apply `umask` to the mode of created files and directories
+    pub fn with_umask(mut self, umask: u32) -> Self {
+        self.umask = umask;
+        self
+    }
+
+    /// Make operation `op` (e.g. `"mov"`) fail with `UnsupportedFeature`
+    pub fn without(mut self, op: &'static str) -> Self {
+        self.unsupported.insert(op);
+        self
+    }
+
+    /// Make any write operation on `path` fail with `PexError`
+    pub fn fail_on<P: AsRef<Path>>(&mut self, path: P) {
+        self.failing.insert(path.as_ref().to_path_buf());
+    }
+
+    /// Get another connected client of the same tree, e.g. to simulate concurrent changes
+    pub fn share(&self) -> Self {
+        Self {
+            connected: true,
+            tree: self.tree.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Get the content of the file at `path`
+    pub fn content<P: AsRef<Path>>(&self, path: P) -> Option<Vec<u8>> {
+        self.tree
+            .lock()
+            .unwrap()
+            .get(path.as_ref())
+            .map(|x| x.content.clone())
+    }
+
+    fn insert(&self, path: &Path, metadata: Metadata, content: Vec<u8>) {
+        self.tree
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), Node { metadata, content });
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        absolutize(self.wrkdir.as_path(), path)
+    }
+
+    fn check_connection(&self) -> RemoteResult<()> {
+        if self.connected {
+            Ok(())
+        } else {
+            Err(RemoteError::new(RemoteErrorType::NotConnected))
+        }
+    }
+
+    fn mask(&self, mode: UnixPex) -> UnixPex {
+        UnixPex::from(u32::from(mode) & !self.umask)
+    }
+
+    fn check_supported(&self, op: &'static str) -> RemoteResult<()> {
+        if self.unsupported.contains(op) {
+            Err(RemoteError::new(RemoteErrorType::UnsupportedFeature))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_writable(&self, path: &Path) -> RemoteResult<()> {
+        if self.failing.contains(path) {
+            Err(RemoteError::new(RemoteErrorType::PexError))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn node(&self, path: &Path) -> RemoteResult<Node> {
+        self.tree
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))
+    }
+
+    fn check_parent(&self, path: &Path) -> RemoteResult<()> {
+        match path.parent() {
+            Some(parent) if !self.node(parent)?.metadata.is_dir() => {
+                Err(RemoteError::new(RemoteErrorType::BadFile))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn children(&self, path: &Path) -> Vec<PathBuf> {
+        self.tree
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|x| x.parent() == Some(path))
+            .cloned()
+            .collect()
+    }
+
+    fn open_write(&self, path: &Path, metadata: &Metadata, truncate: bool) -> RemoteResult<()> {
+        self.check_connection()?;
+        self.check_writable(path)?;
+        self.check_parent(path)?;
+        let mut tree = self.tree.lock().unwrap();
+        let mut metadata = metadata.clone().file_type(FileType::File);
+        metadata.mode = metadata.mode.map(|x| self.mask(x));
+        let node = tree.entry(path.to_path_buf()).or_insert_with(|| Node {
+            metadata,
+            content: vec![],
+        });
+        if node.metadata.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        if truncate {
+            node.content.clear();
+            node.metadata.size = 0;
+        }
+        Ok(())
+    }
+}
+
+/// Writer which writes straight into the tree node
+struct MemoryWriter {
+    path: PathBuf,
+    tree: Tree,
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut tree = self.tree.lock().unwrap();
+        let node = tree
+            .get_mut(&self.path)
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+        node.content.extend_from_slice(buf);
+        node.metadata.size = node.content.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl RemoteFs for MemoryFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        if self.connected {
+            return Err(RemoteError::new(RemoteErrorType::AlreadyConnected));
+        }
+        self.connected = true;
+        self.wrkdir = PathBuf::from("/");
+        Ok(Welcome::default())
+    }
+
+    fn capabilities(&mut self) -> Capabilities {
+        Capabilities::default().exec(!self.exec_replies.is_empty())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.check_connection()?;
+        self.connected = false;
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.check_connection()?;
+        Ok(self.wrkdir.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.check_connection()?;
+        let dir = self.resolve(dir);
+        if !self.node(dir.as_path())?.metadata.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.wrkdir = dir.clone();
+        Ok(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        if !self.node(path.as_path())?.metadata.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        let mut entries = self
+            .children(path.as_path())
+            .into_iter()
+            .map(|x| self.stat(x.as_path()))
+            .collect::<RemoteResult<Vec<File>>>()?;
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        let node = self.node(path.as_path())?;
+        Ok(File {
+            path,
+            metadata: node.metadata,
+        })
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        self.check_writable(path.as_path())?;
+        let mut tree = self.tree.lock().unwrap();
+        let node = tree
+            .get_mut(&path)
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory))?;
+        node.metadata = Metadata {
+            file_type: node.metadata.file_type.clone(),
+            size: node.metadata.size,
+            symlink: node.metadata.symlink.clone(),
+            ..metadata
+        };
+        Ok(())
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        Ok(self.tree.lock().unwrap().contains_key(&path))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        self.check_writable(path.as_path())?;
+        if self.node(path.as_path())?.metadata.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        self.tree.lock().unwrap().remove(&path);
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        self.check_writable(path.as_path())?;
+        if !self.node(path.as_path())?.metadata.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        if !self.children(path.as_path()).is_empty() {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryNotEmpty));
+        }
+        self.tree.lock().unwrap().remove(&path);
+        Ok(())
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        if self.exists(path.as_path())? {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+        }
+        self.check_parent(path.as_path())?;
+        self.insert(
+            path.as_path(),
+            Metadata::default()
+                .file_type(FileType::Directory)
+                .mode(self.mask(mode)),
+            vec![],
+        );
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        self.check_parent(path.as_path())?;
+        self.insert(
+            path.as_path(),
+            Metadata::default()
+                .file_type(FileType::Symlink)
+                .symlink(target),
+            vec![],
+        );
+        Ok(())
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.check_connection()?;
+        self.check_supported("copy")?;
+        let src = self.resolve(src);
+        let dest = self.resolve(dest);
+        let node = self.node(src.as_path())?;
+        if node.metadata.is_dir() {
+            return Err(RemoteError::new(RemoteErrorType::UnsupportedFeature));
+        }
+        self.check_parent(dest.as_path())?;
+        self.tree.lock().unwrap().insert(dest, node);
+        Ok(())
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.check_connection()?;
+        self.check_supported("mov")?;
+        let src = self.resolve(src);
+        let dest = self.resolve(dest);
+        self.node(src.as_path())?;
+        self.check_writable(src.as_path())?;
+        self.check_parent(dest.as_path())?;
+        let mut tree = self.tree.lock().unwrap();
+        let moved: Vec<PathBuf> = tree
+            .keys()
+            .filter(|x| x.starts_with(src.as_path()))
+            .cloned()
+            .collect();
+        for path in moved {
+            let node = tree.remove(&path).unwrap();
+            let target = dest.join(path.strip_prefix(src.as_path()).unwrap());
+            tree.insert(target, node);
+        }
+        Ok(())
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.check_connection()?;
+        self.exec_replies
+            .get(cmd)
+            .cloned()
+            .ok_or_else(|| RemoteError::new(RemoteErrorType::UnsupportedFeature))
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.check_supported("append")?;
+        let path = self.resolve(path);
+        self.open_write(path.as_path(), metadata, false)?;
+        let writer: Box<dyn Write + Send> = Box::new(MemoryWriter {
+            path,
+            tree: self.tree.clone(),
+        });
+        Ok(WriteStream::from(writer))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let path = self.resolve(path);
+        self.open_write(path.as_path(), metadata, true)?;
+        let writer: Box<dyn Write + Send> = Box::new(MemoryWriter {
+            path,
+            tree: self.tree.clone(),
+        });
+        Ok(WriteStream::from(writer))
+    }
+
+    fn on_written(&mut self, _writable: WriteStream) -> RemoteResult<()> {
+        self.check_supported("on_written")
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.check_connection()?;
+        let path = self.resolve(path);
+        let node = self.node(path.as_path())?;
+        if !node.metadata.is_file() {
+            return Err(RemoteError::new(RemoteErrorType::BadFile));
+        }
+        let reader: Box<dyn ReadAndSeek> = Box::new(Cursor::new(node.content));
+        Ok(ReadStream::from(reader))
+    }
+}
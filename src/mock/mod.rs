@@ -4,6 +4,10 @@
 
 use crate::RemoteFs;
 
+mod memory;
+
+pub use memory::MemoryFs;
+
 // -- mock
 pub struct MockRemoteFs;
 
@@ -0,0 +1,7 @@
+//! ## Profiles
+//!
+//! named connection profiles, to instantiate clients by name
+
+mod registry;
+
+pub use self::registry::{Profile, Registry};
@@ -0,0 +1,275 @@
+//! ## Registry
+//!
+//! registry of named connection profiles
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// A function which builds a new client for a profile
+type Factory = Box<dyn Fn() -> RemoteResult<Box<dyn RemoteFs>> + Send + Sync>;
+
+/// A function which builds a new client for a protocol, configured with the options of a profile
+type ProtocolFactory =
+    Arc<dyn Fn(&BTreeMap<String, String>) -> RemoteResult<Box<dyn RemoteFs>> + Send + Sync>;
+
+/// A named connection profile: the protocol it uses, its options (e.g. `host` and `port`)
+/// and a factory building clients configured with them
+pub struct Profile {
+    protocol: String,
+    options: BTreeMap<String, String>,
+    factory: Factory,
+}
+
+/// The serialized form of a `Profile`
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct ProfileConfig {
+    protocol: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    options: BTreeMap<String, String>,
+}
+
+impl Profile {
+    /// Instantiates a new `Profile` for `protocol` (e.g. `sftp`), building clients with `factory`
+    pub fn new<S, F>(protocol: S, factory: F) -> Self
+    where
+        S: ToString,
+        F: Fn() -> RemoteResult<Box<dyn RemoteFs>> + Send + Sync + 'static,
+    {
+        Self {
+            protocol: protocol.to_string(),
+            options: BTreeMap::new(),
+            factory: Box::new(factory),
+        }
+    }
+
+    /// Set option `key` to `value`. Options are saved with the profile, but `factory` must apply them
+    pub fn option<K: ToString, V: ToString>(mut self, key: K, value: V) -> Self {
+        self.options.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Get the protocol used by the profile
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// Get the options of the profile
+    pub fn options(&self) -> &BTreeMap<String, String> {
+        &self.options
+    }
+
+    /// Build a new client for this profile, without connecting it
+    pub fn build(&self) -> RemoteResult<Box<dyn RemoteFs>> {
+        (self.factory)()
+    }
+}
+
+/// A registry of named connection profiles, so that applications can instantiate clients by name
+/// (e.g. `registry.connect("backup-server")`).
+///
+/// With the `serde` feature, profiles can be saved to and loaded from TOML, as a table per profile:
+///
+/// ```toml
+/// [backup-server]
+/// protocol = "sftp"
+///
+/// [backup-server.options]
+/// host = "backup.example.com"
+/// port = "22"
+/// ```
+///
+/// Loaded profiles build their clients with the factory registered for their protocol with `register_protocol`
+#[derive(Default)]
+pub struct Registry {
+    profiles: BTreeMap<String, Profile>,
+    protocols: BTreeMap<String, ProtocolFactory>,
+}
+
+impl Registry {
+    /// Register `factory` to build the clients of the profiles using `protocol` loaded from TOML,
+    /// replacing any factory registered for the same protocol
+    pub fn register_protocol<S, F>(&mut self, protocol: S, factory: F) -> &mut Self
+    where
+        S: ToString,
+        F: Fn(&BTreeMap<String, String>) -> RemoteResult<Box<dyn RemoteFs>> + Send + Sync + 'static,
+    {
+        self.protocols
+            .insert(protocol.to_string(), Arc::new(factory));
+        self
+    }
+
+    /// Register `profile` as `name`, replacing any profile with the same name
+    pub fn register<S: ToString>(&mut self, name: S, profile: Profile) -> &mut Self {
+        self.profiles.insert(name.to_string(), profile);
+        self
+    }
+
+    /// Remove the profile registered as `name`
+    pub fn unregister(&mut self, name: &str) -> Option<Profile> {
+        self.profiles.remove(name)
+    }
+
+    /// Get the profile registered as `name`
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Iterate over the names of the registered profiles, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Build a new client for the profile registered as `name`, without connecting it
+    pub fn build(&self, name: &str) -> RemoteResult<Box<dyn RemoteFs>> {
+        self.get(name)
+            .ok_or_else(|| {
+                RemoteError::new_ex(
+                    RemoteErrorType::BadAddress,
+                    format!("no such profile: {name}"),
+                )
+            })?
+            .build()
+    }
+
+    /// Build a new client for the profile registered as `name` and connect it
+    pub fn connect(&self, name: &str) -> RemoteResult<Box<dyn RemoteFs>> {
+        let mut client = self.build(name)?;
+        debug!("Connecting to profile {}", name);
+        client.connect()?;
+        Ok(client)
+    }
+
+    /// Serialize the registered profiles to TOML
+    #[cfg(feature = "serde")]
+    pub fn to_toml(&self) -> RemoteResult<String> {
+        let profiles: BTreeMap<&str, ProfileConfig> = self
+            .profiles
+            .iter()
+            .map(|(name, profile)| {
+                (
+                    name.as_str(),
+                    ProfileConfig {
+                        protocol: profile.protocol.clone(),
+                        options: profile.options.clone(),
+                    },
+                )
+            })
+            .collect();
+        toml::to_string(&profiles)
+            .map_err(|e| RemoteError::from_source(RemoteErrorType::BadFile, e))
+    }
+
+    /// Register the profiles serialized in `toml`, replacing the profiles with the same names.
+    /// Fails with `BadFile` if `toml` is not valid, or with `UnsupportedFeature` if no factory is registered
+    /// for the protocol of a profile; no profile is registered then
+    #[cfg(feature = "serde")]
+    pub fn load_toml(&mut self, toml: &str) -> RemoteResult<()> {
+        let configs: BTreeMap<String, ProfileConfig> = toml::from_str(toml)
+            .map_err(|e| RemoteError::from_source(RemoteErrorType::BadFile, e))?;
+        let mut profiles = Vec::with_capacity(configs.len());
+        for (name, config) in configs {
+            let factory = self
+                .protocols
+                .get(&config.protocol)
+                .cloned()
+                .ok_or_else(|| {
+                    RemoteError::new_ex(
+                        RemoteErrorType::UnsupportedFeature,
+                        format!(
+                            "no factory for protocol {} of profile {name}",
+                            config.protocol
+                        ),
+                    )
+                })?;
+            let options = config.options.clone();
+            let mut profile = Profile::new(config.protocol, move || factory(&options));
+            profile.options = config.options;
+            profiles.push((name, profile));
+        }
+        for (name, profile) in profiles {
+            debug!("Loaded profile {}", name);
+            self.register(name, profile);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MemoryFs;
+
+    #[test]
+    fn should_connect_to_registered_profile() {
+        let mut registry = Registry::default();
+        registry
+            .register(
+                "backup-server",
+                Profile::new("memory", || Ok(Box::new(MemoryFs::default()))),
+            )
+            .register(
+                "archive",
+                Profile::new("memory", || Ok(Box::new(MemoryFs::default()))),
+            );
+        assert_eq!(
+            registry.names().collect::<Vec<&str>>(),
+            vec!["archive", "backup-server"]
+        );
+        assert_eq!(registry.get("archive").unwrap().protocol(), "memory");
+        let mut client = registry.connect("backup-server").unwrap();
+        assert_eq!(client.is_connected(), true);
+        assert_eq!(registry.build("archive").unwrap().is_connected(), false);
+        assert!(registry.unregister("archive").is_some());
+        assert_eq!(
+            registry.connect("archive").err().unwrap().kind,
+            RemoteErrorType::BadAddress
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn should_save_and_load_profiles() {
+        let mut registry = Registry::default();
+        registry.register(
+            "backup-server",
+            Profile::new("memory", || Ok(Box::new(MemoryFs::default())))
+                .option("host", "backup.example.com")
+                .option("port", 22),
+        );
+        let toml = registry.to_toml().unwrap();
+        let mut loaded = Registry::default();
+        assert_eq!(
+            loaded.load_toml(&toml).unwrap_err().kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+        assert_eq!(loaded.names().count(), 0);
+        loaded.register_protocol("memory", |options| {
+            assert_eq!(options.get("port").map(String::as_str), Some("22"));
+            Ok(Box::new(MemoryFs::default()))
+        });
+        loaded.load_toml(&toml).unwrap();
+        let profile = loaded.get("backup-server").unwrap();
+        assert_eq!(profile.protocol(), "memory");
+        assert_eq!(
+            profile.options().get("host").map(String::as_str),
+            Some("backup.example.com")
+        );
+        assert_eq!(
+            loaded.connect("backup-server").unwrap().is_connected(),
+            true
+        );
+        assert_eq!(
+            loaded.load_toml("[broken").unwrap_err().kind,
+            RemoteErrorType::BadFile
+        );
+    }
+}
@@ -0,0 +1,568 @@
+//! ## Apply
+//!
+//! apply the actions planned by the sync engine
+
+use std::path::Path;
+
+use super::backup::Backup;
+use super::{plan, DeleteLimit, SyncAction, SyncOpts};
+use crate::fs::{copy_stream, BulkReport, ErrorPolicy, Metadata, Snapshot, TransferOpts, UnixPex};
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Synchronize the tree at `dest` on `dest_fs` with the tree at `source` on `source_fs`, as specified in `opts`.
+/// `dest` is created if it doesn't exist.
+/// Failures are handled according to `opts.error_policy` and collected into the returned `BulkReport`
+pub fn sync<S, D>(
+    source_fs: &mut S,
+    source: &Path,
+    dest_fs: &mut D,
+    dest: &Path,
+    opts: &SyncOpts,
+) -> RemoteResult<BulkReport>
+where
+    S: RemoteFs + ?Sized,
+    D: RemoteFs + ?Sized,
+{
+    let walk_opts = opts.walk_opts();
+    let source_snapshot = source_fs.snapshot(source, &walk_opts, opts.checksum)?;
+    let mut dest_snapshot = match dest_fs.snapshot(dest, &walk_opts, opts.checksum) {
+        Ok(snapshot) => snapshot,
+        Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => {
+            debug!("{} doesn't exist; creating it", dest.display());
+            dest_fs.create_dir(dest, UnixPex::from(0o755))?;
+            Snapshot::new(dest)
+        }
+        Err(err) => return Err(err),
+    };
+    if let Some(backup) = opts.backup.as_ref() {
+        let backup = Backup::new(backup, dest);
+        if let Ok(relative) = backup.root().strip_prefix(dest) {
+            dest_snapshot.retain(|path, _| !path.starts_with(relative));
+        }
+    }
+    let actions = plan(&source_snapshot, &dest_snapshot, opts);
+    if let Some(limit) = opts.delete_limit {
+        check_delete_limit(&actions, &dest_snapshot, limit)?;
+    }
+    debug!(
+        "Synchronizing {} with {}: {} actions",
+        dest.display(),
+        source.display(),
+        actions.len()
+    );
+    apply(source_fs, source, dest_fs, dest, &actions, opts)
+}
+
+/// Apply `actions` to the tree at `dest` on `dest_fs`, uploading files from the tree at `source` on `source_fs`.
+/// If `opts.backup` is set, the files overwritten or removed are moved into the backup directory instead,
+/// and expired backups are removed at the end.
+/// Failures are handled according to `opts.error_policy` and collected into the returned `BulkReport`
+pub fn apply<S, D>(
+    source_fs: &mut S,
+    source: &Path,
+    dest_fs: &mut D,
+    dest: &Path,
+    actions: &[SyncAction],
+    opts: &SyncOpts,
+) -> RemoteResult<BulkReport>
+where
+    S: RemoteFs + ?Sized,
+    D: RemoteFs + ?Sized,
+{
+    let mut backup = opts.backup.as_ref().map(|x| Backup::new(x, dest));
+    let mut backed_up = false;
+    let mut report = BulkReport::default();
+    for action in actions.iter() {
+        trace!("Applying {:?}", action);
+        let (path, result) = match action {
+            SyncAction::CreateDir(path) => (
+                path,
+                source_fs
+                    .stat(source.join(path).as_path())
+                    .map(|x| x.metadata.mode.unwrap_or_else(|| UnixPex::from(0o755)))
+                    .and_then(|mode| dest_fs.create_dir(dest.join(path).as_path(), mode)),
+            ),
+            SyncAction::Upload(path) => (
+                path,
+                is_same_content(source_fs, source, dest_fs, dest, path, opts).and_then(|same| {
+                    if same {
+                        debug!("{} is already up to date", path.display());
+                        return Ok(());
+                    }
+                    if let Some(backup) = backup.as_mut() {
+                        backed_up |= backup.save(dest_fs, dest, path)?;
+                    }
+                    upload(
+                        source_fs,
+                        source.join(path).as_path(),
+                        dest_fs,
+                        dest.join(path).as_path(),
+                    )
+                }),
+            ),
+            SyncAction::Move { from, to } => (
+                to,
+                dest_fs.mov(dest.join(from).as_path(), dest.join(to).as_path()),
+            ),
+            SyncAction::RemoveFile(path) | SyncAction::RemoveDir(path) if backup.is_some() => (
+                path,
+                backup
+                    .as_mut()
+                    .unwrap()
+                    .save(dest_fs, dest, path)
+                    .map(|x| backed_up |= x),
+            ),
+            SyncAction::RemoveFile(path) => (path, dest_fs.remove_file(dest.join(path).as_path())),
+            SyncAction::RemoveDir(path) => {
+                (path, dest_fs.remove_dir_all(dest.join(path).as_path()))
+            }
+        };
+        match result {
+            Ok(()) => report.succeeded += 1,
+            Err(err) if opts.error_policy == ErrorPolicy::Abort => return Err(err),
+            Err(err) => {
+                warn!("Sync of {} failed: {}", path.display(), err);
+                report.failures.push((path.clone(), err));
+            }
+        }
+    }
+    if let (Some(backup), true) = (backup, backed_up) {
+        backup.prune(dest_fs)?;
+    }
+    Ok(report)
+}
+
+/// Check the amount of entries on the destination removed by `actions`, including the content of the directories,
+/// failing with `QuotaExceeded` if it exceeds `limit`
+fn check_delete_limit(
+    actions: &[SyncAction],
+    dest: &Snapshot,
+    limit: DeleteLimit,
+) -> RemoteResult<()> {
+    let removed: Vec<&Path> = actions
+        .iter()
+        .filter_map(|x| match x {
+            SyncAction::RemoveFile(path) | SyncAction::RemoveDir(path) => Some(path.as_path()),
+            _ => None,
+        })
+        .collect();
+    let count = dest
+        .iter()
+        .filter(|(path, _)| removed.iter().any(|x| path.starts_with(x)))
+        .count();
+    if limit.is_exceeded(count, dest.len()) {
+        error!(
+            "Sync would remove {} of {} entries from {}; aborting",
+            count,
+            dest.len(),
+            dest.root().display()
+        );
+        return Err(RemoteError::new_ex(
+            RemoteErrorType::QuotaExceeded,
+            format!(
+                "sync would remove {} of {} entries from the destination",
+                count,
+                dest.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Returns whether `dedup` is set and file `path` has the same content on the source and on the destination
+fn is_same_content<S, D>(
+    source_fs: &mut S,
+    source: &Path,
+    dest_fs: &mut D,
+    dest: &Path,
+    path: &Path,
+    opts: &SyncOpts,
+) -> RemoteResult<bool>
+where
+    S: RemoteFs + ?Sized,
+    D: RemoteFs + ?Sized,
+{
+    let algorithm = match opts.dedup {
+        Some(algorithm) => algorithm,
+        None => return Ok(false),
+    };
+    let dest_checksum = match dest_fs.checksum(dest.join(path).as_path(), algorithm) {
+        Ok(checksum) => checksum,
+        Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    Ok(source_fs.checksum(source.join(path).as_path(), algorithm)? == dest_checksum)
+}
+
+/// Upload file `src` on `source_fs` to `dest` on `dest_fs` through the client streams,
+/// then set the modification time of `dest` to the one of `src`, so that it's not planned for upload again.
+/// The streams are finalized even if the upload fails
+fn upload<S, D>(source_fs: &mut S, src: &Path, dest_fs: &mut D, dest: &Path) -> RemoteResult<()>
+where
+    S: RemoteFs + ?Sized,
+    D: RemoteFs + ?Sized,
+{
+    let metadata = source_fs.stat(src)?.metadata;
+    let mut reader = source_fs.open(src)?;
+    let mut writer = match dest_fs.create(dest, &metadata) {
+        Ok(writer) => writer,
+        Err(err) => {
+            if let Err(err) = source_fs.on_read(reader) {
+                warn!(
+                    "Failed to finalize read stream of {}: {}",
+                    src.display(),
+                    err
+                );
+            }
+            return Err(err);
+        }
+    };
+    let copied = copy_stream(&mut reader, &mut writer, &TransferOpts::default());
+    let read = source_fs.on_read(reader);
+    let written = dest_fs.on_written(writer);
+    let bytes = copied?;
+    read?;
+    written?;
+    trace!("Uploaded {} bytes to {}", bytes, dest.display());
+    if metadata.modified.is_none() {
+        return Ok(());
+    }
+    let metadata = Metadata {
+        modified: metadata.modified,
+        ..dest_fs.stat(dest)?.metadata
+    };
+    match dest_fs.setstat(dest, metadata) {
+        Err(err) if err.kind == RemoteErrorType::UnsupportedFeature => {
+            warn!(
+                "Could not set modification time of {}: {}",
+                dest.display(),
+                err
+            );
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::ChecksumAlgorithm;
+    use crate::mock::MemoryFs;
+    use crate::sync::BackupOpts;
+
+    #[test]
+    fn should_sync_trees() {
+        let mut source = MemoryFs::connected()
+            .with_dir("/photos")
+            .with_dir("/photos/2024")
+            .with_file("/photos/2024/a.jpg", b"sunset")
+            .with_file("/photos/b.jpg", b"beach");
+        let mut dest = MemoryFs::connected()
+            .with_dir("/backup")
+            .with_file("/backup/a.jpg", b"sunset")
+            .with_file("/backup/c.jpg", b"stale");
+        let opts = SyncOpts::default()
+            .checksum(ChecksumAlgorithm::Crc32)
+            .delete(true)
+            .detect_renames(true);
+        let report = sync(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &opts,
+        )
+        .unwrap();
+        assert_eq!(report.succeeded, 4);
+        assert!(report.failures.is_empty());
+        assert_eq!(dest.content("/backup/2024/a.jpg").unwrap(), b"sunset");
+        assert_eq!(dest.content("/backup/b.jpg").unwrap(), b"beach");
+        assert_eq!(dest.exists(Path::new("/backup/a.jpg")).unwrap(), false);
+        assert_eq!(dest.exists(Path::new("/backup/c.jpg")).unwrap(), false);
+        // nothing to do
+        let report = sync(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &opts,
+        )
+        .unwrap();
+        assert_eq!(report.succeeded, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "find")]
+    fn should_ignore_paths() {
+        let mut source = MemoryFs::connected()
+            .with_dir("/src")
+            .with_dir("/src/target")
+            .with_file("/src/target/app", b"binary")
+            .with_file("/src/main.rs", b"fn main() {}")
+            .with_file("/src/debug.log", b"log");
+        let mut dest = MemoryFs::connected()
+            .with_dir("/backup")
+            .with_file("/backup/old.log", b"log");
+        let opts = SyncOpts::default()
+            .delete(true)
+            .ignore(crate::fs::IgnoreRules::parse("target/\n*.log"));
+        let report = sync(
+            &mut source,
+            Path::new("/src"),
+            &mut dest,
+            Path::new("/backup"),
+            &opts,
+        )
+        .unwrap();
+        assert_eq!(report.succeeded, 1);
+        assert_eq!(dest.content("/backup/main.rs").unwrap(), b"fn main() {}");
+        assert_eq!(dest.exists(Path::new("/backup/target")).unwrap(), false);
+        assert_eq!(dest.exists(Path::new("/backup/debug.log")).unwrap(), false);
+        assert_eq!(dest.exists(Path::new("/backup/old.log")).unwrap(), true);
+    }
+
+    #[test]
+    fn should_backup_overwritten_and_removed_files() {
+        let mut source = MemoryFs::connected()
+            .with_dir("/site")
+            .with_file("/site/index.html", b"v2.0");
+        let mut dest = MemoryFs::connected()
+            .with_dir("/www")
+            .with_file("/www/index.html", b"v1")
+            .with_file("/www/old.html", b"old")
+            .with_dir("/www/.backup")
+            .with_dir("/www/.backup/20000101-000000")
+            .with_dir("/www/.backup/20000102-000000");
+        let opts = SyncOpts::default()
+            .delete(true)
+            .backup(BackupOpts::new(".backup", 2));
+        let report = sync(
+            &mut source,
+            Path::new("/site"),
+            &mut dest,
+            Path::new("/www"),
+            &opts,
+        )
+        .unwrap();
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(dest.content("/www/index.html").unwrap(), b"v2.0");
+        assert_eq!(dest.exists(Path::new("/www/old.html")).unwrap(), false);
+        let backups = dest.list_dir(Path::new("/www/.backup")).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert!(backups
+            .iter()
+            .all(|x| x.path() != Path::new("/www/.backup/20000101-000000")));
+        let session = backups
+            .into_iter()
+            .find(|x| x.path() != Path::new("/www/.backup/20000102-000000"))
+            .unwrap();
+        assert_eq!(
+            dest.content(session.path().join("index.html")).unwrap(),
+            b"v1"
+        );
+        assert_eq!(
+            dest.content(session.path().join("old.html")).unwrap(),
+            b"old"
+        );
+    }
+
+    #[test]
+    fn should_skip_upload_of_same_content() {
+        let mut source = MemoryFs::connected()
+            .with_dir("/photos")
+            .with_file("/photos/a.jpg", b"sunset")
+            .with_file("/photos/b.jpg", b"beach");
+        let mut dest = MemoryFs::connected()
+            .with_dir("/backup")
+            .with_file("/backup/a.jpg", b"sunset")
+            .with_file("/backup/b.jpg", b"BEACH");
+        dest.setstat(
+            Path::new("/backup/a.jpg"),
+            crate::fs::Metadata::default().modified(std::time::UNIX_EPOCH),
+        )
+        .unwrap();
+        dest.setstat(
+            Path::new("/backup/b.jpg"),
+            crate::fs::Metadata::default().modified(std::time::UNIX_EPOCH),
+        )
+        .unwrap();
+        let opts = SyncOpts::default().dedup(ChecksumAlgorithm::Sha256);
+        let actions = plan(
+            &source
+                .snapshot(Path::new("/photos"), &Default::default(), None)
+                .unwrap(),
+            &dest
+                .snapshot(Path::new("/backup"), &Default::default(), None)
+                .unwrap(),
+            &opts,
+        );
+        assert_eq!(actions.len(), 2);
+        let report = apply(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &actions,
+            &opts,
+        )
+        .unwrap();
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(dest.content("/backup/b.jpg").unwrap(), b"beach");
+        assert_eq!(
+            dest.stat(Path::new("/backup/a.jpg"))
+                .unwrap()
+                .metadata
+                .modified,
+            Some(std::time::UNIX_EPOCH)
+        );
+    }
+
+    #[test]
+    fn should_create_dest_root() {
+        let mut source = MemoryFs::connected()
+            .with_dir("/photos")
+            .with_file("/photos/a.jpg", b"sunset");
+        let mut dest = MemoryFs::connected();
+        sync(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &SyncOpts::default(),
+        )
+        .unwrap();
+        assert_eq!(dest.content("/backup/a.jpg").unwrap(), b"sunset");
+    }
+
+    #[test]
+    fn should_keep_modification_time_of_uploaded_files() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut source = MemoryFs::connected()
+            .with_dir("/photos")
+            .with_file("/photos/a.jpg", b"sunset");
+        source
+            .setstat(
+                Path::new("/photos/a.jpg"),
+                Metadata::default().modified(modified),
+            )
+            .unwrap();
+        let mut dest = MemoryFs::connected()
+            .with_dir("/backup")
+            .with_file("/backup/a.jpg", b"old");
+        dest.setstat(
+            Path::new("/backup/a.jpg"),
+            Metadata::default().modified(UNIX_EPOCH),
+        )
+        .unwrap();
+        let opts = SyncOpts::default();
+        sync(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &opts,
+        )
+        .unwrap();
+        assert_eq!(dest.content("/backup/a.jpg").unwrap(), b"sunset");
+        assert_eq!(
+            dest.stat(Path::new("/backup/a.jpg"))
+                .unwrap()
+                .metadata
+                .modified,
+            Some(modified)
+        );
+        // up to date now
+        let report = sync(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &opts,
+        )
+        .unwrap();
+        assert_eq!(report.succeeded, 0);
+    }
+
+    #[test]
+    fn should_continue_on_failure() {
+        let mut source = MemoryFs::connected().with_dir("/photos");
+        let mut dest = MemoryFs::connected().with_dir("/backup");
+        let actions = vec![
+            SyncAction::Upload(PathBuf::from("a.jpg")),
+            SyncAction::CreateDir(PathBuf::from("2024")),
+        ];
+        assert!(apply(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &actions,
+            &SyncOpts::default(),
+        )
+        .is_err());
+        let report = apply(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &actions[..1],
+            &SyncOpts::default().error_policy(ErrorPolicy::Continue),
+        )
+        .unwrap();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, PathBuf::from("a.jpg"));
+    }
+
+    #[test]
+    fn should_abort_when_exceeding_delete_limit() {
+        let mut source = MemoryFs::connected()
+            .with_dir("/photos")
+            .with_file("/photos/a.jpg", b"a");
+        let mut dest = MemoryFs::connected()
+            .with_dir("/backup")
+            .with_file("/backup/a.jpg", b"a")
+            .with_file("/backup/b.jpg", b"b")
+            .with_dir("/backup/2024")
+            .with_file("/backup/2024/c.jpg", b"c");
+        let opts = SyncOpts::default()
+            .delete(true)
+            .delete_limit(DeleteLimit::Percent(50));
+        let err = sync(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &opts,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::QuotaExceeded);
+        assert!(dest.content("/backup/b.jpg").is_some());
+        assert!(dest.content("/backup/2024/c.jpg").is_some());
+        let report = sync(
+            &mut source,
+            Path::new("/photos"),
+            &mut dest,
+            Path::new("/backup"),
+            &opts.delete_limit(DeleteLimit::Count(3)),
+        )
+        .unwrap();
+        assert_eq!(report.succeeded, 2);
+        assert!(dest.content("/backup/b.jpg").is_none());
+    }
+
+    #[test]
+    fn should_check_delete_limit() {
+        assert_eq!(DeleteLimit::Count(2).is_exceeded(2, 10), false);
+        assert_eq!(DeleteLimit::Count(2).is_exceeded(3, 10), true);
+        assert_eq!(DeleteLimit::Percent(20).is_exceeded(2, 10), false);
+        assert_eq!(DeleteLimit::Percent(20).is_exceeded(3, 10), true);
+        assert_eq!(DeleteLimit::Percent(0).is_exceeded(0, 0), false);
+    }
+}
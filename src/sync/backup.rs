@@ -0,0 +1,151 @@
+//! ## Backup
+//!
+//! keep the old versions of the files overwritten or removed by the sync engine
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::BackupOpts;
+use crate::fs::walk::create_dir_all;
+use crate::fs::MoveOpts;
+use crate::utils::time::DateTime;
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// A backup session: old versions are moved into a directory named after the time the session started,
+/// with microseconds precision (`YYYYMMDD-HHMMSS-UUUUUU`)
+pub struct Backup {
+    root: PathBuf,
+    session: PathBuf,
+    keep: usize,
+    started: bool,
+}
+
+impl Backup {
+    /// Start a new backup session for the sync of `dest`. A relative backup directory is relative to `dest`
+    pub fn new(opts: &BackupOpts, dest: &Path) -> Self {
+        let root = dest.join(opts.dir.as_path());
+        let now = SystemTime::now();
+        let t = DateTime::from(now);
+        let micros = now
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.subsec_micros())
+            .unwrap_or(0);
+        let session = root.join(format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}-{:06}",
+            t.year, t.month, t.day, t.hour, t.minute, t.second, micros
+        ));
+        Self {
+            root,
+            session,
+            keep: opts.keep,
+            started: false,
+        }
+    }
+
+    /// Get the backup directory
+    pub fn root(&self) -> &Path {
+        self.root.as_path()
+    }
+
+    /// Move `dest/relative` into the session directory, if it exists.
+    /// The session directory is created on the first save, failing with `DirectoryAlreadyExists` if it already exists,
+    /// so that sessions never mix.
+    /// Returns whether it was moved
+    pub fn save<T: RemoteFs + ?Sized>(
+        &mut self,
+        fs: &mut T,
+        dest: &Path,
+        relative: &Path,
+    ) -> RemoteResult<bool> {
+        let src = dest.join(relative);
+        if !fs.exists(src.as_path())? {
+            return Ok(false);
+        }
+        if !self.started {
+            self.start(fs)?;
+        }
+        let target = self.session.join(relative);
+        if let Some(parent) = target.parent() {
+            create_dir_all(fs, parent)?;
+        }
+        debug!("Backing up {} to {}", src.display(), target.display());
+        fs.move_with(
+            src.as_path(),
+            target.as_path(),
+            &MoveOpts::default().copy_fallback(true),
+        )?;
+        Ok(true)
+    }
+
+    /// Create the session directory
+    fn start<T: RemoteFs + ?Sized>(&mut self, fs: &mut T) -> RemoteResult<()> {
+        if fs.exists(self.session.as_path())? {
+            error!("Backup session {} already exists", self.session.display());
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::DirectoryAlreadyExists,
+                format!("backup session {} already exists", self.session.display()),
+            ));
+        }
+        create_dir_all(fs, self.session.as_path())?;
+        self.started = true;
+        Ok(())
+    }
+
+    /// Remove the oldest sessions, keeping the most recent `keep`
+    pub fn prune<T: RemoteFs + ?Sized>(&self, fs: &mut T) -> RemoteResult<()> {
+        let mut sessions: Vec<PathBuf> = fs
+            .list_dir(self.root.as_path())?
+            .into_iter()
+            .filter(|x| x.is_dir())
+            .map(|x| x.path)
+            .collect();
+        sessions.sort();
+        let expired = sessions.len().saturating_sub(self.keep);
+        for session in sessions.into_iter().take(expired) {
+            debug!("Removing expired backup {}", session.display());
+            fs.remove_dir_all(session.as_path())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MemoryFs;
+
+    #[test]
+    fn should_not_reuse_backup_sessions() {
+        let mut fs = MemoryFs::connected()
+            .with_dir("/dest")
+            .with_file("/dest/a.txt", b"a")
+            .with_file("/dest/b.txt", b"b");
+        let opts = BackupOpts::new(".backup", 3);
+        let mut first = Backup::new(&opts, Path::new("/dest"));
+        let mut second = Backup::new(&opts, Path::new("/dest"));
+        assert!(first
+            .save(&mut fs, Path::new("/dest"), Path::new("a.txt"))
+            .unwrap());
+        assert!(first
+            .save(&mut fs, Path::new("/dest"), Path::new("b.txt"))
+            .unwrap());
+        second.session = first.session.clone();
+        fs.create_file(
+            Path::new("/dest/a.txt"),
+            &Default::default(),
+            Box::new(std::io::Cursor::new(b"a".to_vec())),
+        )
+        .unwrap();
+        assert_eq!(
+            second
+                .save(&mut fs, Path::new("/dest"), Path::new("a.txt"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::DirectoryAlreadyExists
+        );
+        assert_eq!(fs.content("/dest/a.txt").unwrap(), b"a");
+    }
+}
@@ -0,0 +1,12 @@
+//! ## Sync
+//!
+//! one-way synchronization of a directory tree between two `RemoteFs`
+
+mod apply;
+mod backup;
+mod opts;
+mod plan;
+
+pub use self::apply::{apply, sync};
+pub use self::opts::{BackupOpts, DeleteLimit, SyncOpts};
+pub use self::plan::{plan, SyncAction};
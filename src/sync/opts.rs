@@ -0,0 +1,140 @@
+//! ## Opts
+//!
+//! options for the sync engine
+
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "find")]
+use crate::fs::IgnoreRules;
+use crate::fs::{ChecksumAlgorithm, ErrorPolicy, WalkDirOpts};
+
+/// Where to keep the old versions of the files overwritten or removed by the sync, as `rsync --backup-dir` does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupOpts {
+    /// The backup directory on the destination; if relative, it's relative to the root of the destination
+    /// and it's excluded from the sync.
+    /// Each sync moves the old versions into a subdirectory named after its start time (e.g. `20240131-235959`)
+    pub dir: PathBuf,
+    /// Amount of backups to keep; older ones are removed at the end of the sync
+    pub keep: usize,
+}
+
+impl BackupOpts {
+    /// Instantiates a new `BackupOpts`, keeping `keep` backups in `dir`
+    pub fn new<P: AsRef<Path>>(dir: P, keep: usize) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            keep,
+        }
+    }
+}
+
+/// The maximum amount of entries a sync can remove from the destination.
+/// Entries moved into the backup directory count as removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteLimit {
+    /// At most this amount of entries
+    Count(usize),
+    /// At most this percentage (0-100) of the entries on the destination
+    Percent(u8),
+}
+
+impl DeleteLimit {
+    /// Returns whether removing `removed` entries out of `total` exceeds the limit
+    pub fn is_exceeded(&self, removed: usize, total: usize) -> bool {
+        match self {
+            Self::Count(max) => removed > *max,
+            Self::Percent(max) => removed * 100 > total * (*max as usize),
+        }
+    }
+}
+
+/// Options for `sync`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncOpts {
+    /// Move the files overwritten or removed on the destination into a backup directory
+    pub backup: Option<BackupOpts>,
+    /// Compare files by checksum instead of size and modify time.
+    /// The checksum of each file is computed reading it on both sides
+    pub checksum: Option<ChecksumAlgorithm>,
+    /// Before overwriting a file on the destination, compare its checksum with the source one, computed with `checksum`,
+    /// and skip the upload if the content is the same, even if size or modify time differ
+    pub dedup: Option<ChecksumAlgorithm>,
+    /// Remove from the destination the entries which don't exist in the source
+    pub delete: bool,
+    /// Abort the sync, before applying any action, if it would remove more entries from the destination than this.
+    /// Protects the destination from being wiped when the source is unexpectedly empty
+    pub delete_limit: Option<DeleteLimit>,
+    /// Detect files which were renamed in the source (same size and checksum, different path)
+    /// and move them on the destination instead of uploading them again.
+    /// Requires `checksum` and `delete` to be set
+    pub detect_renames: bool,
+    /// How to behave when an action fails
+    pub error_policy: ErrorPolicy,
+    /// Rules to exclude paths from the sync, on both the source and the destination.
+    /// Excluded entries on the destination are never removed
+    #[cfg(feature = "find")]
+    pub ignore: Option<IgnoreRules>,
+}
+
+impl SyncOpts {
+    /// Keep the old versions of the files overwritten or removed as specified in `backup`
+    pub fn backup(mut self, backup: BackupOpts) -> Self {
+        self.backup = Some(backup);
+        self
+    }
+
+    /// Compare files by checksum with `algorithm`
+    pub fn checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.checksum = Some(algorithm);
+        self
+    }
+
+    /// Skip uploads of files whose content already matches, comparing checksums computed with `algorithm`
+    pub fn dedup(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.dedup = Some(algorithm);
+        self
+    }
+
+    /// Remove from the destination the entries which don't exist in the source
+    pub fn delete(mut self, delete: bool) -> Self {
+        self.delete = delete;
+        self
+    }
+
+    /// Abort the sync if it would remove more entries from the destination than `limit`
+    pub fn delete_limit(mut self, limit: DeleteLimit) -> Self {
+        self.delete_limit = Some(limit);
+        self
+    }
+
+    /// Detect renamed files and move them on the destination
+    pub fn detect_renames(mut self, detect: bool) -> Self {
+        self.detect_renames = detect;
+        self
+    }
+
+    /// Set how to behave when an action fails
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Exclude the paths matching `rules` from the sync
+    #[cfg(feature = "find")]
+    pub fn ignore(mut self, rules: IgnoreRules) -> Self {
+        self.ignore = Some(rules);
+        self
+    }
+
+    /// Get the options to walk the source and the destination with
+    pub(crate) fn walk_opts(&self) -> WalkDirOpts {
+        let opts = WalkDirOpts::default();
+        #[cfg(feature = "find")]
+        let opts = match self.ignore.clone() {
+            Some(rules) => opts.ignore(rules),
+            None => opts,
+        };
+        opts
+    }
+}
@@ -0,0 +1,235 @@
+//! ## Plan
+//!
+//! compute the actions to synchronize two snapshots
+
+use std::path::{Path, PathBuf};
+
+use super::SyncOpts;
+use crate::fs::{FileType, Snapshot, SnapshotEntry};
+
+/// An action to perform on the destination to synchronize it with the source.
+/// Paths are relative to the roots of the source and of the destination
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SyncAction {
+    /// Create a directory
+    CreateDir(PathBuf),
+    /// Upload a file from the source
+    Upload(PathBuf),
+    /// Move a file already on the destination, which was renamed in the source
+    Move { from: PathBuf, to: PathBuf },
+    /// Remove a file or a symlink
+    RemoveFile(PathBuf),
+    /// Remove a directory and all its content
+    RemoveDir(PathBuf),
+}
+
+/// Compute the actions to perform on `dest` to synchronize it with `source`, ordered as they must be applied:
+/// removal of entries whose type changed, directories creation, moves, uploads and finally removal of the entries
+/// which don't exist in the source (only if `delete` is set).
+///
+/// Symbolic links in the source are not synchronized
+pub fn plan(source: &Snapshot, dest: &Snapshot, opts: &SyncOpts) -> Vec<SyncAction> {
+    let mut conflicts: Vec<SyncAction> = Vec::new();
+    let mut dirs: Vec<SyncAction> = Vec::new();
+    let mut uploads: Vec<PathBuf> = Vec::new();
+    let mut removed_dirs: Vec<PathBuf> = Vec::new();
+    for (path, entry) in source.iter() {
+        match dest.get(path) {
+            Some(existing) if existing.file_type != entry.file_type => {
+                debug!("{} changed type; it will be replaced", path.display());
+                conflicts.push(removal(path, existing, &mut removed_dirs));
+            }
+            Some(existing) if entry.file_type.is_file() && entry.changed_from(existing) => {
+                uploads.push(path.to_path_buf());
+                continue;
+            }
+            Some(_) => continue,
+            None => {}
+        }
+        match entry.file_type {
+            FileType::Directory => dirs.push(SyncAction::CreateDir(path.to_path_buf())),
+            FileType::File => uploads.push(path.to_path_buf()),
+            FileType::Symlink => debug!("skipping symlink {}", path.display()),
+        }
+    }
+    // detect renames
+    let mut moves: Vec<SyncAction> = Vec::new();
+    let mut moved: Vec<&Path> = Vec::new();
+    if opts.delete && opts.detect_renames && opts.checksum.is_some() {
+        let mut candidates: Vec<(&Path, &SnapshotEntry)> = dest
+            .iter()
+            .filter(|(path, entry)| {
+                entry.file_type.is_file()
+                    && entry.checksum.is_some()
+                    && source.get(path).is_none()
+                    && !is_under(path, &removed_dirs)
+            })
+            .collect();
+        uploads.retain(|path| {
+            let entry = match (dest.get(path), source.get(path)) {
+                (None, Some(entry)) if entry.checksum.is_some() => entry,
+                _ => return true,
+            };
+            match candidates
+                .iter()
+                .position(|(_, x)| x.size == entry.size && x.checksum == entry.checksum)
+            {
+                Some(i) => {
+                    let (from, _) = candidates.remove(i);
+                    debug!("{} was renamed to {}", from.display(), path.display());
+                    moved.push(from);
+                    moves.push(SyncAction::Move {
+                        from: from.to_path_buf(),
+                        to: path.clone(),
+                    });
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+    // removals
+    let mut removals: Vec<SyncAction> = Vec::new();
+    if opts.delete {
+        for (path, entry) in dest.iter() {
+            if source.get(path).is_none()
+                && !moved.contains(&path)
+                && !is_under(path, &removed_dirs)
+            {
+                removals.push(removal(path, entry, &mut removed_dirs));
+            }
+        }
+    }
+    conflicts
+        .into_iter()
+        .chain(dirs)
+        .chain(moves)
+        .chain(uploads.into_iter().map(SyncAction::Upload))
+        .chain(removals)
+        .collect()
+}
+
+/// Make the action to remove `path`; if it's a directory, it is pushed to `removed_dirs`
+fn removal(path: &Path, entry: &SnapshotEntry, removed_dirs: &mut Vec<PathBuf>) -> SyncAction {
+    if entry.file_type.is_dir() {
+        removed_dirs.push(path.to_path_buf());
+        SyncAction::RemoveDir(path.to_path_buf())
+    } else {
+        SyncAction::RemoveFile(path.to_path_buf())
+    }
+}
+
+/// Returns whether `path` is inside one of `dirs`
+fn is_under(path: &Path, dirs: &[PathBuf]) -> bool {
+    dirs.iter().any(|dir| path.starts_with(dir))
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::ChecksumAlgorithm;
+
+    fn entry(file_type: FileType, size: u64, checksum: &str) -> SnapshotEntry {
+        SnapshotEntry {
+            file_type,
+            size,
+            modified: Some(UNIX_EPOCH + Duration::from_secs(100)),
+            checksum: Some(checksum.to_string()),
+        }
+    }
+
+    fn snapshot(entries: &[(&str, SnapshotEntry)]) -> Snapshot {
+        let mut snapshot = Snapshot::new("/");
+        for (path, entry) in entries.iter() {
+            snapshot.insert(path, entry.clone());
+        }
+        snapshot
+    }
+
+    #[test]
+    fn should_plan_sync() {
+        let source = snapshot(&[
+            ("docs", entry(FileType::Directory, 0, "")),
+            ("docs/a.txt", entry(FileType::File, 5, "aaaa")),
+            ("docs/b.txt", entry(FileType::File, 5, "bbbb")),
+            ("new", entry(FileType::Directory, 0, "")),
+            ("new/c.txt", entry(FileType::File, 5, "cccc")),
+            ("old", entry(FileType::File, 3, "0000")),
+        ]);
+        let dest = snapshot(&[
+            ("docs", entry(FileType::Directory, 0, "")),
+            ("docs/a.txt", entry(FileType::File, 5, "aaaa")),
+            ("docs/b.txt", entry(FileType::File, 5, "BBBB")),
+            ("old", entry(FileType::Directory, 0, "")),
+            ("old/d.txt", entry(FileType::File, 5, "dddd")),
+            ("stale.txt", entry(FileType::File, 1, "ffff")),
+        ]);
+        let opts = SyncOpts::default();
+        assert_eq!(
+            plan(&source, &dest, &opts),
+            vec![
+                SyncAction::RemoveDir(PathBuf::from("old")),
+                SyncAction::CreateDir(PathBuf::from("new")),
+                SyncAction::Upload(PathBuf::from("docs/b.txt")),
+                SyncAction::Upload(PathBuf::from("new/c.txt")),
+                SyncAction::Upload(PathBuf::from("old")),
+            ]
+        );
+        assert_eq!(
+            plan(&source, &dest, &opts.delete(true)),
+            vec![
+                SyncAction::RemoveDir(PathBuf::from("old")),
+                SyncAction::CreateDir(PathBuf::from("new")),
+                SyncAction::Upload(PathBuf::from("docs/b.txt")),
+                SyncAction::Upload(PathBuf::from("new/c.txt")),
+                SyncAction::Upload(PathBuf::from("old")),
+                SyncAction::RemoveFile(PathBuf::from("stale.txt")),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_detect_renames() {
+        let source = snapshot(&[
+            ("2024", entry(FileType::Directory, 0, "")),
+            ("2024/a.jpg", entry(FileType::File, 5, "aaaa")),
+            ("2024/b.jpg", entry(FileType::File, 5, "bbbb")),
+            ("c.jpg", entry(FileType::File, 5, "cccc")),
+        ]);
+        let dest = snapshot(&[
+            ("a.jpg", entry(FileType::File, 5, "aaaa")),
+            ("b.jpg", entry(FileType::File, 5, "BBBB")),
+            ("c.jpg", entry(FileType::File, 5, "cccc")),
+        ]);
+        let opts = SyncOpts::default()
+            .checksum(ChecksumAlgorithm::Crc32)
+            .delete(true)
+            .detect_renames(true);
+        assert_eq!(
+            plan(&source, &dest, &opts),
+            vec![
+                SyncAction::CreateDir(PathBuf::from("2024")),
+                SyncAction::Move {
+                    from: PathBuf::from("a.jpg"),
+                    to: PathBuf::from("2024/a.jpg"),
+                },
+                SyncAction::Upload(PathBuf::from("2024/b.jpg")),
+                SyncAction::RemoveFile(PathBuf::from("b.jpg")),
+            ]
+        );
+        // renames are not detected without delete
+        assert_eq!(
+            plan(&source, &dest, &opts.delete(false)),
+            vec![
+                SyncAction::CreateDir(PathBuf::from("2024")),
+                SyncAction::Upload(PathBuf::from("2024/a.jpg")),
+                SyncAction::Upload(PathBuf::from("2024/b.jpg")),
+            ]
+        );
+    }
+}
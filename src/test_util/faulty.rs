@@ -0,0 +1,304 @@
+//! ## Faulty
+//!
+//! a `RemoteFs` decorator which injects failures
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::thread;
+use std::time::Duration;
+
+use crate::fs::stream::{ReadAndSeek, WriteAndSeek};
+use crate::fs::{ReadStream, WriteStream};
+use crate::wrapper::forward::forward_remote_fs;
+use crate::{RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// A `RemoteFs` decorator which injects deterministic faults into the calls to the inner file system,
+/// so that retry and resume logic can be tested:
+///
+/// - an error every Nth call
+/// - random latency, from a seeded generator
+/// - disconnection of streams after a certain amount of bytes
+pub struct FaultyFs<T: RemoteFs> {
+    inner: T,
+    calls: u64,
+    fail_every: Option<(u64, RemoteErrorType)>,
+    latency: Option<(Duration, Duration)>,
+    stream_limit: Option<u64>,
+    seed: u64,
+}
+
+impl<T: RemoteFs> FaultyFs<T> {
+    /// Instantiates a new `FaultyFs` wrapping `inner`, which doesn't inject any fault yet
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            calls: 0,
+            fail_every: None,
+            latency: None,
+            stream_limit: None,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    /// Fail every `n`th call with an error of kind `kind`
+    pub fn fail_every(mut self, n: u64, kind: RemoteErrorType) -> Self {
+        self.fail_every = Some((n.max(1), kind));
+        self
+    }
+
+    /// Delay each call by a random duration between `min` and `max`
+    pub fn latency(mut self, min: Duration, max: Duration) -> Self {
+        self.latency = Some((min, max.max(min)));
+        self
+    }
+
+    /// Make streams fail with `ConnectionAborted` after `bytes` have been transferred
+    pub fn disconnect_streams_after(mut self, bytes: u64) -> Self {
+        self.stream_limit = Some(bytes);
+        self
+    }
+
+    /// Set the seed of the random generator used for latency
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed.max(1);
+        self
+    }
+
+    /// Returns the amount of calls made so far
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Get a reference to the inner file system
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner file system
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consume the decorator, returning the inner file system
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Apply faults to call `op`
+    fn fault(&mut self, op: &str) -> RemoteResult<()> {
+        self.calls += 1;
+        if let Some((min, max)) = self.latency {
+            let delay = min + (max - min).mul_f64(self.random());
+            trace!("Delaying {} by {:?}", op, delay);
+            thread::sleep(delay);
+        }
+        match self.fail_every {
+            Some((n, kind)) if self.calls.is_multiple_of(n) => {
+                debug!("Injecting {} on {}", kind, op);
+                Err(RemoteError::new_ex(kind, "injected failure").with_op(op))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get a random number in [0, 1) with xorshift
+    fn random(&mut self) -> f64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Forward a method to the inner file system, applying faults to the call first.
+/// The streams returned by the inner file system are wrapped, so that they can be disconnected
+macro_rules! forward_faulty {
+    (@plain $this:ident, $method:ident, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $call
+        }
+    };
+    (@stream $wrap:ident, $this:ident, $method:ident, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $this.fault(stringify!($method))?;
+            let stream = $call?;
+            Ok($this.$wrap(stream))
+        }
+    };
+    ($this:ident, append, $($rest:tt)*) => {
+        forward_faulty!(@stream wrap_write, $this, append, $($rest)*);
+    };
+    ($this:ident, create, $($rest:tt)*) => {
+        forward_faulty!(@stream wrap_write, $this, create, $($rest)*);
+    };
+    ($this:ident, append_with, $($rest:tt)*) => {
+        forward_faulty!(@stream wrap_write, $this, append_with, $($rest)*);
+    };
+    ($this:ident, create_with, $($rest:tt)*) => {
+        forward_faulty!(@stream wrap_write, $this, create_with, $($rest)*);
+    };
+    ($this:ident, open, $($rest:tt)*) => {
+        forward_faulty!(@stream wrap_read, $this, open, $($rest)*);
+    };
+    ($this:ident, open_with, $($rest:tt)*) => {
+        forward_faulty!(@stream wrap_read, $this, open_with, $($rest)*);
+    };
+    ($this:ident, $method:ident, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $this.fault(stringify!($method))?;
+            $call
+        }
+    };
+}
+
+impl<T: RemoteFs> RemoteFs for FaultyFs<T> {
+    forward_remote_fs!(forward_faulty);
+}
+
+impl<T: RemoteFs> FaultyFs<T> {
+    fn wrap_read(&self, mut stream: ReadStream) -> ReadStream {
+        match self.stream_limit {
+            Some(limit) => {
+                let tags = stream.take_tags();
+                let reader: Box<dyn ReadAndSeek> = Box::new(FaultyStream::new(stream, limit));
+                ReadStream::from(reader).with_tags(tags)
+            }
+            None => stream,
+        }
+    }
+
+    fn wrap_write(&self, mut stream: WriteStream) -> WriteStream {
+        match self.stream_limit {
+            Some(limit) => {
+                let tags = stream.take_tags();
+                let writer: Box<dyn WriteAndSeek> = Box::new(FaultyStream::new(stream, limit));
+                WriteStream::from(writer).with_tags(tags)
+            }
+            None => stream,
+        }
+    }
+}
+
+/// A stream which fails with `ConnectionAborted` once `remaining` bytes have been transferred
+struct FaultyStream<S> {
+    inner: S,
+    remaining: u64,
+}
+
+impl<S> FaultyStream<S> {
+    fn new(inner: S, limit: u64) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+
+    fn allowed(&self, len: usize) -> io::Result<usize> {
+        match self.remaining {
+            0 if len > 0 => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "injected disconnection",
+            )),
+            remaining => Ok((remaining.min(len as u64)) as usize),
+        }
+    }
+}
+
+impl<S: Read> Read for FaultyStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let allowed = self.allowed(buf.len())?;
+        let read = self.inner.read(&mut buf[..allowed])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for FaultyStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let allowed = self.allowed(buf.len())?;
+        let written = self.inner.write(&buf[..allowed])?;
+        self.remaining -= written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Seek> Seek for FaultyStream<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl ReadAndSeek for FaultyStream<ReadStream> {}
+
+impl WriteAndSeek for FaultyStream<WriteStream> {}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::Metadata;
+    use crate::mock::MemoryFs;
+
+    #[test]
+    fn should_fail_every_nth_call() {
+        let mut client = FaultyFs::new(MemoryFs::connected().with_file("/a.txt", b"hello"))
+            .fail_every(3, RemoteErrorType::ConnectionError);
+        assert!(client.stat(Path::new("/a.txt")).is_ok());
+        assert!(client.stat(Path::new("/a.txt")).is_ok());
+        assert_eq!(
+            client.stat(Path::new("/a.txt")).unwrap_err().kind,
+            RemoteErrorType::ConnectionError
+        );
+        assert!(client.stat(Path::new("/a.txt")).is_ok());
+        assert_eq!(client.calls(), 4);
+    }
+
+    #[test]
+    fn should_delay_calls() {
+        let mut client = FaultyFs::new(MemoryFs::connected())
+            .latency(Duration::from_millis(5), Duration::from_millis(10))
+            .seed(42);
+        let started = std::time::Instant::now();
+        assert!(client.pwd().is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn should_disconnect_streams() {
+        let mut client = FaultyFs::new(MemoryFs::connected().with_file("/a.txt", b"hello"))
+            .disconnect_streams_after(3);
+        let mut content = Vec::new();
+        let err = client
+            .open(Path::new("/a.txt"))
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+        assert_eq!(content, b"hel");
+        let err = client
+            .create(Path::new("/b.txt"), &Metadata::default())
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+        assert_eq!(client.inner().content("/b.txt").unwrap(), b"hel");
+    }
+
+    #[test]
+    fn should_count_compound_operations_once() {
+        let mut client = FaultyFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_file("/home/a.txt", b"hello"),
+        );
+        assert!(client.remove_dir_all(Path::new("/home")).is_ok());
+        assert_eq!(client.calls(), 1);
+    }
+}
@@ -0,0 +1,9 @@
+//! ## Test util
+//!
+//! utilities to test applications built on top of `RemoteFs` (enabled by the `test-util` feature)
+
+mod faulty;
+mod replay;
+
+pub use self::faulty::FaultyFs;
+pub use self::replay::{RecordingFs, ReplayFs};
@@ -0,0 +1,793 @@
+//! ## Replay
+//!
+//! record the calls made to a `RemoteFs` and replay them offline
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::fs::stream::ReadAndSeek;
+use crate::fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream};
+use crate::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// The error kinds which can be recorded
+const ERROR_KINDS: &[RemoteErrorType] = &[
+    RemoteErrorType::AlreadyConnected,
+    RemoteErrorType::AuthenticationFailed,
+    RemoteErrorType::BadAddress,
+    RemoteErrorType::ConnectionError,
+    RemoteErrorType::SslError,
+    RemoteErrorType::StatFailed,
+    RemoteErrorType::BadFile,
+    RemoteErrorType::Cancelled,
+    RemoteErrorType::Conflict,
+    RemoteErrorType::DirectoryAlreadyExists,
+    RemoteErrorType::DirectoryNotEmpty,
+    RemoteErrorType::FileCreateDenied,
+    RemoteErrorType::FinalizeFailed,
+    RemoteErrorType::CouldNotOpenFile,
+    RemoteErrorType::CouldNotRemoveFile,
+    RemoteErrorType::IoError,
+    RemoteErrorType::NoSuchFileOrDirectory,
+    RemoteErrorType::PexError,
+    RemoteErrorType::ProtocolError,
+    RemoteErrorType::QuotaExceeded,
+    RemoteErrorType::SymlinkLoop,
+    RemoteErrorType::TransferVerificationFailed,
+    RemoteErrorType::NotConnected,
+    RemoteErrorType::ObjectArchived,
+    RemoteErrorType::UnsupportedFeature,
+];
+
+/// The outcome of a recorded call
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Unit,
+    Err(RemoteError),
+    Path(PathBuf),
+    Bool(bool),
+    Exec(u32, String),
+    Welcome(Option<String>),
+    Files(Vec<File>),
+    Data(Vec<u8>),
+    Stream,
+}
+
+/// A recorded call
+#[derive(Debug, Clone, PartialEq)]
+struct Interaction {
+    op: String,
+    args: Vec<String>,
+    outcome: Outcome,
+}
+
+/// A `RemoteFs` decorator which records all the calls made to the inner file system, and their results,
+/// into `sink`, so that they can be served back by `ReplayFs`.
+///
+/// The content of files opened for read is recorded too, while the content written is not.
+/// Metadata is recorded without acl, attributes, etag and version
+pub struct RecordingFs<T: RemoteFs, W: Write> {
+    inner: T,
+    sink: W,
+    error: Option<io::Error>,
+}
+
+impl<T: RemoteFs, W: Write> RecordingFs<T, W> {
+    /// Instantiates a new `RecordingFs`, recording the calls made to `inner` into `sink`
+    pub fn new(inner: T, sink: W) -> Self {
+        Self {
+            inner,
+            sink,
+            error: None,
+        }
+    }
+
+    /// Stop recording, returning the inner file system and the sink.
+    /// Fails if writing to the sink failed at any time
+    pub fn finish(mut self) -> io::Result<(T, W)> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.sink.flush()?;
+        Ok((self.inner, self.sink))
+    }
+
+    fn record<R>(
+        &mut self,
+        op: &str,
+        args: &[String],
+        result: RemoteResult<R>,
+        outcome: impl FnOnce(&R) -> Outcome,
+    ) -> RemoteResult<R> {
+        let interaction = Interaction {
+            op: op.to_string(),
+            args: args.to_vec(),
+            outcome: match result.as_ref() {
+                Ok(value) => outcome(value),
+                Err(err) => Outcome::Err(err.clone()),
+            },
+        };
+        if self.error.is_none() {
+            if let Err(err) = write_interaction(&mut self.sink, &interaction) {
+                error!("Failed to record {}: {}", op, err);
+                self.error = Some(err);
+            }
+        }
+        result
+    }
+}
+
+impl<T: RemoteFs, W: Write> RemoteFs for RecordingFs<T, W> {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let result = self.inner.connect();
+        self.record("connect", &[], result, |x| {
+            Outcome::Welcome(x.banner.clone())
+        })
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        let result = self.inner.disconnect();
+        self.record("disconnect", &[], result, |_| Outcome::Unit)
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        let result = self.inner.pwd();
+        self.record("pwd", &[], result, |x| Outcome::Path(x.clone()))
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let result = self.inner.change_dir(dir);
+        self.record("change_dir", &[path_arg(dir)], result, |x| {
+            Outcome::Path(x.clone())
+        })
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let result = self.inner.list_dir(path);
+        self.record("list_dir", &[path_arg(path)], result, |x| {
+            Outcome::Files(x.clone())
+        })
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let result = self.inner.stat(path);
+        self.record("stat", &[path_arg(path)], result, |x| {
+            Outcome::Files(vec![x.clone()])
+        })
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let result = self.inner.setstat(path, metadata);
+        self.record("setstat", &[path_arg(path)], result, |_| Outcome::Unit)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        let result = self.inner.exists(path);
+        self.record("exists", &[path_arg(path)], result, |x| Outcome::Bool(*x))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        let result = self.inner.remove_file(path);
+        self.record("remove_file", &[path_arg(path)], result, |_| Outcome::Unit)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        let result = self.inner.remove_dir(path);
+        self.record("remove_dir", &[path_arg(path)], result, |_| Outcome::Unit)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        let result = self.inner.create_dir(path, mode);
+        self.record("create_dir", &[path_arg(path)], result, |_| Outcome::Unit)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        let result = self.inner.symlink(path, target);
+        let args = [path_arg(path), path_arg(target)];
+        self.record("symlink", &args, result, |_| Outcome::Unit)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let result = self.inner.copy(src, dest);
+        self.record("copy", &[path_arg(src), path_arg(dest)], result, |_| {
+            Outcome::Unit
+        })
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let result = self.inner.mov(src, dest);
+        self.record("mov", &[path_arg(src), path_arg(dest)], result, |_| {
+            Outcome::Unit
+        })
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        let result = self.inner.exec(cmd);
+        self.record("exec", &[cmd.to_string()], result, |(code, output)| {
+            Outcome::Exec(*code, output.clone())
+        })
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let result = self.inner.append(path, metadata);
+        self.record("append", &[path_arg(path)], result, |_| Outcome::Stream)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let result = self.inner.create(path, metadata);
+        self.record("create", &[path_arg(path)], result, |_| Outcome::Stream)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let result = self.inner.open(path).and_then(|mut stream| {
+            let mut data = Vec::new();
+            stream
+                .read_to_end(&mut data)
+                .map_err(|e| RemoteError::from_io(RemoteErrorType::ProtocolError, e))?;
+            self.inner.on_read(stream)?;
+            Ok(data)
+        });
+        let data = self.record("open", &[path_arg(path)], result, |x| {
+            Outcome::Data(x.clone())
+        })?;
+        let reader: Box<dyn ReadAndSeek> = Box::new(Cursor::new(data));
+        Ok(ReadStream::from(reader))
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        let result = self.inner.on_written(writable);
+        self.record("on_written", &[], result, |_| Outcome::Unit)
+    }
+}
+
+/// A `RemoteFs` which serves back the calls recorded by `RecordingFs`.
+/// Calls must be made in the same order and with the same arguments they were recorded with,
+/// otherwise a `ProtocolError` is returned
+pub struct ReplayFs {
+    connected: bool,
+    interactions: VecDeque<Interaction>,
+}
+
+impl ReplayFs {
+    /// Instantiates a new `ReplayFs` reading a recording from `reader`
+    pub fn new<R: BufRead>(reader: R) -> io::Result<Self> {
+        Ok(Self {
+            connected: false,
+            interactions: read_interactions(reader)?,
+        })
+    }
+
+    /// Returns the amount of recorded calls which haven't been replayed yet
+    pub fn remaining(&self) -> usize {
+        self.interactions.len()
+    }
+
+    fn replay(&mut self, op: &str, args: &[String]) -> RemoteResult<Outcome> {
+        match self.interactions.pop_front() {
+            Some(x) if x.op == op && x.args == args => match x.outcome {
+                Outcome::Err(err) => Err(err),
+                outcome => Ok(outcome),
+            },
+            Some(x) => Err(RemoteError::new_ex(
+                RemoteErrorType::ProtocolError,
+                format!("expected {} {:?}; got {} {:?}", x.op, x.args, op, args),
+            )),
+            None => Err(RemoteError::new_ex(
+                RemoteErrorType::ProtocolError,
+                format!("unexpected {op} {args:?}; recording is over"),
+            )),
+        }
+    }
+
+    fn replay_unit(&mut self, op: &str, args: &[String]) -> RemoteResult<()> {
+        self.replay(op, args).and_then(|x| match x {
+            Outcome::Unit => Ok(()),
+            other => Err(unexpected_outcome(op, other)),
+        })
+    }
+
+    fn replay_path(&mut self, op: &str, args: &[String]) -> RemoteResult<PathBuf> {
+        self.replay(op, args).and_then(|x| match x {
+            Outcome::Path(path) => Ok(path),
+            other => Err(unexpected_outcome(op, other)),
+        })
+    }
+
+    fn replay_files(&mut self, op: &str, args: &[String]) -> RemoteResult<Vec<File>> {
+        self.replay(op, args).and_then(|x| match x {
+            Outcome::Files(files) => Ok(files),
+            other => Err(unexpected_outcome(op, other)),
+        })
+    }
+
+    fn replay_stream(&mut self, op: &str, args: &[String]) -> RemoteResult<WriteStream> {
+        self.replay(op, args).and_then(|x| match x {
+            Outcome::Stream => {
+                let writer: Box<dyn Write + Send> = Box::new(io::sink());
+                Ok(WriteStream::from(writer))
+            }
+            other => Err(unexpected_outcome(op, other)),
+        })
+    }
+}
+
+impl RemoteFs for ReplayFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let banner = self.replay("connect", &[]).and_then(|x| match x {
+            Outcome::Welcome(banner) => Ok(banner),
+            other => Err(unexpected_outcome("connect", other)),
+        })?;
+        self.connected = true;
+        Ok(Welcome::default().banner(banner))
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.replay_unit("disconnect", &[])?;
+        self.connected = false;
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.replay_path("pwd", &[])
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.replay_path("change_dir", &[path_arg(dir)])
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.replay_files("list_dir", &[path_arg(path)])
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.replay_files("stat", &[path_arg(path)])?
+            .pop()
+            .ok_or_else(|| unexpected_outcome("stat", Outcome::Files(vec![])))
+    }
+
+    fn setstat(&mut self, path: &Path, _metadata: Metadata) -> RemoteResult<()> {
+        self.replay_unit("setstat", &[path_arg(path)])
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.replay("exists", &[path_arg(path)])
+            .and_then(|x| match x {
+                Outcome::Bool(exists) => Ok(exists),
+                other => Err(unexpected_outcome("exists", other)),
+            })
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.replay_unit("remove_file", &[path_arg(path)])
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.replay_unit("remove_dir", &[path_arg(path)])
+    }
+
+    fn create_dir(&mut self, path: &Path, _mode: UnixPex) -> RemoteResult<()> {
+        self.replay_unit("create_dir", &[path_arg(path)])
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.replay_unit("symlink", &[path_arg(path), path_arg(target)])
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.replay_unit("copy", &[path_arg(src), path_arg(dest)])
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.replay_unit("mov", &[path_arg(src), path_arg(dest)])
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.replay("exec", &[cmd.to_string()])
+            .and_then(|x| match x {
+                Outcome::Exec(code, output) => Ok((code, output)),
+                other => Err(unexpected_outcome("exec", other)),
+            })
+    }
+
+    fn append(&mut self, path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.replay_stream("append", &[path_arg(path)])
+    }
+
+    fn create(&mut self, path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.replay_stream("create", &[path_arg(path)])
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let data = self
+            .replay("open", &[path_arg(path)])
+            .and_then(|x| match x {
+                Outcome::Data(data) => Ok(data),
+                other => Err(unexpected_outcome("open", other)),
+            })?;
+        let reader: Box<dyn ReadAndSeek> = Box::new(Cursor::new(data));
+        Ok(ReadStream::from(reader))
+    }
+
+    fn on_written(&mut self, _writable: WriteStream) -> RemoteResult<()> {
+        self.replay_unit("on_written", &[])
+    }
+}
+
+fn unexpected_outcome(op: &str, outcome: Outcome) -> RemoteError {
+    RemoteError::new_ex(
+        RemoteErrorType::ProtocolError,
+        format!("unexpected recorded outcome for {op}: {outcome:?}"),
+    )
+}
+
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+// -- format
+//
+// each interaction is a `call` line, with the operation and its arguments, followed by the outcome line(s).
+// Fields are separated by tabs and escaped; optional fields are `-` if none, or `=` followed by the value
+
+fn write_interaction<W: Write>(w: &mut W, interaction: &Interaction) -> io::Result<()> {
+    let mut fields = vec!["call".to_string(), interaction.op.clone()];
+    fields.extend(interaction.args.iter().cloned());
+    write_line(w, &fields)?;
+    match &interaction.outcome {
+        Outcome::Unit => write_line(w, &["ok".to_string()]),
+        Outcome::Err(err) => write_line(
+            w,
+            &[
+                "err".to_string(),
+                format!("{:?}", err.kind),
+                encode_opt(err.msg.as_ref()),
+                encode_opt(err.op().map(str::to_string).as_ref()),
+                encode_opt(err.path().map(path_arg).as_ref()),
+            ],
+        ),
+        Outcome::Path(path) => write_line(w, &["path".to_string(), path_arg(path)]),
+        Outcome::Bool(b) => write_line(w, &["bool".to_string(), b.to_string()]),
+        Outcome::Exec(code, output) => {
+            write_line(w, &["exec".to_string(), code.to_string(), output.clone()])
+        }
+        Outcome::Welcome(banner) => {
+            write_line(w, &["welcome".to_string(), encode_opt(banner.as_ref())])
+        }
+        Outcome::Files(files) => {
+            write_line(w, &["files".to_string(), files.len().to_string()])?;
+            files
+                .iter()
+                .try_for_each(|x| write_line(w, &encode_file(x)))
+        }
+        Outcome::Data(data) => {
+            let hex: String = data.iter().map(|x| format!("{x:02x}")).collect();
+            write_line(w, &["data".to_string(), hex])
+        }
+        Outcome::Stream => write_line(w, &["stream".to_string()]),
+    }
+}
+
+fn read_interactions<R: BufRead>(reader: R) -> io::Result<VecDeque<Interaction>> {
+    let mut lines = reader.lines();
+    let mut interactions = VecDeque::new();
+    while let Some(line) = lines.next() {
+        let call = split_line(&line?);
+        if call.len() < 2 || call[0] != "call" {
+            return Err(invalid_data(format!("expected call; got {call:?}")));
+        }
+        let outcome = split_line(
+            &lines
+                .next()
+                .ok_or_else(|| invalid_data("missing outcome"))??,
+        );
+        let outcome = match outcome
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            ["ok"] => Outcome::Unit,
+            ["err", kind, msg, op, path] => {
+                let kind = ERROR_KINDS
+                    .iter()
+                    .find(|x| format!("{x:?}") == *kind)
+                    .ok_or_else(|| invalid_data(format!("unknown error kind {kind}")))?;
+                let mut err = RemoteError::new(*kind);
+                err.msg = decode_opt(msg)?;
+                if let Some(op) = decode_opt(op)? {
+                    err = err.with_op(op);
+                }
+                if let Some(path) = decode_opt(path)? {
+                    err = err.with_path(path);
+                }
+                Outcome::Err(err)
+            }
+            ["path", path] => Outcome::Path(PathBuf::from(path)),
+            ["bool", b] => Outcome::Bool(*b == "true"),
+            ["exec", code, output] => Outcome::Exec(parse(code)?, output.to_string()),
+            ["welcome", banner] => Outcome::Welcome(decode_opt(banner)?),
+            ["files", n] => {
+                let n: usize = parse(n)?;
+                let mut files = Vec::with_capacity(n);
+                for _ in 0..n {
+                    let line = lines.next().ok_or_else(|| invalid_data("missing file"))??;
+                    files.push(decode_file(&split_line(&line))?);
+                }
+                Outcome::Files(files)
+            }
+            ["data", hex] => Outcome::Data(decode_hex(hex)?),
+            ["stream"] => Outcome::Stream,
+            other => return Err(invalid_data(format!("invalid outcome {other:?}"))),
+        };
+        interactions.push_back(Interaction {
+            op: call[1].clone(),
+            args: call[2..].to_vec(),
+            outcome,
+        });
+    }
+    Ok(interactions)
+}
+
+fn encode_file(file: &File) -> Vec<String> {
+    let metadata = file.metadata();
+    let file_type = match metadata.file_type {
+        FileType::Directory => "d",
+        FileType::File => "f",
+        FileType::Symlink => "l",
+    };
+    vec![
+        "file".to_string(),
+        path_arg(file.path()),
+        file_type.to_string(),
+        metadata.size.to_string(),
+        encode_opt(metadata.accessed.map(encode_time).as_ref()),
+        encode_opt(metadata.created.map(encode_time).as_ref()),
+        encode_opt(metadata.modified.map(encode_time).as_ref()),
+        encode_opt(metadata.mode.map(u32::from).as_ref()),
+        encode_opt(metadata.uid.as_ref()),
+        encode_opt(metadata.gid.as_ref()),
+        encode_opt(metadata.symlink.as_deref().map(path_arg).as_ref()),
+    ]
+}
+
+fn decode_file(fields: &[String]) -> io::Result<File> {
+    let [tag, path, file_type, size, accessed, created, modified, mode, uid, gid, symlink] = fields
+    else {
+        return Err(invalid_data(format!("invalid file {fields:?}")));
+    };
+    if tag != "file" {
+        return Err(invalid_data(format!("expected file; got {tag}")));
+    }
+    let file_type = match file_type.as_str() {
+        "d" => FileType::Directory,
+        "f" => FileType::File,
+        "l" => FileType::Symlink,
+        other => return Err(invalid_data(format!("invalid file type {other}"))),
+    };
+    let metadata = Metadata {
+        accessed: decode_opt(accessed)?.map(|x| decode_time(&x)).transpose()?,
+        created: decode_opt(created)?.map(|x| decode_time(&x)).transpose()?,
+        modified: decode_opt(modified)?.map(|x| decode_time(&x)).transpose()?,
+        mode: decode_opt(mode)?
+            .map(|x| parse::<u32>(&x).map(UnixPex::from))
+            .transpose()?,
+        uid: decode_opt(uid)?.map(|x| parse(&x)).transpose()?,
+        gid: decode_opt(gid)?.map(|x| parse(&x)).transpose()?,
+        symlink: decode_opt(symlink)?.map(PathBuf::from),
+        size: parse(size)?,
+        file_type,
+        ..Default::default()
+    };
+    Ok(File {
+        path: PathBuf::from(path),
+        metadata,
+    })
+}
+
+fn encode_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "{}.{:09}",
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos()
+    )
+}
+
+fn decode_time(s: &str) -> io::Result<SystemTime> {
+    let (secs, nanos) = s
+        .split_once('.')
+        .ok_or_else(|| invalid_data(format!("invalid time {s}")))?;
+    Ok(UNIX_EPOCH + Duration::new(parse(secs)?, parse(nanos)?))
+}
+
+fn encode_opt<T: ToString>(value: Option<&T>) -> String {
+    match value {
+        Some(value) => format!("={}", value.to_string()),
+        None => "-".to_string(),
+    }
+}
+
+fn decode_opt(s: &str) -> io::Result<Option<String>> {
+    match s {
+        "-" => Ok(None),
+        s => s
+            .strip_prefix('=')
+            .map(|x| Some(x.to_string()))
+            .ok_or_else(|| invalid_data(format!("invalid optional field {s}"))),
+    }
+}
+
+fn decode_hex(hex: &str) -> io::Result<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+                .ok_or_else(|| invalid_data("invalid hex data"))
+        })
+        .collect()
+}
+
+fn parse<T: std::str::FromStr>(s: &str) -> io::Result<T> {
+    s.parse()
+        .map_err(|_| invalid_data(format!("invalid number {s}")))
+}
+
+fn write_line<W: Write>(w: &mut W, fields: &[String]) -> io::Result<()> {
+    let fields: Vec<String> = fields.iter().map(|x| escape(x)).collect();
+    writeln!(w, "{}", fields.join("\t"))
+}
+
+fn split_line(line: &str) -> Vec<String> {
+    line.split('\t').map(unescape).collect()
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            (c, false) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn invalid_data<S: ToString>(msg: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MemoryFs;
+
+    #[test]
+    fn should_record_and_replay_session() {
+        let mut client = RecordingFs::new(
+            MemoryFs::default()
+                .with_dir("/home")
+                .with_file("/home/a.txt", b"hello\tworld\n")
+                .with_symlink("/home/b.txt", "a.txt")
+                .with_exec("uname", 0, "Linux\n"),
+            Vec::new(),
+        );
+        assert!(client.connect().is_ok());
+        let files = client.list_dir(Path::new("/home")).unwrap();
+        let stat = client.stat(Path::new("/home/a.txt")).unwrap();
+        assert_eq!(client.exists(Path::new("/tmp")).unwrap(), false);
+        let err = client.stat(Path::new("/tmp")).unwrap_err();
+        let mut content = Vec::new();
+        client
+            .open(Path::new("/home/a.txt"))
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(client.exec("uname").unwrap(), (0, "Linux\n".to_string()));
+        assert!(client.remove_file(Path::new("/home/b.txt")).is_ok());
+        let (_, recording) = client.finish().unwrap();
+
+        let mut client = ReplayFs::new(recording.as_slice()).unwrap();
+        assert_eq!(client.remaining(), 8);
+        assert!(client.connect().is_ok());
+        assert_eq!(client.is_connected(), true);
+        assert_eq!(client.list_dir(Path::new("/home")).unwrap(), files);
+        assert_eq!(client.stat(Path::new("/home/a.txt")).unwrap(), stat);
+        assert_eq!(client.exists(Path::new("/tmp")).unwrap(), false);
+        assert_eq!(client.stat(Path::new("/tmp")).unwrap_err(), err);
+        let mut replayed = Vec::new();
+        client
+            .open(Path::new("/home/a.txt"))
+            .unwrap()
+            .read_to_end(&mut replayed)
+            .unwrap();
+        assert_eq!(replayed, content);
+        assert_eq!(client.exec("uname").unwrap(), (0, "Linux\n".to_string()));
+        // wrong order
+        assert_eq!(
+            client.remove_dir(Path::new("/home")).unwrap_err().kind,
+            RemoteErrorType::ProtocolError
+        );
+        assert_eq!(client.remaining(), 0);
+    }
+
+    #[test]
+    fn should_preserve_file_metadata() {
+        let file = File {
+            path: PathBuf::from("/home/omar/my\tfile.txt"),
+            metadata: Metadata::default()
+                .modified(UNIX_EPOCH + Duration::new(1700000000, 123))
+                .mode(UnixPex::from(0o644))
+                .uid(1000)
+                .size(42),
+        };
+        assert_eq!(
+            decode_file(&split_line(&encode_line(&encode_file(&file)))).unwrap(),
+            file
+        );
+    }
+
+    #[test]
+    fn should_preserve_error_context() {
+        let interaction = Interaction {
+            op: "stat".to_string(),
+            args: vec!["/tmp".to_string()],
+            outcome: Outcome::Err(
+                RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)
+                    .with_op("stat")
+                    .with_path("/tmp"),
+            ),
+        };
+        let mut recording = Vec::new();
+        write_interaction(&mut recording, &interaction).unwrap();
+        let mut interactions = read_interactions(recording.as_slice()).unwrap();
+        match interactions.pop_front().map(|x| x.outcome) {
+            Some(Outcome::Err(err)) => {
+                assert_eq!(err.kind, RemoteErrorType::NoSuchFileOrDirectory);
+                assert_eq!(err.op(), Some("stat"));
+                assert_eq!(err.path(), Some(Path::new("/tmp")));
+            }
+            outcome => panic!("unexpected outcome {outcome:?}"),
+        }
+    }
+
+    fn encode_line(fields: &[String]) -> String {
+        let mut line = Vec::new();
+        write_line(&mut line, fields).unwrap();
+        String::from_utf8(line).unwrap().trim_end().to_string()
+    }
+
+    #[test]
+    fn should_reject_invalid_recording() {
+        assert!(ReplayFs::new("call\tpwd\nwhat\n".as_bytes()).is_err());
+        assert!(ReplayFs::new("pwd\n".as_bytes()).is_err());
+        assert!(ReplayFs::new("call\tpwd\n".as_bytes()).is_err());
+    }
+}
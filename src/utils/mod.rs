@@ -3,3 +3,6 @@
 //! `utils` is the module which provides utilities of different kind
 
 pub mod path;
+pub mod shell;
+pub mod time;
+pub mod url;
@@ -2,7 +2,7 @@
 //!
 //! path utilities
 
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 /// Absolutize target path if relative.
 pub fn absolutize(wrkdir: &Path, target: &Path) -> PathBuf {
@@ -16,6 +16,31 @@ pub fn absolutize(wrkdir: &Path, target: &Path) -> PathBuf {
     }
 }
 
+/// Lexically resolve `.` and `..` components of `path`, without accessing the file system
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Replace the leading `~` component of `path` with `home`.
+/// Returns `None` if `path` doesn't start with `~` (`~user` is not expanded)
+pub fn expand_tilde(home: &Path, path: &Path) -> Option<PathBuf> {
+    match path.strip_prefix("~") {
+        Ok(rest) if rest.as_os_str().is_empty() => Some(home.to_path_buf()),
+        Ok(rest) => Some(home.join(rest)),
+        Err(_) => None,
+    }
+}
+
 #[cfg(test)]
 mod test {
 
@@ -24,12 +49,38 @@ mod test {
     #[test]
     fn absolutize_path() {
         assert_eq!(
-            absolutize(&Path::new("/home/omar"), &Path::new("readme.txt")).as_path(),
+            absolutize(Path::new("/home/omar"), Path::new("readme.txt")).as_path(),
             Path::new("/home/omar/readme.txt")
         );
         assert_eq!(
-            absolutize(&Path::new("/home/omar"), &Path::new("/tmp/readme.txt")).as_path(),
+            absolutize(Path::new("/home/omar"), Path::new("/tmp/readme.txt")).as_path(),
             Path::new("/tmp/readme.txt")
         );
     }
+
+    #[test]
+    fn normalize_path() {
+        assert_eq!(
+            normalize(Path::new("/home/omar/../cristiano/./docs")).as_path(),
+            Path::new("/home/cristiano/docs")
+        );
+        assert_eq!(normalize(Path::new("/..")).as_path(), Path::new("/"));
+    }
+
+    #[test]
+    fn expand_tilde_path() {
+        let home = Path::new("/home/omar");
+        assert_eq!(
+            expand_tilde(home, Path::new("~/uploads"))
+                .unwrap()
+                .as_path(),
+            Path::new("/home/omar/uploads")
+        );
+        assert_eq!(
+            expand_tilde(home, Path::new("~")).unwrap().as_path(),
+            Path::new("/home/omar")
+        );
+        assert!(expand_tilde(home, Path::new("~cristiano/docs")).is_none());
+        assert!(expand_tilde(home, Path::new("/tmp/~")).is_none());
+    }
 }
@@ -0,0 +1,142 @@
+//! ## Shell
+//!
+//! utilities to build shell commands for remote file systems supporting `exec`
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Environment to set before running a command with `exec`.
+/// By default it sets `LC_ALL=C`, so that the output of commands is not localized and can be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellEnv {
+    vars: BTreeMap<String, String>,
+}
+
+impl Default for ShellEnv {
+    fn default() -> Self {
+        Self::empty().locale("C")
+    }
+}
+
+impl ShellEnv {
+    /// Instantiates a new `ShellEnv` which doesn't set any variable, not even the locale
+    pub fn empty() -> Self {
+        Self {
+            vars: BTreeMap::new(),
+        }
+    }
+
+    /// Set the locale commands are run with (`LC_ALL`)
+    pub fn locale<S: ToString>(self, locale: S) -> Self {
+        self.var("LC_ALL", locale)
+    }
+
+    /// Set environment variable `key` to `value`.
+    /// Variables whose name is not a valid shell identifier are ignored
+    pub fn var<K: ToString, V: ToString>(mut self, key: K, value: V) -> Self {
+        let key = key.to_string();
+        if is_identifier(&key) {
+            self.vars.insert(key, value.to_string());
+        } else {
+            warn!("Ignoring invalid environment variable name {:?}", key);
+        }
+        self
+    }
+
+    /// Wrap `cmd`, so that it runs with this environment
+    pub fn wrap(&self, cmd: &str) -> String {
+        if self.vars.is_empty() {
+            return cmd.to_string();
+        }
+        let vars: Vec<String> = self
+            .vars
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, escape(v)))
+            .collect();
+        format!("export {}; {}", vars.join(" "), cmd)
+    }
+}
+
+/// Escape `arg` so that it is passed verbatim as a single argument to a POSIX shell.
+/// Arguments made up only of safe characters are returned as they are; any other is wrapped in single quotes.
+pub fn escape(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Escape `path` so that it is passed verbatim as a single argument to a POSIX shell.
+/// Relative paths starting with `-` are prefixed with `./`, so that commands don't take them for options
+pub fn escape_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    match path.starts_with('-') {
+        true => escape(format!("./{path}").as_str()),
+        false => escape(path.as_ref()),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c)
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_not_escape_safe_args() {
+        assert_eq!(escape("/home/omar/readme.txt"), "/home/omar/readme.txt");
+        assert_eq!(escape("-rf"), "-rf");
+    }
+
+    #[test]
+    fn should_escape_hostile_args() {
+        assert_eq!(escape(""), "''");
+        assert_eq!(escape("my file.txt"), "'my file.txt'");
+        assert_eq!(escape("\"quoted\".txt"), "'\"quoted\".txt'");
+        assert_eq!(escape("$(rm -rf ~)"), "'$(rm -rf ~)'");
+        assert_eq!(escape("`whoami`;ls"), "'`whoami`;ls'");
+        assert_eq!(escape("omar's file"), "'omar'\\''s file'");
+        assert_eq!(escape("line\nbreak"), "'line\nbreak'");
+        assert_eq!(escape("*.txt"), "'*.txt'");
+    }
+
+    #[test]
+    fn should_escape_paths() {
+        assert_eq!(
+            escape_path(Path::new("/home/omar/$HOME.txt")),
+            "'/home/omar/$HOME.txt'"
+        );
+        assert_eq!(escape_path(Path::new("-rf")), "./-rf");
+        assert_eq!(escape_path(Path::new("--help me")), "'./--help me'");
+        assert_eq!(escape_path(Path::new("/tmp/-rf")), "/tmp/-rf");
+    }
+
+    #[test]
+    fn should_wrap_command_with_env() {
+        assert_eq!(ShellEnv::default().wrap("ls -l"), "export LC_ALL=C; ls -l");
+        assert_eq!(ShellEnv::empty().wrap("ls -l"), "ls -l");
+        assert_eq!(
+            ShellEnv::default()
+                .locale("en_US.UTF-8")
+                .var("TZ", "UTC")
+                .var("GREETING", "hello world")
+                .var("1NVALID", "x")
+                .var("EVIL; rm", "x")
+                .wrap("date"),
+            "export GREETING='hello world' LC_ALL=en_US.UTF-8 TZ=UTC; date"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! ## Time
+//!
+//! utilities to format times without depending on a date crate
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A UTC date and time, with seconds precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl From<SystemTime> for DateTime {
+    fn from(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        let (days, secs) = ((secs / 86400) as i64, (secs % 86400) as u32);
+        // civil date from days since epoch (Howard Hinnant's algorithm)
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Self {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: (secs % 3600) / 60,
+            second: secs % 60,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_convert_system_time_to_date_time() {
+        assert_eq!(
+            DateTime::from(UNIX_EPOCH),
+            DateTime {
+                year: 1970,
+                month: 1,
+                day: 1,
+                hour: 0,
+                minute: 0,
+                second: 0
+            }
+        );
+        assert_eq!(
+            DateTime::from(UNIX_EPOCH + Duration::from_secs(951782400 + 3661)),
+            DateTime {
+                year: 2000,
+                month: 2,
+                day: 29,
+                hour: 1,
+                minute: 1,
+                second: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! ## Audit
+//!
+//! a `RemoteFs` decorator reporting the operations made on the file system to an audit sink
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::forward::forward_remote_fs;
+use crate::fs::format::json_string;
+use crate::utils::time::DateTime;
+use crate::{RemoteFs, RemoteResult};
+
+/// The outcome of an audited operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Success,
+    /// The operation failed with this error
+    Failure(String),
+}
+
+impl AuditOutcome {
+    fn of<R>(result: &RemoteResult<R>) -> Self {
+        match result {
+            Ok(_) => Self::Success,
+            Err(err) => Self::Failure(err.to_string()),
+        }
+    }
+}
+
+/// A structured record of an operation made on the file system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// When the operation was made
+    pub timestamp: SystemTime,
+    /// Who made the operation
+    pub principal: String,
+    /// The name of the operation (e.g. `remove_file`)
+    pub operation: &'static str,
+    /// The path the operation was made on, if any
+    pub path: Option<PathBuf>,
+    /// The second path of the operation, if any (e.g. the destination of `mov`)
+    pub target: Option<PathBuf>,
+    /// The command run by the operation, if any (e.g. by `exec`)
+    pub command: Option<String>,
+    pub outcome: AuditOutcome,
+}
+
+impl AuditRecord {
+    /// Render the record as a single line JSON object, without the line terminator.
+    /// The timestamp is expressed in RFC 3339 format (UTC)
+    pub fn to_json(&self) -> String {
+        let t = DateTime::from(self.timestamp);
+        let path = |x: &Option<PathBuf>| {
+            x.as_ref()
+                .map(|x| json_string(&x.to_string_lossy()))
+                .unwrap_or_else(|| "null".to_string())
+        };
+        let command = self
+            .command
+            .as_deref()
+            .map(json_string)
+            .unwrap_or_else(|| "null".to_string());
+        let (outcome, error) = match &self.outcome {
+            AuditOutcome::Success => ("success", "null".to_string()),
+            AuditOutcome::Failure(err) => ("failure", json_string(err)),
+        };
+        format!(
+            "{{\"timestamp\":\"{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z\",\"principal\":{},\"operation\":\"{}\",\"path\":{},\"target\":{},\"command\":{},\"outcome\":\"{}\",\"error\":{}}}",
+            t.year,
+            t.month,
+            t.day,
+            t.hour,
+            t.minute,
+            t.second,
+            json_string(&self.principal),
+            self.operation,
+            path(&self.path),
+            path(&self.target),
+            command,
+            outcome,
+            error
+        )
+    }
+}
+
+/// A destination for the audit records emitted by `AuditedFs`
+pub trait AuditSink {
+    /// Store `record`
+    fn record(&mut self, record: &AuditRecord) -> io::Result<()>;
+}
+
+/// An `AuditSink` writing records as JSON lines (one JSON object per line), flushing after each record
+pub struct JsonlSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonlSink<W> {
+    /// Instantiates a new `JsonlSink` writing to `writer`
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Consume the sink, returning the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl JsonlSink<std::fs::File> {
+    /// Instantiates a new `JsonlSink` appending to the local file at `path`, which is created if it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(Self::new)
+    }
+}
+
+impl<W: Write> AuditSink for JsonlSink<W> {
+    fn record(&mut self, record: &AuditRecord) -> io::Result<()> {
+        writeln!(self.writer, "{}", record.to_json())?;
+        self.writer.flush()
+    }
+}
+
+/// A `RemoteFs` decorator which reports each operation made on the inner file system on behalf of `principal`,
+/// and its outcome, to an `AuditSink`.
+///
+/// Operations are reported once completed. All the methods of `RemoteFs` are forwarded to the inner file system,
+/// so that its implementations are used, and reported as a single operation named after the method (e.g. `remove_dir_all`).
+/// The commands run with `exec`, `exec_with_env` and `exec_bytes` are recorded in full, arguments included.
+/// Failing to store a record doesn't fail the operation; the first error is returned by `finish`
+pub struct AuditedFs<T: RemoteFs, S: AuditSink> {
+    inner: T,
+    auditor: Auditor<S>,
+}
+
+impl<T: RemoteFs, S: AuditSink> AuditedFs<T, S> {
+    /// Instantiates a new `AuditedFs`, reporting the operations made by `principal` on `inner` to `sink`
+    pub fn new<P: ToString>(inner: T, sink: S, principal: P) -> Self {
+        Self {
+            inner,
+            auditor: Auditor {
+                sink,
+                principal: principal.to_string(),
+                error: None,
+            },
+        }
+    }
+
+    /// Get a reference to the inner file system
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Stop auditing, returning the inner file system and the sink.
+    /// Fails if storing a record failed at any time
+    pub fn finish(mut self) -> io::Result<(T, S)> {
+        match self.auditor.error.take() {
+            Some(err) => Err(err),
+            None => Ok((self.inner, self.auditor.sink)),
+        }
+    }
+}
+
+/// Stores the records of the operations made on an `AuditedFs`.
+/// Kept apart from the inner file system, so that it can be used while the inner file system is borrowed
+struct Auditor<S: AuditSink> {
+    sink: S,
+    principal: String,
+    /// The first error returned by the sink
+    error: Option<io::Error>,
+}
+
+impl<S: AuditSink> Auditor<S> {
+    /// Store the record of `operation`, keeping the first error returned by the sink
+    fn audit<R>(
+        &mut self,
+        operation: &'static str,
+        path: Option<&Path>,
+        target: Option<&Path>,
+        command: Option<&str>,
+        result: RemoteResult<R>,
+    ) -> RemoteResult<R> {
+        let record = AuditRecord {
+            timestamp: SystemTime::now(),
+            principal: self.principal.clone(),
+            operation,
+            path: path.map(Path::to_path_buf),
+            target: target.map(Path::to_path_buf),
+            command: command.map(str::to_string),
+            outcome: AuditOutcome::of(&result),
+        };
+        if let Err(err) = self.sink.record(&record) {
+            error!("Failed to audit {}: {}", operation, err);
+            if self.error.is_none() {
+                self.error = Some(err);
+            }
+        }
+        result
+    }
+}
+
+/// Forward a method to the inner file system, auditing it once completed with its paths and command.
+/// Getting the working directory and finalizing streams are not audited
+macro_rules! forward_audited {
+    (@plain $this:ident, $method:ident, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $call
+        }
+    };
+    ($this:ident, pwd, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        forward_audited!(@plain $this, pwd, $sig -> $ret, $call);
+    };
+    ($this:ident, on_written, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        forward_audited!(@plain $this, on_written, $sig -> $ret, $call);
+    };
+    ($this:ident, on_written_ext, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        forward_audited!(@plain $this, on_written_ext, $sig -> $ret, $call);
+    };
+    ($this:ident, on_read, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        forward_audited!(@plain $this, on_read, $sig -> $ret, $call);
+    };
+    ($this:ident, $method:ident, [$($path:ident),*], [$($cmd:ident)?], $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            let paths: &[&Path] = &[$($path),*];
+            let commands: &[&str] = &[$($cmd)?];
+            let result = $call;
+            $this.auditor.audit(
+                stringify!($method),
+                paths.first().copied(),
+                paths.get(1).copied(),
+                commands.first().copied(),
+                result,
+            )
+        }
+    };
+}
+
+impl<T: RemoteFs, S: AuditSink> RemoteFs for AuditedFs<T, S> {
+    forward_remote_fs!(forward_audited);
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::ConnectOpts;
+    use crate::mock::MemoryFs;
+
+    #[derive(Default)]
+    struct VecSink(Vec<AuditRecord>);
+
+    impl AuditSink for VecSink {
+        fn record(&mut self, record: &AuditRecord) -> io::Result<()> {
+            self.0.push(record.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_audit_operations() {
+        let mut client = AuditedFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_file("/home/a.txt", b"a"),
+            VecSink::default(),
+            "alice",
+        );
+        assert!(client
+            .mov(Path::new("/home/a.txt"), Path::new("/home/b.txt"))
+            .is_ok());
+        assert!(client.remove_file(Path::new("/home/a.txt")).is_err());
+        let (_, sink) = client.finish().unwrap();
+        let records: Vec<_> = sink
+            .0
+            .iter()
+            .map(|x| {
+                (
+                    x.principal.as_str(),
+                    x.operation,
+                    x.path.clone(),
+                    x.target.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                (
+                    "alice",
+                    "mov",
+                    Some(PathBuf::from("/home/a.txt")),
+                    Some(PathBuf::from("/home/b.txt"))
+                ),
+                (
+                    "alice",
+                    "remove_file",
+                    Some(PathBuf::from("/home/a.txt")),
+                    None
+                ),
+            ]
+        );
+        assert_eq!(sink.0[0].outcome, AuditOutcome::Success);
+        assert!(matches!(sink.0[1].outcome, AuditOutcome::Failure(_)));
+    }
+
+    #[test]
+    fn should_write_jsonl() {
+        let mut sink = JsonlSink::new(Vec::new());
+        let mut record = AuditRecord {
+            timestamp: UNIX_EPOCH + Duration::from_secs(1700000000),
+            principal: "alice".to_string(),
+            operation: "copy",
+            path: Some(PathBuf::from("/a.txt")),
+            target: Some(PathBuf::from("/b \"1\".txt")),
+            command: None,
+            outcome: AuditOutcome::Success,
+        };
+        sink.record(&record).unwrap();
+        record.operation = "exec";
+        record.target = None;
+        record.command = Some("rm -rf \"/a b\"".to_string());
+        record.outcome = AuditOutcome::Failure("No such file or directory".to_string());
+        sink.record(&record).unwrap();
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            concat!(
+                "{\"timestamp\":\"2023-11-14T22:13:20Z\",\"principal\":\"alice\",\"operation\":\"copy\",\"path\":\"/a.txt\",\"target\":\"/b \\\"1\\\".txt\",\"command\":null,\"outcome\":\"success\",\"error\":null}\n",
+                "{\"timestamp\":\"2023-11-14T22:13:20Z\",\"principal\":\"alice\",\"operation\":\"exec\",\"path\":\"/a.txt\",\"target\":null,\"command\":\"rm -rf \\\"/a b\\\"\",\"outcome\":\"failure\",\"error\":\"No such file or directory\"}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn should_audit_compound_operations_once() {
+        let mut client = AuditedFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_dir("/home/a")
+                .with_file("/home/a/b.txt", b"b"),
+            VecSink::default(),
+            "alice",
+        );
+        assert!(client.remove_dir_all(Path::new("/home/a")).is_ok());
+        let (_, sink) = client.finish().unwrap();
+        let records: Vec<_> = sink
+            .0
+            .iter()
+            .map(|x| (x.operation, x.path.clone()))
+            .collect();
+        assert_eq!(
+            records,
+            vec![("remove_dir_all", Some(PathBuf::from("/home/a")))]
+        );
+    }
+
+    #[test]
+    fn should_audit_commands() {
+        let mut client = AuditedFs::new(
+            MemoryFs::connected().with_exec("rm -rf /home", 0, ""),
+            VecSink::default(),
+            "alice",
+        );
+        assert!(client.exec("rm -rf /home").is_ok());
+        assert!(client.connect_with(&ConnectOpts::default()).is_err());
+        let (_, sink) = client.finish().unwrap();
+        let records: Vec<_> = sink
+            .0
+            .iter()
+            .map(|x| (x.operation, x.path.clone(), x.command.clone()))
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                ("exec", None, Some("rm -rf /home".to_string())),
+                ("connect_with", None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_audit_read_lines() {
+        let mut client = AuditedFs::new(
+            MemoryFs::connected().with_file("/a.txt", b"a\nb\n"),
+            VecSink::default(),
+            "alice",
+        );
+        assert_eq!(
+            client
+                .read_lines(Path::new("/a.txt"))
+                .unwrap()
+                .collect::<RemoteResult<Vec<_>>>()
+                .unwrap(),
+            vec!["a".to_string(), "b".to_string()]
+        );
+        let (_, sink) = client.finish().unwrap();
+        assert_eq!(sink.0.len(), 1);
+        assert_eq!(sink.0[0].operation, "read_lines");
+        assert_eq!(sink.0[0].path.as_deref(), Some(Path::new("/a.txt")));
+    }
+}
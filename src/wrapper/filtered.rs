@@ -0,0 +1,623 @@
+//! ## Filtered
+//!
+//! a `RemoteFs` decorator applying glob rules to paths
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use wildmatch::WildMatch;
+
+use crate::fs::{
+    Capabilities, ConnectOpts, Metadata, ReadStream, Semantics, UnixPex, WalkDirOpts, Welcome,
+    WriteReport, WriteStream,
+};
+use crate::utils::path::{absolutize, normalize};
+use crate::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Glob rules applied by `FilteredFs`. Patterns support wildcards (`*`, `?`).
+///
+/// A pattern containing `/` is matched against the absolute path; any other pattern is matched against the file name,
+/// and, for `exclude` and `deny_write`, against the name of each parent directory too
+/// (so `.git` hides a `.git` directory and all its content)
+#[derive(Debug, Clone, Default)]
+pub struct FilterRules {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    deny_write: Vec<String>,
+}
+
+impl FilterRules {
+    /// Show only files matching `pattern` (or any other include pattern). Directories are always shown
+    pub fn include<S: ToString>(mut self, pattern: S) -> Self {
+        self.include.push(pattern.to_string());
+        self
+    }
+
+    /// Hide files matching `pattern`; hidden files are not listed and behave as not existing
+    pub fn exclude<S: ToString>(mut self, pattern: S) -> Self {
+        self.exclude.push(pattern.to_string());
+        self
+    }
+
+    /// Deny write operations on files matching `pattern`
+    pub fn deny_write<S: ToString>(mut self, pattern: S) -> Self {
+        self.deny_write.push(pattern.to_string());
+        self
+    }
+
+    /// Returns whether `path` is visible. `is_dir` tells whether include patterns apply
+    pub fn is_visible(&self, path: &Path, is_dir: bool) -> bool {
+        let included =
+            is_dir || self.include.is_empty() || self.include.iter().any(|x| matches_name(x, path));
+        included && !self.exclude.iter().any(|x| matches_any(x, path))
+    }
+
+    /// Returns whether write operations on `path` are denied
+    pub fn is_write_denied(&self, path: &Path) -> bool {
+        self.deny_write.iter().any(|x| matches_any(x, path))
+    }
+
+    /// Returns whether no rule is set
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.deny_write.is_empty()
+    }
+}
+
+/// Match `pattern` against the path, if it contains `/`, or against the file name otherwise
+fn matches_name(pattern: &str, path: &Path) -> bool {
+    if pattern.contains('/') {
+        WildMatch::new(pattern).matches(path.to_string_lossy().as_ref())
+    } else {
+        path.file_name()
+            .map(|x| WildMatch::new(pattern).matches(x.to_string_lossy().as_ref()))
+            .unwrap_or(false)
+    }
+}
+
+/// Match `pattern` against the path, if it contains `/`, or against any of its components otherwise
+fn matches_any(pattern: &str, path: &Path) -> bool {
+    if pattern.contains('/') {
+        let matcher = WildMatch::new(pattern);
+        path.ancestors()
+            .any(|x| matcher.matches(x.to_string_lossy().as_ref()))
+    } else {
+        let matcher = WildMatch::new(pattern);
+        path.iter()
+            .any(|x| matcher.matches(x.to_string_lossy().as_ref()))
+    }
+}
+
+/// A `RemoteFs` decorator which applies `FilterRules` to all operations and listing results,
+/// e.g. to hide `.git` directories or to block writes to `*.exe` files.
+///
+/// Operations on hidden paths fail with `NoSuchFileOrDirectory`, while write operations on denied paths fail with `PexError`.
+/// Symbolic links, copies and moves which would make hidden entries, or entries whose writing is denied,
+/// reachable through other paths fail with `PexError`.
+///
+/// Since the paths accessed by a command can't be filtered, `exec` fails with `PexError` if any rule is set,
+/// and `capabilities` reports it as unsupported, so that the default implementations of `RemoteFs` which run commands
+/// (e.g. `grep` and `checksum`) go through the filtered operations instead
+pub struct FilteredFs<T: RemoteFs> {
+    inner: T,
+    rules: FilterRules,
+    wrkdir: Option<PathBuf>,
+}
+
+impl<T: RemoteFs> FilteredFs<T> {
+    /// Instantiates a new `FilteredFs`, applying `rules` to `inner`
+    pub fn new(inner: T, rules: FilterRules) -> Self {
+        Self {
+            inner,
+            rules,
+            wrkdir: None,
+        }
+    }
+
+    /// Get a reference to the inner file system
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Consume the decorator, returning the inner file system
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Get the absolute, normalized path of `path`
+    fn resolve(&mut self, path: &Path) -> RemoteResult<PathBuf> {
+        let wrkdir = match self.wrkdir.clone() {
+            Some(wrkdir) => wrkdir,
+            None => {
+                let wrkdir = self.inner.pwd()?;
+                self.wrkdir = Some(wrkdir.clone());
+                wrkdir
+            }
+        };
+        Ok(normalize(absolutize(wrkdir.as_path(), path).as_path()))
+    }
+
+    /// Check whether `path` is visible; include rules are applied only if `is_dir` is false
+    fn check_visible(&mut self, path: &Path, is_dir: bool) -> RemoteResult<()> {
+        let path = self.resolve(path)?;
+        match self.rules.is_visible(path.as_path(), is_dir) {
+            true => Ok(()),
+            false => Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory).with_path(path)),
+        }
+    }
+
+    /// Check whether `path` is visible and writable
+    fn check_writable(&mut self, path: &Path, is_dir: bool) -> RemoteResult<()> {
+        self.check_visible(path, is_dir)?;
+        let path = self.resolve(path)?;
+        match self.rules.is_write_denied(path.as_path()) {
+            true => Err(
+                RemoteError::new_ex(RemoteErrorType::PexError, "writing is denied").with_path(path),
+            ),
+            false => Ok(()),
+        }
+    }
+
+    /// Check whether the file at `path` is visible, according to its type
+    fn check_file(&mut self, file: &File) -> RemoteResult<()> {
+        self.check_visible(file.path(), file.is_dir())
+    }
+
+    /// Get the entries in the tree at `dir`, each with the path it maps to under `dest`.
+    /// If no rule is set, or `dir` is not a directory, no entry is returned
+    fn tree(&mut self, dir: &Path, dest: &Path) -> RemoteResult<Vec<(PathBuf, PathBuf, bool)>> {
+        let dir = self.resolve(dir)?;
+        let dest = self.resolve(dest)?;
+        if self.rules.is_empty() || !self.inner.stat(dir.as_path())?.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut tree = Vec::new();
+        for entry in self
+            .inner
+            .walk_dir(dir.as_path(), &WalkDirOpts::default())?
+        {
+            let path = self.resolve(entry.path())?;
+            let mapped = dest.join(path.strip_prefix(dir.as_path()).unwrap_or(path.as_path()));
+            tree.push((path, mapped, entry.is_dir()));
+        }
+        Ok(tree)
+    }
+
+    /// Check that the tree at `src` contains no hidden entries and, if `writable`, no entries whose writing is denied,
+    /// and that all the paths it maps to under `dest` are writable
+    fn check_tree(&mut self, src: &Path, dest: &Path, writable: bool) -> RemoteResult<()> {
+        for (path, mapped, is_dir) in self.tree(src, dest)? {
+            if !self.rules.is_visible(path.as_path(), is_dir) {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::PexError,
+                    "the tree contains filtered entries",
+                )
+                .with_path(self.resolve(src)?));
+            }
+            if writable && self.rules.is_write_denied(path.as_path()) {
+                return Err(
+                    RemoteError::new_ex(RemoteErrorType::PexError, "writing is denied")
+                        .with_path(path),
+                );
+            }
+            self.check_writable(mapped.as_path(), is_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Check that `target`, and the tree under it, can't be reached through the link at `link` escaping the rules:
+    /// hidden entries must be hidden through the link too, and so must entries whose writing is denied
+    fn check_link_target(&mut self, link: &Path, target: &Path) -> RemoteResult<()> {
+        let link = self.resolve(link)?;
+        let target =
+            normalize(absolutize(link.parent().unwrap_or(Path::new("/")), target).as_path());
+        let is_dir = self
+            .inner
+            .stat(target.as_path())
+            .map(|x| x.is_dir())
+            .unwrap_or(false);
+        self.check_writable(target.as_path(), is_dir)?;
+        for (path, mapped, is_dir) in self.tree(target.as_path(), link.as_path())? {
+            let hidden = !self.rules.is_visible(path.as_path(), is_dir)
+                && self.rules.is_visible(mapped.as_path(), is_dir);
+            let denied = self.rules.is_write_denied(path.as_path())
+                && !self.rules.is_write_denied(mapped.as_path());
+            if hidden || denied {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::PexError,
+                    "the link target contains filtered entries",
+                )
+                .with_path(link));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: RemoteFs> RemoteFs for FilteredFs<T> {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.wrkdir = None;
+        self.inner.connect()
+    }
+
+    fn connect_with(&mut self, opts: &ConnectOpts) -> RemoteResult<Welcome> {
+        self.wrkdir = None;
+        self.inner.connect_with(opts)
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn capabilities(&mut self) -> Capabilities {
+        let mut capabilities = self.inner.capabilities();
+        if !self.rules.is_empty() {
+            capabilities.exec = false;
+        }
+        capabilities
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.inner.summary()
+    }
+
+    fn semantics(&self) -> Semantics {
+        self.inner.semantics()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        let wrkdir = self.inner.pwd()?;
+        self.wrkdir = Some(wrkdir.clone());
+        Ok(wrkdir)
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.check_visible(dir, true)?;
+        let wrkdir = self.inner.change_dir(dir)?;
+        self.wrkdir = Some(wrkdir.clone());
+        Ok(wrkdir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.check_visible(path, true)?;
+        let entries = self.inner.list_dir(path)?;
+        let mut visible = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let path = self.resolve(entry.path())?;
+            if self.rules.is_visible(path.as_path(), entry.is_dir()) {
+                visible.push(entry);
+            }
+        }
+        Ok(visible)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.check_visible(path, true)?;
+        let file = self.inner.stat(path)?;
+        self.check_file(&file)?;
+        Ok(file)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let is_dir = metadata.is_dir();
+        self.check_writable(path, is_dir)?;
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.stat(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.check_writable(path, false)?;
+        self.inner.remove_file(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.check_writable(path, true)?;
+        self.inner.remove_dir(path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.check_writable(path, true)?;
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.check_writable(path, false)?;
+        self.check_link_target(path, target)?;
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let src_file = self.stat(src)?;
+        self.check_writable(dest, src_file.is_dir())?;
+        self.check_tree(src, dest, false)?;
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let src_file = self.stat(src)?;
+        self.check_writable(src, src_file.is_dir())?;
+        self.check_writable(dest, src_file.is_dir())?;
+        self.check_tree(src, dest, true)?;
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        match self.rules.is_empty() {
+            true => self.inner.exec(cmd),
+            false => Err(RemoteError::new_ex(
+                RemoteErrorType::PexError,
+                "commands can't be executed while filter rules are set",
+            )
+            .with_op("exec")),
+        }
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.check_writable(path, false)?;
+        self.inner.append(path, metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.check_writable(path, false)?;
+        self.inner.create(path, metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.check_visible(path, false)?;
+        self.inner.open(path)
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_written_ext(&mut self, writable: WriteStream) -> RemoteResult<WriteReport> {
+        self.inner.on_written_ext(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.check_writable(path, false)?;
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.check_writable(path, false)?;
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.check_visible(src, false)?;
+        self.inner.open_file(src, dest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::GrepOpts;
+    use crate::mock::MemoryFs;
+
+    fn client() -> FilteredFs<MemoryFs> {
+        client_with(MemoryFs::connected())
+    }
+
+    fn client_with(fs: MemoryFs) -> FilteredFs<MemoryFs> {
+        FilteredFs::new(
+            fs.with_dir("/repo")
+                .with_dir("/repo/.git")
+                .with_file("/repo/.git/HEAD", b"ref: refs/heads/main")
+                .with_file("/repo/main.rs", b"fn main() {}")
+                .with_file("/repo/setup.exe", b"MZ")
+                .with_file("/repo/README.md", b"# repo"),
+            FilterRules::default()
+                .exclude(".git")
+                .exclude("/repo/README.*")
+                .deny_write("*.exe"),
+        )
+    }
+
+    #[test]
+    fn should_filter_listing() {
+        let mut client = client();
+        let names: Vec<String> = client
+            .list_dir(Path::new("/repo"))
+            .unwrap()
+            .iter()
+            .map(File::name)
+            .collect();
+        assert_eq!(names, vec!["main.rs", "setup.exe"]);
+    }
+
+    #[test]
+    fn should_hide_excluded_paths() {
+        let mut client = client();
+        assert_eq!(client.exists(Path::new("/repo/.git/HEAD")).unwrap(), false);
+        assert_eq!(client.exists(Path::new("/repo/README.md")).unwrap(), false);
+        assert_eq!(client.exists(Path::new("/repo/main.rs")).unwrap(), true);
+        assert_eq!(
+            client
+                .open(Path::new("/repo/.git/HEAD"))
+                .err()
+                .unwrap()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        assert!(client.change_dir(Path::new("/repo")).is_ok());
+        assert_eq!(
+            client.stat(Path::new(".git")).unwrap_err().kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+    }
+
+    #[test]
+    fn should_deny_writes() {
+        let mut client = client();
+        assert_eq!(
+            client
+                .remove_file(Path::new("/repo/setup.exe"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            client
+                .create(Path::new("/repo/virus.exe"), &Metadata::default())
+                .err()
+                .unwrap()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert!(client.remove_file(Path::new("/repo/main.rs")).is_ok());
+    }
+
+    #[test]
+    fn should_check_symlink_targets() {
+        let mut client = client_with(MemoryFs::connected().with_dir("/links"));
+        assert_eq!(
+            client
+                .symlink(Path::new("/repo/l"), Path::new(".git"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        assert_eq!(
+            client
+                .symlink(Path::new("/links/setup"), Path::new("../repo/setup.exe"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        // README.md would be reachable as /links/repo/README.md
+        assert_eq!(
+            client
+                .symlink(Path::new("/links/repo"), Path::new("../repo"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert!(client
+            .symlink(Path::new("/links/main.rs"), Path::new("../repo/main.rs"))
+            .is_ok());
+    }
+
+    #[test]
+    fn should_not_copy_or_move_trees_with_filtered_entries() {
+        let mut client = client_with(
+            MemoryFs::connected()
+                .with_dir("/repo/src")
+                .with_file("/repo/src/lib.rs", b"")
+                .with_dir("/repo/bin")
+                .with_file("/repo/bin/tool.exe", b"MZ"),
+        );
+        assert_eq!(
+            client
+                .mov(Path::new("/repo"), Path::new("/backup"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            client
+                .mov(Path::new("/repo/bin"), Path::new("/tools"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            client
+                .copy(Path::new("/repo/bin"), Path::new("/tools"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert!(client.inner.exists(Path::new("/repo/.git/HEAD")).unwrap());
+        assert!(client
+            .inner
+            .exists(Path::new("/repo/bin/tool.exe"))
+            .unwrap());
+        assert!(client
+            .mov(Path::new("/repo/src"), Path::new("/src"))
+            .is_ok());
+        assert!(client.exists(Path::new("/src/lib.rs")).unwrap());
+    }
+
+    #[test]
+    fn should_not_exec_with_rules() {
+        let mut client = FilteredFs::new(
+            MemoryFs::connected()
+                .with_dir("/repo")
+                .with_dir("/repo/.git")
+                .with_file("/repo/.git/HEAD", b"ref: refs/heads/main")
+                .with_exec(
+                    "grep -rnHIF --null -e ref /repo",
+                    0,
+                    "/repo/.git/HEAD\x001:ref\n",
+                ),
+            FilterRules::default().exclude(".git"),
+        );
+        assert_eq!(client.capabilities().exec, false);
+        assert_eq!(
+            client.exec("cat /repo/.git/HEAD").unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+        assert!(client
+            .grep(Path::new("/repo"), "ref", &GrepOpts::default())
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            client
+                .tail(Path::new("/repo/.git/HEAD"), 1)
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        let mut client = FilteredFs::new(
+            MemoryFs::connected().with_exec("true", 0, ""),
+            FilterRules::default(),
+        );
+        assert_eq!(client.capabilities().exec, true);
+        assert!(client.exec("true").is_ok());
+    }
+
+    #[test]
+    fn should_apply_include_rules_to_files_only() {
+        let mut client = FilteredFs::new(
+            MemoryFs::connected()
+                .with_dir("/docs")
+                .with_dir("/docs/guide")
+                .with_file("/docs/guide/intro.md", b"")
+                .with_file("/docs/logo.png", b""),
+            FilterRules::default().include("*.md"),
+        );
+        assert_eq!(client.list_dir(Path::new("/docs")).unwrap().len(), 1);
+        assert_eq!(client.list_dir(Path::new("/docs/guide")).unwrap().len(), 1);
+        assert_eq!(client.exists(Path::new("/docs/logo.png")).unwrap(), false);
+    }
+}
@@ -0,0 +1,574 @@
+//! ## Forward
+//!
+//! a macro implementing the methods of `RemoteFs` in decorators, forwarding them to the inner file system
+
+/// Implement the methods of `RemoteFs` in a decorator, forwarding each of them to `self.inner` through the macro `$hook`,
+/// so that the implementations of the inner file system are used instead of the default ones.
+/// New methods of `RemoteFs` must be listed here, so that all the decorators forward them.
+///
+/// `$hook` is invoked for each method and must expand to the method definition:
+///
+/// - `$hook!(@plain self, method, (args) -> ret, call)` for the methods which can't fail
+///   (`is_connected`, `capabilities`, `summary`, `semantics` and `validate_path`);
+/// - `$hook!(self, method, [paths], [command], (args) -> ret, call)` for all the others, which return a `RemoteResult`,
+///   where `paths` are the arguments naming the paths the method operates on (e.g. `src` and `dest` for `copy`,
+///   `path` and `target` for `symlink`),
+///   and `command` the command run by `exec`, `exec_with_env` and `exec_bytes`.
+///
+/// `call` is the call of the same method on `self.inner`, with the same arguments.
+/// A decorator implements a method on its own by matching its name in `$hook`
+macro_rules! forward_remote_fs {
+    ($hook:ident) => {
+        $hook!(@plain self, is_connected, (&mut self) -> bool, self.inner.is_connected());
+        $hook!(@plain self, capabilities, (&mut self) -> $crate::fs::Capabilities, self.inner.capabilities());
+        $hook!(@plain self, summary, (&self) -> Option<String>, self.inner.summary());
+        $hook!(@plain self, semantics, (&self) -> $crate::fs::Semantics, self.inner.semantics());
+        $hook!(
+            @plain self,
+            validate_path,
+            (&self, path: &::std::path::Path) -> Result<(), $crate::fs::PathError>,
+            self.inner.validate_path(path)
+        );
+        $hook!(
+            self,
+            connect,
+            [],
+            [],
+            (&mut self) -> $crate::RemoteResult<$crate::fs::Welcome>,
+            self.inner.connect()
+        );
+        $hook!(
+            self,
+            connect_with,
+            [],
+            [],
+            (&mut self, opts: &$crate::fs::ConnectOpts) -> $crate::RemoteResult<$crate::fs::Welcome>,
+            self.inner.connect_with(opts)
+        );
+        $hook!(self, disconnect, [], [], (&mut self) -> $crate::RemoteResult<()>, self.inner.disconnect());
+        $hook!(
+            self,
+            ping,
+            [],
+            [],
+            (&mut self) -> $crate::RemoteResult<::std::time::Duration>,
+            self.inner.ping()
+        );
+        $hook!(
+            self,
+            server_time,
+            [],
+            [],
+            (&mut self) -> $crate::RemoteResult<::std::time::SystemTime>,
+            self.inner.server_time()
+        );
+        $hook!(
+            self,
+            home_dir,
+            [],
+            [],
+            (&mut self) -> $crate::RemoteResult<::std::path::PathBuf>,
+            self.inner.home_dir()
+        );
+        $hook!(
+            self,
+            pwd,
+            [],
+            [],
+            (&mut self) -> $crate::RemoteResult<::std::path::PathBuf>,
+            self.inner.pwd()
+        );
+        $hook!(
+            self,
+            change_dir,
+            [dir],
+            [],
+            (&mut self, dir: &::std::path::Path) -> $crate::RemoteResult<::std::path::PathBuf>,
+            self.inner.change_dir(dir)
+        );
+        $hook!(
+            self,
+            list_dir,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<Vec<$crate::File>>,
+            self.inner.list_dir(path)
+        );
+        $hook!(
+            self,
+            list_dir_opts,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, opts: &$crate::fs::ListDirOpts)
+                -> $crate::RemoteResult<Vec<$crate::File>>,
+            self.inner.list_dir_opts(path, opts)
+        );
+        $hook!(
+            self,
+            list_dir_cb,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                on_entry: &mut dyn FnMut($crate::File) -> ::std::ops::ControlFlow<()>,
+            ) -> $crate::RemoteResult<()>,
+            self.inner.list_dir_cb(path, on_entry)
+        );
+        $hook!(
+            self,
+            dir_version,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<Option<String>>,
+            self.inner.dir_version(path)
+        );
+        $hook!(
+            self,
+            stat,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<$crate::File>,
+            self.inner.stat(path)
+        );
+        $hook!(
+            self,
+            setstat,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, metadata: $crate::fs::Metadata) -> $crate::RemoteResult<()>,
+            self.inner.setstat(path, metadata)
+        );
+        $hook!(
+            self,
+            set_permissions,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, permissions: $crate::fs::Permissions)
+                -> $crate::RemoteResult<()>,
+            self.inner.set_permissions(path, permissions)
+        );
+        $hook!(
+            self,
+            set_owner_recursive,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, uid: Option<u32>, gid: Option<u32>) -> $crate::RemoteResult<()>,
+            self.inner.set_owner_recursive(path, uid, gid)
+        );
+        $hook!(
+            self,
+            set_mode_recursive,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, mode: $crate::fs::UnixPex) -> $crate::RemoteResult<()>,
+            self.inner.set_mode_recursive(path, mode)
+        );
+        $hook!(
+            self,
+            can_read,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<bool>,
+            self.inner.can_read(path)
+        );
+        $hook!(
+            self,
+            can_write,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<bool>,
+            self.inner.can_write(path)
+        );
+        $hook!(
+            self,
+            touch,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                accessed: ::std::time::SystemTime,
+                modified: ::std::time::SystemTime,
+            ) -> $crate::RemoteResult<()>,
+            self.inner.touch(path, accessed, modified)
+        );
+        $hook!(
+            self,
+            exists,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<bool>,
+            self.inner.exists(path)
+        );
+        $hook!(
+            self,
+            remove_file,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<()>,
+            self.inner.remove_file(path)
+        );
+        $hook!(
+            self,
+            remove_dir,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<()>,
+            self.inner.remove_dir(path)
+        );
+        $hook!(
+            self,
+            remove_dir_all,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<()>,
+            self.inner.remove_dir_all(path)
+        );
+        $hook!(
+            self,
+            remove_dir_all_with,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                policy: $crate::fs::ErrorPolicy,
+                on_event: &mut dyn FnMut($crate::fs::FsEvent),
+            ) -> $crate::RemoteResult<$crate::fs::BulkReport>,
+            self.inner.remove_dir_all_with(path, policy, on_event)
+        );
+        $hook!(
+            self,
+            walk_dir,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, opts: &$crate::fs::WalkDirOpts)
+                -> $crate::RemoteResult<Vec<$crate::File>>,
+            self.inner.walk_dir(path, opts)
+        );
+        $hook!(
+            self,
+            checksum,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, algorithm: $crate::fs::ChecksumAlgorithm)
+                -> $crate::RemoteResult<String>,
+            self.inner.checksum(path, algorithm)
+        );
+        $hook!(
+            self,
+            snapshot,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                opts: &$crate::fs::WalkDirOpts,
+                checksum: Option<$crate::fs::ChecksumAlgorithm>,
+            ) -> $crate::RemoteResult<$crate::fs::Snapshot>,
+            self.inner.snapshot(path, opts, checksum)
+        );
+        $hook!(
+            self,
+            create_dir,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, mode: $crate::fs::UnixPex) -> $crate::RemoteResult<()>,
+            self.inner.create_dir(path, mode)
+        );
+        $hook!(
+            self,
+            create_dir_ext,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, mode: $crate::fs::UnixPex, strict_mode: bool)
+                -> $crate::RemoteResult<$crate::File>,
+            self.inner.create_dir_ext(path, mode, strict_mode)
+        );
+        $hook!(
+            self,
+            symlink,
+            [path, target],
+            [],
+            (&mut self, path: &::std::path::Path, target: &::std::path::Path) -> $crate::RemoteResult<()>,
+            self.inner.symlink(path, target)
+        );
+        $hook!(
+            self,
+            copy,
+            [src, dest],
+            [],
+            (&mut self, src: &::std::path::Path, dest: &::std::path::Path) -> $crate::RemoteResult<()>,
+            self.inner.copy(src, dest)
+        );
+        $hook!(
+            self,
+            copy_with,
+            [src, dest],
+            [],
+            (
+                &mut self,
+                src: &::std::path::Path,
+                dest: &::std::path::Path,
+                opts: &$crate::fs::CopyOpts,
+            ) -> $crate::RemoteResult<()>,
+            self.inner.copy_with(src, dest, opts)
+        );
+        $hook!(
+            self,
+            mov,
+            [src, dest],
+            [],
+            (&mut self, src: &::std::path::Path, dest: &::std::path::Path) -> $crate::RemoteResult<()>,
+            self.inner.mov(src, dest)
+        );
+        $hook!(
+            self,
+            move_with,
+            [src, dest],
+            [],
+            (
+                &mut self,
+                src: &::std::path::Path,
+                dest: &::std::path::Path,
+                opts: &$crate::fs::MoveOpts,
+            ) -> $crate::RemoteResult<()>,
+            self.inner.move_with(src, dest, opts)
+        );
+        $hook!(
+            self,
+            exec,
+            [],
+            [cmd],
+            (&mut self, cmd: &str) -> $crate::RemoteResult<(u32, String)>,
+            self.inner.exec(cmd)
+        );
+        $hook!(
+            self,
+            exec_with_env,
+            [],
+            [cmd],
+            (&mut self, cmd: &str, env: &$crate::shell::ShellEnv) -> $crate::RemoteResult<(u32, String)>,
+            self.inner.exec_with_env(cmd, env)
+        );
+        $hook!(
+            self,
+            exec_bytes,
+            [],
+            [cmd],
+            (&mut self, cmd: &str) -> $crate::RemoteResult<$crate::fs::ExecOutput>,
+            self.inner.exec_bytes(cmd)
+        );
+        $hook!(
+            self,
+            append,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, metadata: &$crate::fs::Metadata)
+                -> $crate::RemoteResult<$crate::fs::WriteStream>,
+            self.inner.append(path, metadata)
+        );
+        $hook!(
+            self,
+            create,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, metadata: &$crate::fs::Metadata)
+                -> $crate::RemoteResult<$crate::fs::WriteStream>,
+            self.inner.create(path, metadata)
+        );
+        $hook!(
+            self,
+            open,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path) -> $crate::RemoteResult<$crate::fs::ReadStream>,
+            self.inner.open(path)
+        );
+        $hook!(
+            self,
+            append_with,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                metadata: &$crate::fs::Metadata,
+                opts: &$crate::fs::StreamOpts,
+            ) -> $crate::RemoteResult<$crate::fs::WriteStream>,
+            self.inner.append_with(path, metadata, opts)
+        );
+        $hook!(
+            self,
+            create_with,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                metadata: &$crate::fs::Metadata,
+                opts: &$crate::fs::StreamOpts,
+            ) -> $crate::RemoteResult<$crate::fs::WriteStream>,
+            self.inner.create_with(path, metadata, opts)
+        );
+        $hook!(
+            self,
+            open_with,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, opts: &$crate::fs::StreamOpts)
+                -> $crate::RemoteResult<$crate::fs::ReadStream>,
+            self.inner.open_with(path, opts)
+        );
+        $hook!(
+            self,
+            read_lines,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path)
+                -> $crate::RemoteResult<Box<dyn Iterator<Item = $crate::RemoteResult<String>> + '_>>,
+            self.inner.read_lines(path)
+        );
+        $hook!(
+            self,
+            tail,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, n: usize) -> $crate::RemoteResult<Vec<String>>,
+            self.inner.tail(path, n)
+        );
+        $hook!(
+            self,
+            grep,
+            [path],
+            [],
+            (&mut self, path: &::std::path::Path, pattern: &str, opts: &$crate::fs::GrepOpts)
+                -> $crate::RemoteResult<Vec<$crate::fs::GrepMatch>>,
+            self.inner.grep(path, pattern, opts)
+        );
+        $hook!(
+            self,
+            zip_dir,
+            [dir, archive],
+            [],
+            (&mut self, dir: &::std::path::Path, archive: &::std::path::Path) -> $crate::RemoteResult<()>,
+            self.inner.zip_dir(dir, archive)
+        );
+        $hook!(
+            self,
+            unzip,
+            [archive, dest],
+            [],
+            (&mut self, archive: &::std::path::Path, dest: &::std::path::Path) -> $crate::RemoteResult<()>,
+            self.inner.unzip(archive, dest)
+        );
+        $hook!(
+            self,
+            on_written,
+            [],
+            [],
+            (&mut self, writable: $crate::fs::WriteStream) -> $crate::RemoteResult<()>,
+            self.inner.on_written(writable)
+        );
+        $hook!(
+            self,
+            on_written_ext,
+            [],
+            [],
+            (&mut self, writable: $crate::fs::WriteStream) -> $crate::RemoteResult<$crate::fs::WriteReport>,
+            self.inner.on_written_ext(writable)
+        );
+        $hook!(
+            self,
+            on_read,
+            [],
+            [],
+            (&mut self, readable: $crate::fs::ReadStream) -> $crate::RemoteResult<()>,
+            self.inner.on_read(readable)
+        );
+        $hook!(
+            self,
+            append_file,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                metadata: &$crate::fs::Metadata,
+                reader: Box<dyn ::std::io::Read + Send>,
+            ) -> $crate::RemoteResult<u64>,
+            self.inner.append_file(path, metadata, reader)
+        );
+        $hook!(
+            self,
+            resume_upload,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                metadata: &$crate::fs::Metadata,
+                reader: Box<dyn $crate::fs::stream::ReadAndSeek>,
+                verify: Option<$crate::fs::ChecksumAlgorithm>,
+            ) -> $crate::RemoteResult<u64>,
+            self.inner.resume_upload(path, metadata, reader, verify)
+        );
+        $hook!(
+            self,
+            create_file,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                metadata: &$crate::fs::Metadata,
+                reader: Box<dyn ::std::io::Read + Send>,
+            ) -> $crate::RemoteResult<u64>,
+            self.inner.create_file(path, metadata, reader)
+        );
+        $hook!(
+            self,
+            create_file_ext,
+            [path],
+            [],
+            (
+                &mut self,
+                path: &::std::path::Path,
+                metadata: &$crate::fs::Metadata,
+                reader: Box<dyn ::std::io::Read + Send>,
+                strict_mode: bool,
+            ) -> $crate::RemoteResult<$crate::File>,
+            self.inner.create_file_ext(path, metadata, reader, strict_mode)
+        );
+        $hook!(
+            self,
+            open_file,
+            [src],
+            [],
+            (&mut self, src: &::std::path::Path, dest: Box<dyn ::std::io::Write + Send>)
+                -> $crate::RemoteResult<u64>,
+            self.inner.open_file(src, dest)
+        );
+        #[cfg(feature = "find")]
+        $hook!(
+            self,
+            find,
+            [],
+            [],
+            (&mut self, search: &str) -> $crate::RemoteResult<Vec<$crate::File>>,
+            self.inner.find(search)
+        );
+        #[cfg(feature = "find")]
+        $hook!(
+            self,
+            find_with,
+            [],
+            [],
+            (&mut self, search: &str, rules: &$crate::fs::IgnoreRules) -> $crate::RemoteResult<Vec<$crate::File>>,
+            self.inner.find_with(search, rules)
+        );
+    };
+}
+
+pub(crate) use forward_remote_fs;
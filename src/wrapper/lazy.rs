@@ -0,0 +1,170 @@
+//! ## Lazy
+//!
+//! a `RemoteFs` decorator connecting on first use
+
+use std::path::{Path, PathBuf};
+
+use super::forward::forward_remote_fs;
+use crate::fs::{Capabilities, ConnectOpts, Welcome};
+use crate::{RemoteFs, RemoteResult};
+
+/// A `RemoteFs` decorator which connects the inner file system on the first operation,
+/// and connects it again before the next operation if the connection is lost or closed with `disconnect`,
+/// restoring the working directory set before. There is no need to call `connect`.
+///
+/// `is_connected` doesn't connect: it returns whether the inner file system is connected at the moment.
+/// All the methods of `RemoteFs` are forwarded to the inner file system once connected,
+/// so that its implementations are used instead of the default ones
+pub struct LazyFs<T: RemoteFs> {
+    inner: T,
+    wrkdir: Option<PathBuf>,
+}
+
+impl<T: RemoteFs> LazyFs<T> {
+    /// Instantiates a new `LazyFs`, connecting `inner` on first use
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            wrkdir: None,
+        }
+    }
+
+    /// Get a reference to the inner file system
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Consume the decorator, returning the inner file system
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Connect the inner file system if it's not connected, restoring the working directory
+    fn ensure_connected(&mut self) -> RemoteResult<()> {
+        if self.inner.is_connected() {
+            return Ok(());
+        }
+        debug!("File system is not connected; connecting");
+        self.inner.connect()?;
+        if let Some(wrkdir) = self.wrkdir.clone() {
+            debug!("Restoring working directory {}", wrkdir.display());
+            self.inner.change_dir(wrkdir.as_path())?;
+        }
+        Ok(())
+    }
+}
+
+/// Forward a method to the inner file system, connecting it first.
+/// Closing the connection and finalizing streams don't connect
+macro_rules! forward_connected {
+    (@plain $this:ident, capabilities, $($rest:tt)*) => {};
+    (@plain $this:ident, $method:ident, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $call
+        }
+    };
+    ($this:ident, connect, $($rest:tt)*) => {};
+    ($this:ident, connect_with, $($rest:tt)*) => {};
+    ($this:ident, change_dir, $($rest:tt)*) => {};
+    ($this:ident, disconnect, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        forward_connected!(@plain $this, disconnect, $sig -> $ret, $call);
+    };
+    ($this:ident, on_written, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        forward_connected!(@plain $this, on_written, $sig -> $ret, $call);
+    };
+    ($this:ident, on_written_ext, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        forward_connected!(@plain $this, on_written_ext, $sig -> $ret, $call);
+    };
+    ($this:ident, on_read, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        forward_connected!(@plain $this, on_read, $sig -> $ret, $call);
+    };
+    ($this:ident, $method:ident, $paths:tt, $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $this.ensure_connected()?;
+            $call
+        }
+    };
+}
+
+impl<T: RemoteFs> RemoteFs for LazyFs<T> {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.wrkdir = None;
+        self.inner.connect()
+    }
+
+    fn connect_with(&mut self, opts: &ConnectOpts) -> RemoteResult<Welcome> {
+        self.wrkdir = None;
+        self.inner.connect_with(opts)
+    }
+
+    fn capabilities(&mut self) -> Capabilities {
+        if let Err(err) = self.ensure_connected() {
+            warn!("Could not connect to get capabilities: {}", err);
+        }
+        self.inner.capabilities()
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.ensure_connected()?;
+        let wrkdir = self.inner.change_dir(dir)?;
+        self.wrkdir = Some(wrkdir.clone());
+        Ok(wrkdir)
+    }
+
+    forward_remote_fs!(forward_connected);
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::{CopyOpts, Metadata, WalkDirOpts};
+    use crate::mock::MemoryFs;
+    use crate::RemoteErrorType;
+
+    #[test]
+    fn should_connect_on_first_use() {
+        let mut client = LazyFs::new(MemoryFs::default().with_dir("/home"));
+        assert_eq!(client.exists(Path::new("/home")).unwrap(), true);
+        assert!(client
+            .create_file(
+                Path::new("/home/a.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"hello".to_vec())),
+            )
+            .is_ok());
+        assert!(client.into_inner().is_connected());
+    }
+
+    #[test]
+    fn should_not_connect_on_is_connected() {
+        let mut client = LazyFs::new(MemoryFs::default().with_dir("/home"));
+        assert_eq!(client.is_connected(), false);
+        assert!(client
+            .walk_dir(Path::new("/home"), &WalkDirOpts::default())
+            .is_ok());
+        assert_eq!(client.is_connected(), true);
+        client.disconnect().unwrap();
+        assert_eq!(client.is_connected(), false);
+        assert!(client
+            .copy_with(Path::new("/home"), Path::new("/copy"), &CopyOpts::default())
+            .is_ok());
+        assert_eq!(client.is_connected(), true);
+    }
+
+    #[test]
+    fn should_reconnect_restoring_wrkdir() {
+        let mut client = LazyFs::new(MemoryFs::default().with_dir("/home"));
+        client.change_dir(Path::new("/home")).unwrap();
+        client.disconnect().unwrap();
+        assert_eq!(client.pwd().unwrap(), PathBuf::from("/home"));
+        assert_eq!(
+            client.connect().unwrap_err().kind,
+            RemoteErrorType::AlreadyConnected
+        );
+    }
+}
@@ -0,0 +1,779 @@
+//! ## Limited
+//!
+//! a `RemoteFs` decorator enforcing client-side quotas and limits
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::fs::stream::{ReadAndSeek, WriteAndSeek};
+use crate::fs::{
+    BulkReport, Capabilities, ConnectOpts, ErrorPolicy, FsEvent, Metadata, ReadStream, Semantics,
+    UnixPex, WalkDirOpts, Welcome, WriteReport, WriteStream,
+};
+use crate::utils::path::{absolutize, normalize};
+use crate::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// Limits enforced by `LimitedFs`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum size of a single uploaded file, in bytes
+    pub max_file_size: Option<u64>,
+    /// Maximum amount of bytes transferred (uploaded and downloaded) during the session
+    pub max_session_bytes: Option<u64>,
+    /// Maximum amount of operations per minute
+    pub max_ops_per_minute: Option<usize>,
+    /// Maximum amount of files and directories removed during the session
+    pub max_deletions: Option<usize>,
+    /// Path prefixes which can't be accessed
+    pub denied_prefixes: Vec<PathBuf>,
+}
+
+impl Limits {
+    /// Set maximum size of a single uploaded file
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Set maximum amount of bytes transferred during the session
+    pub fn max_session_bytes(mut self, bytes: u64) -> Self {
+        self.max_session_bytes = Some(bytes);
+        self
+    }
+
+    /// Set maximum amount of operations per minute
+    pub fn max_ops_per_minute(mut self, ops: usize) -> Self {
+        self.max_ops_per_minute = Some(ops);
+        self
+    }
+
+    /// Set maximum amount of files and directories removed during the session
+    pub fn max_deletions(mut self, deletions: usize) -> Self {
+        self.max_deletions = Some(deletions);
+        self
+    }
+
+    /// Deny access to `prefix` and all its content
+    pub fn deny_prefix<P: AsRef<Path>>(mut self, prefix: P) -> Self {
+        self.denied_prefixes.push(normalize(prefix.as_ref()));
+        self
+    }
+}
+
+/// A `RemoteFs` decorator enforcing client-side `Limits`, as a guardrail for services embedding remotefs.
+/// Operations exceeding a quota fail with `QuotaExceeded`, while operations on denied paths fail with `PexError`.
+///
+/// Transfer limits are enforced on the streams too, which fail with an `io::Error` wrapping a `QuotaExceeded` error.
+///
+/// Before removing a tree with `remove_dir_all`, its entries are counted with `walk_dir`,
+/// so that nothing is removed if removing all of them would exceed the deletion limit.
+///
+/// Since the paths accessed by a command can't be checked, `exec` fails with `PexError` if any path prefix is denied,
+/// and `capabilities` reports it as unsupported, so that the default implementations of `RemoteFs` which run commands
+/// (e.g. `tail` and `checksum`) go through the checked operations instead
+pub struct LimitedFs<T: RemoteFs> {
+    inner: T,
+    limits: Limits,
+    deletions: usize,
+    ops: VecDeque<Instant>,
+    transferred: Arc<AtomicU64>,
+    wrkdir: Option<PathBuf>,
+}
+
+impl<T: RemoteFs> LimitedFs<T> {
+    /// Instantiates a new `LimitedFs`, enforcing `limits` on `inner`
+    pub fn new(inner: T, limits: Limits) -> Self {
+        Self {
+            inner,
+            limits,
+            deletions: 0,
+            ops: VecDeque::new(),
+            transferred: Arc::new(AtomicU64::new(0)),
+            wrkdir: None,
+        }
+    }
+
+    /// Returns the amount of files and directories removed during the session so far
+    pub fn deletions(&self) -> usize {
+        self.deletions
+    }
+
+    /// Returns the amount of bytes transferred during the session so far
+    pub fn transferred(&self) -> u64 {
+        self.transferred.load(Ordering::Relaxed)
+    }
+
+    /// Get a reference to the inner file system
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Consume the decorator, returning the inner file system
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Account a new operation, failing if the rate limit is exceeded
+    fn op(&mut self) -> RemoteResult<()> {
+        if let Some(max) = self.limits.max_ops_per_minute {
+            let now = Instant::now();
+            while self
+                .ops
+                .front()
+                .map(|x| now.duration_since(*x) >= Duration::from_secs(60))
+                .unwrap_or(false)
+            {
+                self.ops.pop_front();
+            }
+            if self.ops.len() >= max {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::QuotaExceeded,
+                    format!("more than {max} operations per minute"),
+                ));
+            }
+            self.ops.push_back(now);
+        }
+        Ok(())
+    }
+
+    /// Resolve `path` against the working directory, normalizing it
+    fn resolve(&mut self, path: &Path) -> RemoteResult<PathBuf> {
+        let wrkdir = match self.wrkdir.clone() {
+            Some(wrkdir) => wrkdir,
+            None => {
+                let wrkdir = self.inner.pwd()?;
+                self.wrkdir = Some(wrkdir.clone());
+                wrkdir
+            }
+        };
+        Ok(normalize(absolutize(wrkdir.as_path(), path).as_path()))
+    }
+
+    /// Account a new operation on `path`, failing if the path is denied
+    fn op_on(&mut self, path: &Path) -> RemoteResult<()> {
+        self.op()?;
+        if self.limits.denied_prefixes.is_empty() {
+            return Ok(());
+        }
+        let path = self.resolve(path)?;
+        match self
+            .limits
+            .denied_prefixes
+            .iter()
+            .any(|x| path.starts_with(x))
+        {
+            true => Err(
+                RemoteError::new_ex(RemoteErrorType::PexError, "access is denied").with_path(path),
+            ),
+            false => Ok(()),
+        }
+    }
+
+    /// Check a new deletion of `path`, failing if the deletion limit is reached.
+    /// The deletion is counted by the caller, once the inner file system has removed the path
+    fn delete(&mut self, path: &Path) -> RemoteResult<()> {
+        self.op_on(path)?;
+        match self.limits.max_deletions {
+            Some(max) if self.deletions >= max => {
+                error!(
+                    "Refusing to remove {}: deletion limit reached",
+                    path.display()
+                );
+                Err(quota_exceeded(format!("more than {max} deletions")).with_path(path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Account the removal of the tree at `path`, failing if any denied prefix is within the tree
+    /// or if removing all its entries would exceed the deletion limit.
+    /// Entries are counted with `walk_dir` up front, so that nothing is removed if the limit would be exceeded.
+    /// Returns the amount of entries in the tree, if counted
+    fn delete_tree(&mut self, path: &Path) -> RemoteResult<Option<usize>> {
+        self.op_on(path)?;
+        if !self.limits.denied_prefixes.is_empty() {
+            let path = self.resolve(path)?;
+            if let Some(prefix) = self
+                .limits
+                .denied_prefixes
+                .iter()
+                .find(|x| x.starts_with(path.as_path()))
+            {
+                return Err(RemoteError::new_ex(
+                    RemoteErrorType::PexError,
+                    format!("access to {} is denied", prefix.display()),
+                )
+                .with_op("remove_dir_all_with")
+                .with_path(path));
+            }
+        }
+        let max = match self.limits.max_deletions {
+            Some(max) => max,
+            None => return Ok(None),
+        };
+        let entry = self.inner.stat(path)?;
+        let count = match entry.is_dir() {
+            true => self.inner.walk_dir(path, &WalkDirOpts::default())?.len() + 1,
+            false => 1,
+        };
+        if self.deletions + count > max {
+            error!(
+                "Refusing to remove {}: removing {} entries would exceed the deletion limit ({} of {} used)",
+                path.display(),
+                count,
+                self.deletions,
+                max
+            );
+            return Err(quota_exceeded(format!(
+                "removing {count} entries exceeds {max} deletions"
+            ))
+            .with_op("remove_dir_all_with")
+            .with_path(path));
+        }
+        Ok(Some(count))
+    }
+
+    /// Check the size declared for an upload to `path`
+    fn check_upload(&self, path: &Path, metadata: &Metadata) -> RemoteResult<()> {
+        match self.limits.max_file_size {
+            Some(max) if metadata.size > max => Err(quota_exceeded(format!(
+                "file size {} exceeds {} bytes",
+                metadata.size, max
+            ))
+            .with_path(path)),
+            _ => Ok(()),
+        }
+    }
+
+    fn counter(&self, file_limit: Option<u64>) -> Counter {
+        Counter {
+            file: 0,
+            file_limit,
+            session: self.transferred.clone(),
+            session_limit: self.limits.max_session_bytes,
+        }
+    }
+
+    fn wrap_read(&self, mut stream: ReadStream) -> ReadStream {
+        let tags = stream.take_tags();
+        let reader: Box<dyn ReadAndSeek> = Box::new(LimitedStream {
+            inner: stream,
+            counter: self.counter(None),
+        });
+        ReadStream::from(reader).with_tags(tags)
+    }
+
+    fn wrap_write(&self, mut stream: WriteStream) -> WriteStream {
+        let tags = stream.take_tags();
+        let writer: Box<dyn WriteAndSeek> = Box::new(LimitedStream {
+            inner: stream,
+            counter: self.counter(self.limits.max_file_size),
+        });
+        WriteStream::from(writer).with_tags(tags)
+    }
+}
+
+impl<T: RemoteFs> RemoteFs for LimitedFs<T> {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.op()?;
+        self.wrkdir = None;
+        self.inner.connect()
+    }
+
+    fn connect_with(&mut self, opts: &ConnectOpts) -> RemoteResult<Welcome> {
+        self.op()?;
+        self.wrkdir = None;
+        self.inner.connect_with(opts)
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.inner.disconnect()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn capabilities(&mut self) -> Capabilities {
+        let mut capabilities = self.inner.capabilities();
+        if !self.limits.denied_prefixes.is_empty() {
+            capabilities.exec = false;
+        }
+        capabilities
+    }
+
+    fn summary(&self) -> Option<String> {
+        self.inner.summary()
+    }
+
+    fn semantics(&self) -> Semantics {
+        self.inner.semantics()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        self.op()?;
+        let wrkdir = self.inner.pwd()?;
+        self.wrkdir = Some(wrkdir.clone());
+        Ok(wrkdir)
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.op_on(dir)?;
+        let wrkdir = self.inner.change_dir(dir)?;
+        self.wrkdir = Some(wrkdir.clone());
+        Ok(wrkdir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        self.op_on(path)?;
+        self.inner.list_dir(path)
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        self.op_on(path)?;
+        self.inner.stat(path)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        self.op_on(path)?;
+        self.inner.setstat(path, metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        self.op_on(path)?;
+        self.inner.exists(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.delete(path)?;
+        self.inner.remove_file(path)?;
+        self.deletions += 1;
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.delete(path)?;
+        self.inner.remove_dir(path)?;
+        self.deletions += 1;
+        Ok(())
+    }
+
+    fn remove_dir_all_with(
+        &mut self,
+        path: &Path,
+        policy: ErrorPolicy,
+        on_event: &mut dyn FnMut(FsEvent),
+    ) -> RemoteResult<BulkReport> {
+        let count = self.delete_tree(path)?;
+        let result = self.inner.remove_dir_all_with(path, policy, on_event);
+        self.deletions += match result.as_ref() {
+            Ok(report) => report.succeeded as usize,
+            Err(_) => count.unwrap_or(0),
+        };
+        result
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        self.op_on(path)?;
+        self.inner.create_dir(path, mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        self.op_on(path)?;
+        // relative targets are resolved from the directory containing the link
+        let link = self.resolve(path)?;
+        let resolved = absolutize(link.parent().unwrap_or(&link), target);
+        self.op_on(resolved.as_path())?;
+        self.inner.symlink(path, target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.op_on(src)?;
+        self.op_on(dest)?;
+        self.inner.copy(src, dest)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.op_on(src)?;
+        self.op_on(dest)?;
+        self.inner.mov(src, dest)
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.op()?;
+        if !self.limits.denied_prefixes.is_empty() {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::PexError,
+                "commands can't be executed while path prefixes are denied",
+            )
+            .with_op("exec"));
+        }
+        self.inner.exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.op_on(path)?;
+        self.check_upload(path, metadata)?;
+        let stream = self.inner.append(path, metadata)?;
+        Ok(self.wrap_write(stream))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.op_on(path)?;
+        self.check_upload(path, metadata)?;
+        let stream = self.inner.create(path, metadata)?;
+        Ok(self.wrap_write(stream))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        self.op_on(path)?;
+        let stream = self.inner.open(path)?;
+        Ok(self.wrap_read(stream))
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.inner.on_written(writable)
+    }
+
+    fn on_written_ext(&mut self, writable: WriteStream) -> RemoteResult<WriteReport> {
+        self.inner.on_written_ext(writable)
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        self.inner.on_read(readable)
+    }
+
+    fn append_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.op_on(path)?;
+        self.check_upload(path, metadata)?;
+        let reader = Box::new(LimitedStream {
+            inner: reader,
+            counter: self.counter(self.limits.max_file_size),
+        });
+        self.inner.append_file(path, metadata, reader)
+    }
+
+    fn create_file(
+        &mut self,
+        path: &Path,
+        metadata: &Metadata,
+        reader: Box<dyn Read + Send>,
+    ) -> RemoteResult<u64> {
+        self.op_on(path)?;
+        self.check_upload(path, metadata)?;
+        let reader = Box::new(LimitedStream {
+            inner: reader,
+            counter: self.counter(self.limits.max_file_size),
+        });
+        self.inner.create_file(path, metadata, reader)
+    }
+
+    fn open_file(&mut self, src: &Path, dest: Box<dyn Write + Send>) -> RemoteResult<u64> {
+        self.op_on(src)?;
+        let dest = Box::new(LimitedStream {
+            inner: dest,
+            counter: self.counter(None),
+        });
+        self.inner.open_file(src, dest)
+    }
+}
+
+fn quota_exceeded(msg: String) -> RemoteError {
+    RemoteError::new_ex(RemoteErrorType::QuotaExceeded, msg)
+}
+
+/// Counts the bytes transferred by a stream, against the limits of the file and of the session
+struct Counter {
+    file: u64,
+    file_limit: Option<u64>,
+    session: Arc<AtomicU64>,
+    session_limit: Option<u64>,
+}
+
+impl Counter {
+    /// Account `len` bytes, failing if a limit would be exceeded
+    fn account(&mut self, len: usize) -> io::Result<()> {
+        let len = len as u64;
+        if let Some(max) = self.file_limit.filter(|max| self.file + len > *max) {
+            return Err(quota_io_error(format!("file size exceeds {max} bytes")));
+        }
+        let session = self.session.fetch_add(len, Ordering::Relaxed) + len;
+        if let Some(max) = self.session_limit.filter(|max| session > *max) {
+            self.session.fetch_sub(len, Ordering::Relaxed);
+            return Err(quota_io_error(format!(
+                "session transfer exceeds {max} bytes"
+            )));
+        }
+        self.file += len;
+        Ok(())
+    }
+}
+
+fn quota_io_error(msg: String) -> io::Error {
+    io::Error::from(quota_exceeded(msg))
+}
+
+/// A stream which accounts the bytes transferred through it
+struct LimitedStream<S> {
+    inner: S,
+    counter: Counter,
+}
+
+impl<S: Read> Read for LimitedStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.counter.account(read)?;
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for LimitedStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.counter.account(buf.len())?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Seek> Seek for LimitedStream<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl ReadAndSeek for LimitedStream<ReadStream> {}
+
+impl WriteAndSeek for LimitedStream<WriteStream> {}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::ChecksumAlgorithm;
+    use crate::mock::MemoryFs;
+
+    #[test]
+    fn should_limit_file_size() {
+        let mut client = LimitedFs::new(MemoryFs::connected(), Limits::default().max_file_size(4));
+        assert_eq!(
+            client
+                .create(Path::new("/a.txt"), &Metadata::default().size(5))
+                .err()
+                .unwrap()
+                .kind,
+            RemoteErrorType::QuotaExceeded
+        );
+        let err = client
+            .create_file(
+                Path::new("/a.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"hello".to_vec())),
+            )
+            .unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::QuotaExceeded);
+        let mut stream = client
+            .create(Path::new("/b.txt"), &Metadata::default())
+            .unwrap();
+        assert!(stream.write_all(b"hell").is_ok());
+        assert!(stream.write_all(b"o").is_err());
+    }
+
+    #[test]
+    fn should_limit_session_bytes() {
+        let mut client = LimitedFs::new(
+            MemoryFs::connected().with_file("/a.txt", b"hello"),
+            Limits::default().max_session_bytes(8),
+        );
+        let mut content = Vec::new();
+        client
+            .open(Path::new("/a.txt"))
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(client.transferred(), 5);
+        let err = client
+            .open(Path::new("/a.txt"))
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<RemoteError>().unwrap();
+        assert_eq!(err.kind, RemoteErrorType::QuotaExceeded);
+        assert_eq!(client.transferred(), 5);
+    }
+
+    #[test]
+    fn should_limit_ops_per_minute() {
+        let mut client = LimitedFs::new(
+            MemoryFs::connected(),
+            Limits::default().max_ops_per_minute(2),
+        );
+        assert!(client.pwd().is_ok());
+        assert!(client.exists(Path::new("/a.txt")).is_ok());
+        assert_eq!(
+            client.pwd().unwrap_err().kind,
+            RemoteErrorType::QuotaExceeded
+        );
+    }
+
+    #[test]
+    fn should_limit_deletions() {
+        let mut client = LimitedFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_file("/home/a.txt", b"a")
+                .with_file("/home/b.txt", b"b")
+                .with_file("/home/c.txt", b"c"),
+            Limits::default().max_deletions(2),
+        );
+        assert!(client.remove_file(Path::new("/home/a.txt")).is_ok());
+        let err = client.remove_dir_all(Path::new("/home")).unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::QuotaExceeded);
+        assert_eq!(err.op(), Some("remove_dir_all"));
+        assert_eq!(client.deletions(), 1);
+        assert_eq!(client.exists(Path::new("/home/b.txt")).unwrap(), true);
+        assert_eq!(client.exists(Path::new("/home/c.txt")).unwrap(), true);
+        // failed removals are not counted
+        assert_eq!(
+            client
+                .remove_file(Path::new("/home/missing.txt"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        assert_eq!(client.deletions(), 1);
+        assert!(client.remove_file(Path::new("/home/b.txt")).is_ok());
+        assert_eq!(client.deletions(), 2);
+    }
+
+    #[test]
+    fn should_count_deletions_of_trees() {
+        let mut client = LimitedFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_dir("/home/docs")
+                .with_file("/home/docs/a.txt", b"a")
+                .with_file("/home/b.txt", b"b"),
+            Limits::default().max_deletions(4),
+        );
+        let report = client
+            .remove_dir_all_with(Path::new("/home"), ErrorPolicy::Abort, &mut |_| {})
+            .unwrap();
+        assert_eq!(report.succeeded, 4);
+        assert_eq!(client.deletions(), 4);
+        assert_eq!(client.exists(Path::new("/home")).unwrap(), false);
+    }
+
+    #[test]
+    fn should_not_remove_trees_containing_denied_prefixes() {
+        let mut client = LimitedFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_dir("/home/secret")
+                .with_file("/home/a.txt", b"a"),
+            Limits::default().deny_prefix("/home/secret"),
+        );
+        assert_eq!(
+            client.remove_dir_all(Path::new("/home")).unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(client.exists(Path::new("/home/a.txt")).unwrap(), true);
+    }
+
+    #[test]
+    fn should_deny_path_prefixes() {
+        let mut client = LimitedFs::new(
+            MemoryFs::connected()
+                .with_dir("/etc")
+                .with_file("/etc/passwd", b"root")
+                .with_dir("/home"),
+            Limits::default().deny_prefix("/etc"),
+        );
+        assert_eq!(
+            client.stat(Path::new("/etc/passwd")).unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+        assert!(client.change_dir(Path::new("/home")).is_ok());
+        let err = client.stat(Path::new("../etc/./passwd")).unwrap_err();
+        assert_eq!(err.kind, RemoteErrorType::PexError);
+        assert_eq!(err.path(), Some(Path::new("/etc/passwd")));
+        assert_eq!(
+            client
+                .mov(Path::new("/home"), Path::new("/etc/home"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert!(client.stat(Path::new("/home")).is_ok());
+    }
+
+    #[test]
+    fn should_resolve_symlink_targets_from_link_directory() {
+        let mut client = LimitedFs::new(
+            MemoryFs::connected()
+                .with_dir("/etc")
+                .with_file("/etc/passwd", b"root")
+                .with_dir("/home")
+                .with_dir("/home/omar"),
+            Limits::default().deny_prefix("/etc"),
+        );
+        // the working directory is `/`, where `etc` is denied
+        assert!(client
+            .symlink(Path::new("/home/omar/link"), Path::new("etc"))
+            .is_ok());
+        assert_eq!(
+            client
+                .symlink(Path::new("/home/passwd"), Path::new("../etc/passwd"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+    }
+
+    #[test]
+    fn should_not_exec_with_denied_prefixes() {
+        let mut client = LimitedFs::new(
+            MemoryFs::connected()
+                .with_dir("/etc")
+                .with_file("/etc/passwd", b"root")
+                .with_exec("tail -n 10 /etc/passwd", 0, "root\n")
+                .with_exec("sha256sum < /etc/passwd", 0, "abcd  -\n"),
+            Limits::default().deny_prefix("/etc"),
+        );
+        assert_eq!(client.capabilities().exec, false);
+        assert_eq!(
+            client.exec("cat /etc/passwd").unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            client.tail(Path::new("/etc/passwd"), 10).unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            client
+                .checksum(Path::new("/etc/passwd"), ChecksumAlgorithm::Sha256)
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            client.can_read(Path::new("/etc/passwd")).unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+    }
+}
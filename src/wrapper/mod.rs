@@ -0,0 +1,24 @@
+//! ## Wrapper
+//!
+//! `RemoteFs` decorators which add behaviour on top of any remote file system
+
+mod audit;
+#[cfg(feature = "find")]
+mod filtered;
+pub(crate) mod forward;
+mod lazy;
+mod limited;
+mod mount;
+mod tilde;
+mod traced;
+mod union;
+
+pub use self::audit::{AuditOutcome, AuditRecord, AuditSink, AuditedFs, JsonlSink};
+#[cfg(feature = "find")]
+pub use self::filtered::{FilterRules, FilteredFs};
+pub use self::lazy::LazyFs;
+pub use self::limited::{LimitedFs, Limits};
+pub use self::mount::MountFs;
+pub use self::tilde::TildeFs;
+pub use self::traced::{Span, SpanCallback, TracedFs};
+pub use self::union::UnionFs;
@@ -0,0 +1,565 @@
+//! ## Mount
+//!
+//! a `RemoteFs` routing operations to different file systems by path prefix
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::fs::{
+    copy_stream, FileType, Metadata, ReadStream, TransferOpts, UnixPex, Welcome, WriteReport,
+    WriteStream,
+};
+use crate::utils::path::{absolutize, normalize};
+use crate::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// A file system mounted on a path prefix
+struct Mount {
+    prefix: PathBuf,
+    fs: Box<dyn RemoteFs>,
+}
+
+/// A `RemoteFs` presenting one namespace over heterogeneous storage, as a mount table does:
+/// each file system is mounted on a path prefix (e.g. `/s3` and `/sftp`), and operations are routed to the file system
+/// with the longest prefix matching the path, which is rewritten relative to the root of the mounted file system.
+///
+/// The directories containing the mount points (e.g. `/`) are virtual: their listing contains the mount points,
+/// and they can't be modified.
+/// Copying files across mounts is done through streams, while moving them fails with `UnsupportedFeature`
+/// (`move_with` with `copy_fallback` can be used instead).
+/// Symlinks can only point to paths on their own mount.
+///
+/// Streams are tagged with the mount they were opened on, so they can be finalized in any order
+pub struct MountFs {
+    mounts: Vec<Mount>,
+    wrkdir: PathBuf,
+}
+
+/// The tag of the streams opened on a mount
+struct MountTag(usize);
+
+impl Default for MountFs {
+    fn default() -> Self {
+        Self {
+            mounts: Vec::new(),
+            wrkdir: PathBuf::from("/"),
+        }
+    }
+}
+
+impl MountFs {
+    /// Mount `fs` on `prefix`; the root of `fs` is mapped to `prefix`
+    pub fn mount<P: AsRef<Path>>(mut self, prefix: P, fs: Box<dyn RemoteFs>) -> Self {
+        self.mounts.push(Mount {
+            prefix: normalize(absolutize(Path::new("/"), prefix.as_ref()).as_path()),
+            fs,
+        });
+        self
+    }
+
+    /// Get the prefixes of the mount points
+    pub fn mount_points(&self) -> Vec<&Path> {
+        self.mounts.iter().map(|x| x.prefix.as_path()).collect()
+    }
+
+    /// Get the absolute, normalized path of `path`
+    fn resolve(&self, path: &Path) -> PathBuf {
+        normalize(absolutize(self.wrkdir.as_path(), path).as_path())
+    }
+
+    /// Get the mount which `path` belongs to and the path on the mounted file system
+    fn route(&self, path: &Path) -> Option<(usize, PathBuf)> {
+        let path = self.resolve(path);
+        self.mounts
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| path.starts_with(x.prefix.as_path()))
+            .max_by_key(|(_, x)| x.prefix.components().count())
+            .map(|(i, x)| {
+                let relative = path.strip_prefix(x.prefix.as_path()).unwrap_or(&path);
+                (i, Path::new("/").join(relative))
+            })
+    }
+
+    /// Get the mount which `path` belongs to and the path on the mounted file system,
+    /// failing if it doesn't belong to any mount
+    fn route_or_err(&self, path: &Path) -> RemoteResult<(usize, PathBuf)> {
+        match self.route(path) {
+            Some(route) => Ok(route),
+            None if self.is_virtual(path) => Err(RemoteError::new_ex(
+                RemoteErrorType::PexError,
+                "virtual directory",
+            )
+            .with_path(self.resolve(path))),
+            None => Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)
+                .with_path(self.resolve(path))),
+        }
+    }
+
+    /// Returns whether `path` is a virtual directory containing mount points
+    fn is_virtual(&self, path: &Path) -> bool {
+        let path = self.resolve(path);
+        self.mounts.iter().any(|x| x.prefix.starts_with(&path))
+    }
+
+    /// Rewrite the path of `file` and its absolute symlink target, from the mounted file system to the mount table
+    fn rewrite(&self, mount: usize, mut file: File) -> File {
+        file.path = self.rewrite_path(mount, file.path());
+        if let Some(target) = file.metadata.symlink.take() {
+            file.metadata.symlink = Some(match target.is_absolute() {
+                true => self.rewrite_path(mount, target.as_path()),
+                false => target,
+            });
+        }
+        file
+    }
+
+    /// Rewrite `path` from the mounted file system to the mount table
+    fn rewrite_path(&self, mount: usize, path: &Path) -> PathBuf {
+        let relative = path.strip_prefix("/").unwrap_or(path);
+        self.mounts[mount].prefix.join(relative)
+    }
+
+    fn untagged_stream() -> RemoteError {
+        RemoteError::new_ex(
+            RemoteErrorType::FinalizeFailed,
+            "the stream wasn't opened by this file system",
+        )
+    }
+
+    fn virtual_dir(path: PathBuf) -> File {
+        File {
+            path,
+            metadata: Metadata::default()
+                .file_type(FileType::Directory)
+                .mode(UnixPex::from(0o555)),
+        }
+    }
+}
+
+impl RemoteFs for MountFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        for mount in self.mounts.iter_mut() {
+            debug!(
+                "Connecting file system mounted on {}",
+                mount.prefix.display()
+            );
+            mount.fs.connect()?;
+        }
+        self.wrkdir = PathBuf::from("/");
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        let mut result = Ok(());
+        for mount in self.mounts.iter_mut() {
+            if let Err(err) = mount.fs.disconnect() {
+                warn!("Failed to disconnect {}: {}", mount.prefix.display(), err);
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.mounts.iter_mut().all(|x| x.fs.is_connected())
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        if self.is_connected() {
+            Ok(self.wrkdir.clone())
+        } else {
+            Err(RemoteError::new(RemoteErrorType::NotConnected))
+        }
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = self.resolve(dir);
+        if let Some((mount, path)) = self.route(dir.as_path()) {
+            self.mounts[mount].fs.change_dir(path.as_path())?;
+        } else if !self.is_virtual(dir.as_path()) {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)
+                .with_op("change_dir")
+                .with_path(dir));
+        }
+        self.wrkdir = dir.clone();
+        Ok(dir)
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        if let Some((mount, inner)) = self.route(path) {
+            let files = self.mounts[mount].fs.list_dir(inner.as_path())?;
+            return Ok(files.into_iter().map(|x| self.rewrite(mount, x)).collect());
+        }
+        let dir = self.resolve(path);
+        if !self.is_virtual(dir.as_path()) {
+            return Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)
+                .with_op("list_dir")
+                .with_path(dir));
+        }
+        let children: BTreeSet<PathBuf> = self
+            .mounts
+            .iter()
+            .filter_map(|x| x.prefix.strip_prefix(dir.as_path()).ok())
+            .filter_map(|x| x.iter().next())
+            .map(|x| dir.join(x))
+            .collect();
+        Ok(children.into_iter().map(Self::virtual_dir).collect())
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        match self.route(path) {
+            Some((mount, inner)) => {
+                let file = self.mounts[mount].fs.stat(inner.as_path())?;
+                Ok(self.rewrite(mount, file))
+            }
+            None if self.is_virtual(path) => Ok(Self::virtual_dir(self.resolve(path))),
+            None => Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)
+                .with_op("stat")
+                .with_path(self.resolve(path))),
+        }
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let (mount, inner) = self.route_or_err(path)?;
+        self.mounts[mount].fs.setstat(inner.as_path(), metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.route(path) {
+            Some((mount, inner)) => self.mounts[mount].fs.exists(inner.as_path()),
+            None => Ok(self.is_virtual(path)),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        let (mount, inner) = self.route_or_err(path)?;
+        self.mounts[mount].fs.remove_file(inner.as_path())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        let (mount, inner) = self.route_or_err(path)?;
+        self.mounts[mount].fs.remove_dir(inner.as_path())
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        let (mount, inner) = self.route_or_err(path)?;
+        self.mounts[mount].fs.create_dir(inner.as_path(), mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        let (mount, inner) = self.route_or_err(path)?;
+        // relative targets are resolved from the directory containing the link
+        let link = self.resolve(path);
+        let resolved = normalize(absolutize(link.parent().unwrap_or(&link), target).as_path());
+        match self.route(resolved.as_path()) {
+            Some((target_mount, target_inner)) if target_mount == mount => {
+                let target = match target.is_absolute() {
+                    true => target_inner,
+                    false => target.to_path_buf(),
+                };
+                self.mounts[mount]
+                    .fs
+                    .symlink(inner.as_path(), target.as_path())
+            }
+            _ => Err(RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "symlinks can't point outside of their mount",
+            )
+            .with_op("symlink")
+            .with_path(link)),
+        }
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let (src_mount, inner_src) = self.route_or_err(src)?;
+        let (dest_mount, inner_dest) = self.route_or_err(dest)?;
+        if src_mount == dest_mount {
+            return self.mounts[src_mount]
+                .fs
+                .copy(inner_src.as_path(), inner_dest.as_path());
+        }
+        let file = self.mounts[src_mount].fs.stat(inner_src.as_path())?;
+        if !file.is_file() {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "only files can be copied across mounts",
+            )
+            .with_op("copy")
+            .with_path(self.resolve(src)));
+        }
+        debug!(
+            "Copying {} to {} across mounts",
+            src.display(),
+            dest.display()
+        );
+        let mut reader = self.mounts[src_mount].fs.open(inner_src.as_path())?;
+        let mut writer = self.mounts[dest_mount]
+            .fs
+            .create(inner_dest.as_path(), file.metadata())?;
+        copy_stream(&mut reader, &mut writer, &TransferOpts::default())?;
+        self.mounts[src_mount].fs.on_read(reader)?;
+        self.mounts[dest_mount].fs.on_written(writer)
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let (src_mount, inner_src) = self.route_or_err(src)?;
+        let (dest_mount, inner_dest) = self.route_or_err(dest)?;
+        if src_mount != dest_mount {
+            return Err(RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "files can't be moved across mounts",
+            )
+            .with_op("mov")
+            .with_path(self.resolve(src)));
+        }
+        self.mounts[src_mount]
+            .fs
+            .mov(inner_src.as_path(), inner_dest.as_path())
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        match self.route(self.wrkdir.clone().as_path()) {
+            Some((mount, _)) => self.mounts[mount].fs.exec(cmd),
+            None => Err(RemoteError::new_ex(
+                RemoteErrorType::UnsupportedFeature,
+                "commands can't be executed in a virtual directory",
+            )
+            .with_op("exec")),
+        }
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let (mount, inner) = self.route_or_err(path)?;
+        let stream = self.mounts[mount].fs.append(inner.as_path(), metadata)?;
+        Ok(stream.tag(MountTag(mount)))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let (mount, inner) = self.route_or_err(path)?;
+        let stream = self.mounts[mount].fs.create(inner.as_path(), metadata)?;
+        Ok(stream.tag(MountTag(mount)))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let (mount, inner) = self.route_or_err(path)?;
+        let stream = self.mounts[mount].fs.open(inner.as_path())?;
+        Ok(stream.tag(MountTag(mount)))
+    }
+
+    fn on_written(&mut self, mut writable: WriteStream) -> RemoteResult<()> {
+        match writable.take_tag::<MountTag>() {
+            Some(MountTag(mount)) => self.mounts[mount].fs.on_written(writable),
+            None => Err(Self::untagged_stream()),
+        }
+    }
+
+    fn on_written_ext(&mut self, mut writable: WriteStream) -> RemoteResult<WriteReport> {
+        match writable.take_tag::<MountTag>() {
+            Some(MountTag(mount)) => self.mounts[mount].fs.on_written_ext(writable),
+            None => Err(Self::untagged_stream()),
+        }
+    }
+
+    fn on_read(&mut self, mut readable: ReadStream) -> RemoteResult<()> {
+        match readable.take_tag::<MountTag>() {
+            Some(MountTag(mount)) => self.mounts[mount].fs.on_read(readable),
+            None => Err(Self::untagged_stream()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::{Cursor, Read, Write};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::MoveOpts;
+    use crate::mock::MemoryFs;
+
+    fn mount_fs() -> MountFs {
+        let s3 = MemoryFs::connected()
+            .with_dir("/bucket")
+            .with_file("/bucket/a.txt", b"from s3");
+        let sftp = MemoryFs::connected()
+            .with_dir("/home")
+            .with_file("/home/b.txt", b"from sftp");
+        MountFs::default()
+            .mount("/remote/s3", Box::new(s3))
+            .mount("/remote/sftp", Box::new(sftp))
+    }
+
+    #[test]
+    fn should_list_virtual_dirs() {
+        let mut fs = mount_fs();
+        assert_eq!(
+            fs.mount_points(),
+            vec![Path::new("/remote/s3"), Path::new("/remote/sftp")]
+        );
+        let paths = |files: Vec<File>| files.into_iter().map(|x| x.path).collect::<Vec<_>>();
+        assert_eq!(
+            paths(fs.list_dir(Path::new("/")).unwrap()),
+            vec![PathBuf::from("/remote")]
+        );
+        assert_eq!(
+            paths(fs.list_dir(Path::new("/remote")).unwrap()),
+            vec![PathBuf::from("/remote/s3"), PathBuf::from("/remote/sftp")]
+        );
+        assert!(fs.stat(Path::new("/remote")).unwrap().is_dir());
+        assert_eq!(fs.exists(Path::new("/remote")).unwrap(), true);
+        assert_eq!(fs.exists(Path::new("/local")).unwrap(), false);
+        assert_eq!(
+            fs.create_dir(Path::new("/remote/ftp"), UnixPex::from(0o755))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+        assert_eq!(
+            fs.remove_dir(Path::new("/remote")).unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+    }
+
+    #[test]
+    fn should_route_by_prefix() {
+        let mut fs = mount_fs();
+        fs.change_dir(Path::new("/remote/s3/bucket")).unwrap();
+        assert_eq!(
+            fs.stat(Path::new("a.txt")).unwrap().path,
+            PathBuf::from("/remote/s3/bucket/a.txt")
+        );
+        assert_eq!(
+            fs.list_dir(Path::new("/remote/sftp/home"))
+                .unwrap()
+                .into_iter()
+                .map(|x| x.path)
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("/remote/sftp/home/b.txt")]
+        );
+        let mut content = String::new();
+        let mut stream = fs.open(Path::new("/remote/sftp/home/b.txt")).unwrap();
+        stream.read_to_string(&mut content).unwrap();
+        fs.on_read(stream).unwrap();
+        assert_eq!(content, "from sftp");
+        fs.create_file(
+            Path::new("c.txt"),
+            &Metadata::default(),
+            Box::new(Cursor::new(b"new".to_vec())),
+        )
+        .unwrap();
+        assert_eq!(
+            fs.exists(Path::new("/remote/s3/bucket/c.txt")).unwrap(),
+            true
+        );
+        assert_eq!(
+            fs.exists(Path::new("/remote/sftp/bucket/c.txt")).unwrap(),
+            false
+        );
+    }
+
+    #[test]
+    fn should_finalize_streams_on_their_mount() {
+        let mut fs = mount_fs();
+        let s3 = fs.open(Path::new("/remote/s3/bucket/a.txt")).unwrap();
+        let sftp = fs.open(Path::new("/remote/sftp/home/b.txt")).unwrap();
+        let mut writer = fs
+            .create(Path::new("/remote/sftp/home/c.txt"), &Metadata::default())
+            .unwrap()
+            .buffered(4);
+        writer.write_all(b"hello").unwrap();
+        writer.flush().unwrap();
+        fs.on_read(sftp).unwrap();
+        fs.on_read(s3).unwrap();
+        assert_eq!(fs.on_written_ext(writer).unwrap().bytes, 5);
+        assert_eq!(
+            fs.stat(Path::new("/remote/sftp/home/c.txt"))
+                .unwrap()
+                .metadata
+                .size,
+            5
+        );
+        let stream = ReadStream::from(Box::new(Cursor::new(Vec::new())) as Box<dyn Read + Send>);
+        assert_eq!(
+            fs.on_read(stream).unwrap_err().kind,
+            RemoteErrorType::FinalizeFailed
+        );
+    }
+
+    #[test]
+    fn should_rewrite_symlink_targets() {
+        let mut fs = mount_fs();
+        fs.symlink(
+            Path::new("/remote/s3/bucket/abs"),
+            Path::new("/remote/s3/bucket/a.txt"),
+        )
+        .unwrap();
+        assert_eq!(
+            fs.stat(Path::new("/remote/s3/bucket/abs"))
+                .unwrap()
+                .metadata
+                .symlink,
+            Some(PathBuf::from("/remote/s3/bucket/a.txt"))
+        );
+        fs.symlink(Path::new("/remote/s3/bucket/rel"), Path::new("a.txt"))
+            .unwrap();
+        assert_eq!(
+            fs.stat(Path::new("/remote/s3/bucket/rel"))
+                .unwrap()
+                .metadata
+                .symlink,
+            Some(PathBuf::from("a.txt"))
+        );
+        assert_eq!(
+            fs.symlink(
+                Path::new("/remote/s3/bucket/other"),
+                Path::new("/remote/sftp/home/b.txt"),
+            )
+            .unwrap_err()
+            .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+        assert_eq!(
+            fs.symlink(Path::new("/remote/s3/up"), Path::new("../sftp/home/b.txt"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+
+    #[test]
+    fn should_copy_across_mounts() {
+        let mut fs = mount_fs();
+        fs.copy(
+            Path::new("/remote/s3/bucket/a.txt"),
+            Path::new("/remote/sftp/home/a.txt"),
+        )
+        .unwrap();
+        let mut content = String::new();
+        let mut stream = fs.open(Path::new("/remote/sftp/home/a.txt")).unwrap();
+        stream.read_to_string(&mut content).unwrap();
+        fs.on_read(stream).unwrap();
+        assert_eq!(content, "from s3");
+        assert_eq!(
+            fs.mov(
+                Path::new("/remote/s3/bucket/a.txt"),
+                Path::new("/remote/sftp/home/c.txt"),
+            )
+            .unwrap_err()
+            .kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+        assert!(fs
+            .move_with(
+                Path::new("/remote/s3/bucket/a.txt"),
+                Path::new("/remote/sftp/home/c.txt"),
+                &MoveOpts::default().copy_fallback(true),
+            )
+            .is_ok());
+        assert_eq!(
+            fs.exists(Path::new("/remote/s3/bucket/a.txt")).unwrap(),
+            false
+        );
+    }
+}
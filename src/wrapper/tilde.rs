@@ -0,0 +1,185 @@
+//! ## Tilde
+//!
+//! a `RemoteFs` decorator expanding `~` in paths to the home directory
+
+use std::path::{Path, PathBuf};
+
+use super::forward::forward_remote_fs;
+use crate::fs::{ConnectOpts, Welcome};
+use crate::utils::path::expand_tilde;
+use crate::{RemoteFs, RemoteResult};
+
+/// A `RemoteFs` decorator which expands a leading `~` in the paths passed to the inner file system
+/// (e.g. `~/uploads`) to the home directory of the user, as returned by `RemoteFs::home_dir`.
+///
+/// The home directory is resolved on the first path starting with `~` after connecting, and kept until disconnecting.
+/// Paths returned by the file system are never changed; `~user` and the targets of symlinks are not expanded
+pub struct TildeFs<T: RemoteFs> {
+    inner: T,
+    home: Option<PathBuf>,
+}
+
+impl<T: RemoteFs> TildeFs<T> {
+    /// Instantiates a new `TildeFs`, expanding `~` in the paths passed to `inner`
+    pub fn new(inner: T) -> Self {
+        Self { inner, home: None }
+    }
+
+    /// Get a reference to the inner file system
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Consume the decorator, returning the inner file system
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Expand `~` in `path`, resolving the home directory if not known yet
+    fn expand(&mut self, path: &Path) -> RemoteResult<PathBuf> {
+        if !path.starts_with("~") {
+            return Ok(path.to_path_buf());
+        }
+        let home = match self.home.as_ref() {
+            Some(home) => home.clone(),
+            None => {
+                let home = self.inner.home_dir()?;
+                debug!("Home directory is {}", home.display());
+                self.home = Some(home.clone());
+                home
+            }
+        };
+        Ok(expand_tilde(home.as_path(), path).unwrap_or_else(|| path.to_path_buf()))
+    }
+}
+
+/// Forward a method to the inner file system, expanding `~` in the paths it operates on
+macro_rules! forward_expanded {
+    (@plain $this:ident, $method:ident, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $call
+        }
+    };
+    ($this:ident, connect, $($rest:tt)*) => {};
+    ($this:ident, connect_with, $($rest:tt)*) => {};
+    ($this:ident, disconnect, $($rest:tt)*) => {};
+    ($this:ident, home_dir, $($rest:tt)*) => {};
+    ($this:ident, symlink, $($rest:tt)*) => {};
+    ($this:ident, $method:ident, [$($path:ident),*], $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $(
+                let $path = $this.expand($path)?;
+                let $path = $path.as_path();
+            )*
+            $call
+        }
+    };
+}
+
+impl<T: RemoteFs> RemoteFs for TildeFs<T> {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        self.home = None;
+        self.inner.connect()
+    }
+
+    fn connect_with(&mut self, opts: &ConnectOpts) -> RemoteResult<Welcome> {
+        self.home = None;
+        self.inner.connect_with(opts)
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.home = None;
+        self.inner.disconnect()
+    }
+
+    fn home_dir(&mut self) -> RemoteResult<PathBuf> {
+        self.expand(Path::new("~"))
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        let path = self.expand(path)?;
+        self.inner.symlink(path.as_path(), target)
+    }
+
+    forward_remote_fs!(forward_expanded);
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::{CopyOpts, Metadata, WalkDirOpts};
+    use crate::mock::MemoryFs;
+    use crate::RemoteErrorType;
+
+    #[test]
+    fn should_expand_tilde() {
+        let mut client = TildeFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_dir("/home/omar")
+                .with_exec("echo \"$HOME\"", 0, "/home/omar\n"),
+        );
+        assert!(client
+            .create_file(
+                Path::new("~/a.txt"),
+                &Metadata::default(),
+                Box::new(Cursor::new(b"hello".to_vec())),
+            )
+            .is_ok());
+        assert_eq!(
+            client.inner().content("/home/omar/a.txt").unwrap(),
+            b"hello"
+        );
+        assert_eq!(
+            client.change_dir(Path::new("~")).unwrap(),
+            PathBuf::from("/home/omar")
+        );
+        assert_eq!(
+            client
+                .walk_dir(Path::new("~"), &WalkDirOpts::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(client.home_dir().unwrap(), PathBuf::from("/home/omar"));
+    }
+
+    #[test]
+    fn should_not_resolve_home_for_other_paths() {
+        let mut client = TildeFs::new(MemoryFs::connected().with_dir("/~"));
+        assert_eq!(client.exists(Path::new("/~")).unwrap(), true);
+        assert_eq!(
+            client.exists(Path::new("~")).unwrap_err().kind,
+            RemoteErrorType::UnsupportedFeature
+        );
+    }
+
+    #[test]
+    fn should_expand_tilde_in_compound_operations() {
+        let mut client = TildeFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_dir("/home/omar")
+                .with_file("/home/omar/a.txt", b"hello")
+                .with_exec("echo \"$HOME\"", 0, "/home/omar\n"),
+        );
+        assert!(client
+            .copy_with(
+                Path::new("~/a.txt"),
+                Path::new("~/b.txt"),
+                &CopyOpts::default()
+            )
+            .is_ok());
+        assert_eq!(
+            client.inner().content("/home/omar/b.txt").unwrap(),
+            b"hello"
+        );
+        assert!(client.remove_dir_all(Path::new("~")).is_ok());
+        assert!(client.inner().content("/home/omar/a.txt").is_none());
+    }
+}
@@ -0,0 +1,377 @@
+//! ## Traced
+//!
+//! a `RemoteFs` decorator reporting a span for each call made on the file system
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::forward::forward_remote_fs;
+use crate::fs::WriteReport;
+use crate::{RemoteError, RemoteFs, RemoteResult};
+
+/// Target of the log records emitted by `TracedFs`
+const LOG_TARGET: &str = "remotefs::trace";
+
+/// Callback receiving the spans reported by `TracedFs`
+pub type SpanCallback = Box<dyn FnMut(&Span) + Send>;
+
+/// A completed call on a `TracedFs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// The name of the called method (e.g. `stat`)
+    pub operation: &'static str,
+    /// The protocol of the inner file system (e.g. `sftp`)
+    pub protocol: String,
+    /// The remote host, if set
+    pub host: Option<String>,
+    /// The path the call was made on, if any
+    pub path: Option<PathBuf>,
+    /// The amount of bytes transferred, for calls transferring data
+    pub bytes: Option<u64>,
+    /// How long the call took
+    pub elapsed: Duration,
+    /// The error returned by the call, if it failed
+    pub error: Option<RemoteError>,
+}
+
+/// A `RemoteFs` decorator which times each call made on the inner file system, reporting it as a `Span`
+/// carrying protocol, host, path and transferred bytes.
+///
+/// Spans are logged with target `remotefs::trace` (at debug level, or warn level if the call failed)
+/// and passed to the callback set with `on_span`, which can forward them to a distributed tracing system.
+/// With the `tracing` feature each call is also made within a `tracing` span named `remotefs`, with target `remotefs::trace`,
+/// recording operation, protocol, host, path, transferred bytes and error, so that the events of the inner file system are nested in it.
+/// All the methods of `RemoteFs` are forwarded to the inner file system, so that its implementations are used,
+/// and reported as a single call (e.g. `remove_dir_all`)
+pub struct TracedFs<T: RemoteFs> {
+    inner: T,
+    protocol: String,
+    host: Option<String>,
+    on_span: Option<SpanCallback>,
+}
+
+impl<T: RemoteFs> TracedFs<T> {
+    /// Instantiates a new `TracedFs`, tracing the calls made on `inner`, which speaks `protocol`
+    pub fn new<S: ToString>(inner: T, protocol: S) -> Self {
+        Self {
+            inner,
+            protocol: protocol.to_string(),
+            host: None,
+            on_span: None,
+        }
+    }
+
+    /// Set the remote host reported in spans
+    pub fn host<S: ToString>(mut self, host: S) -> Self {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    /// Call `callback` with each span, once the call is completed
+    pub fn on_span<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&Span) + Send + 'static,
+    {
+        self.on_span = Some(Box::new(callback));
+        self
+    }
+
+    /// Get a reference to the inner file system
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Consume the decorator, returning the inner file system
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Start a call of `operation` on `path`
+    fn start(&self, operation: &'static str, path: Option<&Path>) -> Call {
+        Call {
+            operation,
+            path: path.map(Path::to_path_buf),
+            started: Instant::now(),
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                target: LOG_TARGET,
+                "remotefs",
+                operation,
+                protocol = self.protocol.as_str(),
+                host = self.host.as_deref(),
+                path = path.map(|x| tracing::field::display(x.display())),
+                bytes = tracing::field::Empty,
+                error = tracing::field::Empty,
+            )
+            .entered(),
+        }
+    }
+}
+
+/// A call in progress on a `TracedFs`.
+/// With the `tracing` feature the call is made within a `tracing` span, which is exited once the call ends
+struct Call {
+    operation: &'static str,
+    path: Option<PathBuf>,
+    started: Instant,
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+}
+
+impl Call {
+    /// End the call, returning its `Span`
+    fn end(
+        self,
+        protocol: &str,
+        host: Option<&str>,
+        bytes: Option<u64>,
+        error: Option<&RemoteError>,
+    ) -> Span {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("bytes", bytes);
+            if let Some(err) = error {
+                self.span.record("error", tracing::field::display(err));
+            }
+        }
+        Span {
+            operation: self.operation,
+            protocol: protocol.to_string(),
+            host: host.map(str::to_string),
+            path: self.path,
+            bytes,
+            elapsed: self.started.elapsed(),
+            error: error.cloned(),
+        }
+    }
+}
+
+/// Log `span` and pass it to `on_span`.
+/// Takes the fields of `TracedFs` apart, so that it can be called while the inner file system is borrowed
+/// (e.g. by the iterator returned by `read_lines`)
+fn report(span: &Span, on_span: Option<&mut SpanCallback>) {
+    let path = span
+        .path
+        .as_deref()
+        .map(|x| x.display().to_string())
+        .unwrap_or_default();
+    let host = span.host.as_deref().unwrap_or_default();
+    match span.error.as_ref() {
+        Some(err) => warn!(
+            target: LOG_TARGET,
+            "{} {} {} {} failed after {:?}: {}",
+            span.protocol,
+            host,
+            span.operation,
+            path,
+            span.elapsed,
+            err
+        ),
+        None => debug!(
+            target: LOG_TARGET,
+            "{} {} {} {} took {:?} ({} bytes)",
+            span.protocol,
+            host,
+            span.operation,
+            path,
+            span.elapsed,
+            span.bytes.unwrap_or(0)
+        ),
+    }
+    if let Some(on_span) = on_span {
+        on_span(span);
+    }
+}
+
+/// Bytes transferred by a call returning no amount of bytes
+fn no_bytes<R>(_: &RemoteResult<R>) -> Option<u64> {
+    None
+}
+
+/// Bytes transferred by a call returning the amount of bytes
+fn copied_bytes(result: &RemoteResult<u64>) -> Option<u64> {
+    result.as_ref().ok().copied()
+}
+
+/// Bytes transferred by a call returning a `WriteReport`
+fn reported_bytes(result: &RemoteResult<WriteReport>) -> Option<u64> {
+    result.as_ref().ok().map(|x| x.bytes)
+}
+
+/// Forward a method to the inner file system, reporting a span for the call on the first path it operates on
+macro_rules! forward_traced {
+    (@plain $this:ident, $method:ident, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            $call
+        }
+    };
+    (@bytes $bytes:ident, $this:ident, $method:ident, [$($path:ident),*], $cmd:tt, $sig:tt -> $ret:ty, $call:expr) => {
+        fn $method $sig -> $ret {
+            let paths: &[&Path] = &[$($path),*];
+            let call = $this.start(stringify!($method), paths.first().copied());
+            let result = $call;
+            let span = call.end(
+                &$this.protocol,
+                $this.host.as_deref(),
+                $bytes(&result),
+                result.as_ref().err(),
+            );
+            report(&span, $this.on_span.as_mut());
+            result
+        }
+    };
+    ($this:ident, on_written_ext, $($rest:tt)*) => {
+        forward_traced!(@bytes reported_bytes, $this, on_written_ext, $($rest)*);
+    };
+    ($this:ident, append_file, $($rest:tt)*) => {
+        forward_traced!(@bytes copied_bytes, $this, append_file, $($rest)*);
+    };
+    ($this:ident, resume_upload, $($rest:tt)*) => {
+        forward_traced!(@bytes copied_bytes, $this, resume_upload, $($rest)*);
+    };
+    ($this:ident, create_file, $($rest:tt)*) => {
+        forward_traced!(@bytes copied_bytes, $this, create_file, $($rest)*);
+    };
+    ($this:ident, open_file, $($rest:tt)*) => {
+        forward_traced!(@bytes copied_bytes, $this, open_file, $($rest)*);
+    };
+    ($this:ident, $($rest:tt)*) => {
+        forward_traced!(@bytes no_bytes, $this, $($rest)*);
+    };
+}
+
+impl<T: RemoteFs> RemoteFs for TracedFs<T> {
+    forward_remote_fs!(forward_traced);
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::fs::{Metadata, WalkDirOpts};
+    use crate::mock::MemoryFs;
+    use crate::RemoteErrorType;
+
+    #[test]
+    fn should_report_spans() {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let reported = spans.clone();
+        let mut client = TracedFs::new(MemoryFs::connected().with_dir("/home"), "memory")
+            .host("localhost")
+            .on_span(move |span| reported.lock().unwrap().push(span.clone()));
+        assert_eq!(
+            client
+                .create_file(
+                    Path::new("/home/a.txt"),
+                    &Metadata::default(),
+                    Box::new(Cursor::new(b"hello".to_vec())),
+                )
+                .unwrap(),
+            5
+        );
+        assert!(client.stat(Path::new("/home/b.txt")).is_err());
+        let spans = spans.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].operation, "create_file");
+        assert_eq!(spans[0].protocol, "memory");
+        assert_eq!(spans[0].host.as_deref(), Some("localhost"));
+        assert_eq!(spans[0].path.as_deref(), Some(Path::new("/home/a.txt")));
+        assert_eq!(spans[0].bytes, Some(5));
+        assert!(spans[0].error.is_none());
+        assert_eq!(spans[1].operation, "stat");
+        assert_eq!(spans[1].bytes, None);
+        assert_eq!(
+            spans[1].error.as_ref().map(|x| x.kind),
+            Some(RemoteErrorType::NoSuchFileOrDirectory)
+        );
+    }
+
+    #[test]
+    fn should_report_compound_operations_once() {
+        let spans = Arc::new(Mutex::new(Vec::new()));
+        let reported = spans.clone();
+        let mut client = TracedFs::new(
+            MemoryFs::connected()
+                .with_dir("/home")
+                .with_file("/home/a.txt", b"hello"),
+            "memory",
+        )
+        .on_span(move |span| reported.lock().unwrap().push(span.clone()));
+        assert_eq!(
+            client
+                .walk_dir(Path::new("/home"), &WalkDirOpts::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        let spans = spans.lock().unwrap();
+        let operations: Vec<_> = spans.iter().map(|x| x.operation).collect();
+        assert_eq!(operations, vec!["walk_dir"]);
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn should_enter_tracing_spans() {
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata as TracingMetadata, Subscriber};
+
+        /// Collects the fields of the spans as `name=value`
+        #[derive(Clone, Default)]
+        struct Collector(Arc<Mutex<Vec<String>>>);
+
+        impl Visit for Collector {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("{}={:?}", field.name(), value));
+            }
+        }
+
+        impl Subscriber for Collector {
+            fn enabled(&self, _: &TracingMetadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("span {}", span.metadata().name()));
+                span.record(&mut self.clone());
+                Id::from_u64(1)
+            }
+
+            fn record(&self, _: &Id, values: &Record<'_>) {
+                values.record(&mut self.clone());
+            }
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, _: &Event<'_>) {}
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let collector = Collector::default();
+        let fields = collector.0.clone();
+        let mut client = TracedFs::new(MemoryFs::connected().with_dir("/home"), "memory");
+        tracing::subscriber::with_default(collector, || {
+            assert!(client.stat(Path::new("/home/a.txt")).is_err());
+        });
+        let fields = fields.lock().unwrap();
+        assert_eq!(fields[0], "span remotefs");
+        assert!(fields.contains(&"operation=\"stat\"".to_string()));
+        assert!(fields.contains(&"protocol=\"memory\"".to_string()));
+        assert!(fields.contains(&"path=/home/a.txt".to_string()));
+        assert!(fields.iter().any(|x| x.starts_with("error=")));
+    }
+}
@@ -0,0 +1,510 @@
+//! ## Union
+//!
+//! a `RemoteFs` combining multiple file systems as overlay layers
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::fs::{
+    copy_stream, Capabilities, Metadata, ReadStream, Semantics, TransferOpts, UnixPex, Welcome,
+    WriteStream,
+};
+use crate::utils::path::{absolutize, normalize};
+use crate::{File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult};
+
+/// A `RemoteFs` combining multiple file systems as layers with precedence, as an overlay file system does
+/// (e.g. a base release with per-customer overrides stored on different servers).
+///
+/// - reads go through the layers, from the top one down, until the path is found
+/// - `list_dir` merges the entries of the directory in all the layers; entries of upper layers hide the lower ones
+/// - writes always go to the top layer; files and directories of lower layers are copied up before being modified,
+///   and directories are copied up with all their entries before being copied
+/// - removing entries which exist only in lower layers fails with `PexError`, and so does moving entries which exist
+///   in any lower layer, since they would still be visible there
+///
+/// Streams opened with `open` are tagged with the layer they were opened on, so they can be finalized in any order
+pub struct UnionFs {
+    layers: Vec<Box<dyn RemoteFs>>,
+    wrkdir: PathBuf,
+}
+
+/// The tag of the streams opened on a layer
+struct LayerTag(usize);
+
+impl UnionFs {
+    /// Instantiates a new `UnionFs` with `top` as the top layer, where writes go
+    pub fn new(top: Box<dyn RemoteFs>) -> Self {
+        Self {
+            layers: vec![top],
+            wrkdir: PathBuf::from("/"),
+        }
+    }
+
+    /// Add a layer below the existing ones
+    pub fn layer(mut self, layer: Box<dyn RemoteFs>) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    /// Get a reference to the layers, from the top one down
+    pub fn layers(&self) -> &[Box<dyn RemoteFs>] {
+        &self.layers
+    }
+
+    /// Consume the union, returning its layers
+    pub fn into_layers(self) -> Vec<Box<dyn RemoteFs>> {
+        self.layers
+    }
+
+    /// Get the absolute, normalized path of `path`
+    fn resolve(&self, path: &Path) -> PathBuf {
+        normalize(absolutize(self.wrkdir.as_path(), path).as_path())
+    }
+
+    /// Find the topmost layer containing `path`
+    fn find(&mut self, path: &Path) -> RemoteResult<(usize, File)> {
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            match layer.stat(path) {
+                Ok(file) => return Ok((i, file)),
+                Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory).with_path(path))
+    }
+
+    /// Make sure `path` exists in the top layer, copying it up from a lower layer if necessary.
+    /// Returns whether the path exists
+    fn copy_up(&mut self, path: &Path) -> RemoteResult<bool> {
+        let (layer, file) = match self.find(path) {
+            Ok(found) => found,
+            Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        if layer == 0 {
+            return Ok(true);
+        }
+        debug!("Copying up {} from layer {}", path.display(), layer);
+        self.create_parents(path)?;
+        if file.is_dir() {
+            let mode = file.metadata().mode.unwrap_or_else(|| UnixPex::from(0o755));
+            self.layers[0].create_dir(path, mode)?;
+        } else if let Some(target) = file.metadata().symlink.as_deref() {
+            self.layers[0].symlink(path, target)?;
+        } else {
+            let mut reader = self.layers[layer].open(path)?;
+            let mut writer = self.layers[0].create(path, file.metadata())?;
+            copy_stream(&mut reader, &mut writer, &TransferOpts::default())?;
+            self.layers[layer].on_read(reader)?;
+            self.layers[0].on_written(writer)?;
+        }
+        Ok(true)
+    }
+
+    /// Make sure `path` exists in the top layer as `copy_up` does and, if it's a directory,
+    /// copy up all its entries recursively.
+    /// Returns whether the path exists
+    fn copy_up_tree(&mut self, path: &Path) -> RemoteResult<bool> {
+        if !self.copy_up(path)? {
+            return Ok(false);
+        }
+        if self.layers[0].stat(path)?.is_dir() {
+            for entry in self.list_dir(path)? {
+                self.copy_up_tree(entry.path())?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Make sure the parent directories of `path` exist in the top layer
+    fn create_parents(&mut self, path: &Path) -> RemoteResult<()> {
+        match path.parent() {
+            Some(parent) if parent != Path::new("/") && !parent.as_os_str().is_empty() => {
+                if self.copy_up(parent)? {
+                    Ok(())
+                } else {
+                    Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory).with_path(parent))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that `path` is in the top layer, since entries of lower layers can't be removed
+    fn check_top(&mut self, path: &Path) -> RemoteResult<()> {
+        match self.find(path)? {
+            (0, _) => Ok(()),
+            (layer, _) => {
+                error!("{} is in read-only layer {}", path.display(), layer);
+                Err(
+                    RemoteError::new_ex(RemoteErrorType::PexError, "read-only layer")
+                        .with_path(path),
+                )
+            }
+        }
+    }
+
+    /// Check that `path` is in the top layer only, since entries of lower layers can't be moved
+    fn check_top_only(&mut self, path: &Path) -> RemoteResult<()> {
+        self.check_top(path)?;
+        for (i, layer) in self.layers.iter_mut().enumerate().skip(1) {
+            if layer.exists(path)? {
+                error!("{} is also in read-only layer {}", path.display(), i);
+                return Err(
+                    RemoteError::new_ex(RemoteErrorType::PexError, "read-only layer")
+                        .with_path(path),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RemoteFs for UnionFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let mut welcome = Welcome::default();
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            let layer_welcome = layer.connect()?;
+            if i == 0 {
+                welcome = layer_welcome;
+            }
+        }
+        self.wrkdir = self.layers[0].pwd()?;
+        Ok(welcome)
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        let mut result = Ok(());
+        for layer in self.layers.iter_mut() {
+            if let Err(err) = layer.disconnect() {
+                warn!("Failed to disconnect layer: {}", err);
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.layers.iter_mut().all(|x| x.is_connected())
+    }
+
+    fn capabilities(&mut self) -> Capabilities {
+        self.layers[0].capabilities()
+    }
+
+    fn semantics(&self) -> Semantics {
+        self.layers[0].semantics()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        if self.is_connected() {
+            Ok(self.wrkdir.clone())
+        } else {
+            Err(RemoteError::new(RemoteErrorType::NotConnected))
+        }
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let dir = self.resolve(dir);
+        match self.find(dir.as_path())? {
+            (_, file) if file.is_dir() => {
+                self.wrkdir = dir.clone();
+                Ok(dir)
+            }
+            _ => Err(
+                RemoteError::new_ex(RemoteErrorType::BadFile, "not a directory")
+                    .with_op("change_dir")
+                    .with_path(dir),
+            ),
+        }
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let path = self.resolve(path);
+        let mut found = false;
+        let mut names = HashSet::new();
+        let mut entries = Vec::new();
+        for layer in self.layers.iter_mut() {
+            match layer.list_dir(path.as_path()) {
+                Ok(files) => {
+                    found = true;
+                    entries.extend(files.into_iter().filter(|x| names.insert(x.name())));
+                }
+                Err(err) if err.kind == RemoteErrorType::NoSuchFileOrDirectory => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        match found {
+            true => Ok(entries),
+            false => Err(RemoteError::new(RemoteErrorType::NoSuchFileOrDirectory)
+                .with_op("list_dir")
+                .with_path(path)),
+        }
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let path = self.resolve(path);
+        self.find(path.as_path()).map(|(_, file)| file)
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let path = self.resolve(path);
+        self.copy_up(path.as_path())?;
+        self.layers[0].setstat(path.as_path(), metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        let path = self.resolve(path);
+        for layer in self.layers.iter_mut() {
+            if layer.exists(path.as_path())? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = self.resolve(path);
+        self.check_top(path.as_path())?;
+        self.layers[0].remove_file(path.as_path())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = self.resolve(path);
+        self.check_top(path.as_path())?;
+        self.layers[0].remove_dir(path.as_path())
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        let path = self.resolve(path);
+        if self.exists(path.as_path())? {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists)
+                .with_op("create_dir")
+                .with_path(path));
+        }
+        self.create_parents(path.as_path())?;
+        self.layers[0].create_dir(path.as_path(), mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        let path = self.resolve(path);
+        self.create_parents(path.as_path())?;
+        self.layers[0].symlink(path.as_path(), target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let src = self.resolve(src);
+        let dest = self.resolve(dest);
+        self.copy_up_tree(src.as_path())?;
+        self.create_parents(dest.as_path())?;
+        self.layers[0].copy(src.as_path(), dest.as_path())
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        let src = self.resolve(src);
+        let dest = self.resolve(dest);
+        self.check_top_only(src.as_path())?;
+        self.create_parents(dest.as_path())?;
+        self.layers[0].mov(src.as_path(), dest.as_path())
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.layers[0].exec(cmd)
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let path = self.resolve(path);
+        if !self.copy_up(path.as_path())? {
+            self.create_parents(path.as_path())?;
+        }
+        self.layers[0].append(path.as_path(), metadata)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let path = self.resolve(path);
+        self.create_parents(path.as_path())?;
+        self.layers[0].create(path.as_path(), metadata)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let path = self.resolve(path);
+        let (layer, _) = self.find(path.as_path())?;
+        let stream = self.layers[layer].open(path.as_path())?;
+        Ok(stream.tag(LayerTag(layer)))
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        self.layers[0].on_written(writable)
+    }
+
+    fn on_read(&mut self, mut readable: ReadStream) -> RemoteResult<()> {
+        match readable.take_tag::<LayerTag>() {
+            Some(LayerTag(layer)) => self.layers[layer].on_read(readable),
+            None => Err(RemoteError::new_ex(
+                RemoteErrorType::FinalizeFailed,
+                "the stream wasn't opened by this file system",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::mock::MemoryFs;
+
+    fn union() -> UnionFs {
+        let base = MemoryFs::connected()
+            .with_dir("/app")
+            .with_file("/app/config.toml", b"base")
+            .with_file("/app/logo.png", b"logo")
+            .with_dir("/app/assets")
+            .with_file("/app/assets/style.css", b"css");
+        let overrides = MemoryFs::connected()
+            .with_dir("/app")
+            .with_file("/app/config.toml", b"customer");
+        UnionFs::new(Box::new(overrides)).layer(Box::new(base))
+    }
+
+    #[test]
+    fn should_read_through_layers() {
+        let mut fs = union();
+        assert_eq!(fs.layers().len(), 2);
+        let mut names: Vec<String> = fs
+            .list_dir(Path::new("/app"))
+            .unwrap()
+            .into_iter()
+            .map(|x| x.name())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["assets", "config.toml", "logo.png"]);
+        assert_eq!(
+            fs.open_file(Path::new("/app/config.toml"), Box::new(io::sink()))
+                .unwrap(),
+            8
+        );
+        let mut content = Vec::new();
+        let mut stream = fs.open(Path::new("/app/logo.png")).unwrap();
+        io::copy(&mut stream, &mut content).unwrap();
+        fs.on_read(stream).unwrap();
+        assert_eq!(content, b"logo");
+        assert_eq!(
+            fs.stat(Path::new("/app/config.toml"))
+                .unwrap()
+                .metadata
+                .size,
+            8
+        );
+        assert_eq!(fs.exists(Path::new("/app/assets/style.css")).unwrap(), true);
+        assert_eq!(fs.exists(Path::new("/app/missing")).unwrap(), false);
+        assert_eq!(
+            fs.list_dir(Path::new("/missing")).unwrap_err().kind,
+            RemoteErrorType::NoSuchFileOrDirectory
+        );
+    }
+
+    #[test]
+    fn should_write_to_top_layer() {
+        let mut fs = union();
+        fs.change_dir(Path::new("/app/assets")).unwrap();
+        let mut stream = fs
+            .append(Path::new("style.css"), &Metadata::default())
+            .unwrap();
+        io::Write::write_all(&mut stream, b"+override").unwrap();
+        fs.on_written(stream).unwrap();
+        let layers = fs.into_layers();
+        let mut fs = UnionFs::new(layers.into_iter().next().unwrap());
+        let mut content = Vec::new();
+        let mut stream = fs.open(Path::new("/app/assets/style.css")).unwrap();
+        io::copy(&mut stream, &mut content).unwrap();
+        fs.on_read(stream).unwrap();
+        assert_eq!(content, b"css+override");
+    }
+
+    #[test]
+    fn should_not_remove_from_lower_layers() {
+        let mut fs = union();
+        assert_eq!(
+            fs.remove_file(Path::new("/app/logo.png")).unwrap_err().kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            fs.mov(Path::new("/app/logo.png"), Path::new("/app/a.png"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert!(fs.remove_file(Path::new("/app/config.toml")).is_ok());
+        // the base one is visible again
+        assert_eq!(
+            fs.stat(Path::new("/app/config.toml"))
+                .unwrap()
+                .metadata
+                .size,
+            4
+        );
+    }
+
+    #[test]
+    fn should_not_move_entries_of_lower_layers() {
+        let mut fs = union();
+        assert_eq!(
+            fs.mov(Path::new("/app"), Path::new("/moved"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(
+            fs.mov(Path::new("/app/config.toml"), Path::new("/app/a.toml"))
+                .unwrap_err()
+                .kind,
+            RemoteErrorType::PexError
+        );
+        assert_eq!(fs.exists(Path::new("/moved")).unwrap(), false);
+        assert_eq!(fs.exists(Path::new("/app/a.toml")).unwrap(), false);
+        assert_eq!(fs.list_dir(Path::new("/app")).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn should_move_entries_of_top_layer() {
+        let mut fs = union();
+        fs.create_dir(Path::new("/app/plugins"), UnixPex::from(0o755))
+            .unwrap();
+        fs.create_file(
+            Path::new("/app/plugins/a.so"),
+            &Metadata::default(),
+            Box::new(&b"so"[..]),
+        )
+        .unwrap();
+        fs.mov(Path::new("/app/plugins"), Path::new("/plugins"))
+            .unwrap();
+        assert_eq!(fs.exists(Path::new("/app/plugins")).unwrap(), false);
+        assert_eq!(fs.exists(Path::new("/plugins/a.so")).unwrap(), true);
+        let names: Vec<String> = fs
+            .list_dir(Path::new("/plugins"))
+            .unwrap()
+            .into_iter()
+            .map(|x| x.name())
+            .collect();
+        assert_eq!(names, vec!["a.so"]);
+    }
+
+    #[test]
+    fn should_finalize_streams_on_their_layer() {
+        let mut fs = union();
+        let base = fs.open(Path::new("/app/logo.png")).unwrap();
+        let top = fs.open(Path::new("/app/config.toml")).unwrap();
+        assert!(fs.on_read(top).is_ok());
+        assert!(fs.on_read(base).is_ok());
+        let stream = ReadStream::from(Box::new(io::empty()) as Box<dyn io::Read + Send>);
+        assert_eq!(
+            fs.on_read(stream).unwrap_err().kind,
+            RemoteErrorType::FinalizeFailed
+        );
+    }
+}